@@ -0,0 +1 @@
+ALTER TABLE HazelUser DROP COLUMN admin;
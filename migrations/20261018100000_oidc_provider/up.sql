@@ -0,0 +1 @@
+ALTER TABLE HazelUser ADD COLUMN admin BOOLEAN NOT NULL DEFAULT 'false';
@@ -0,0 +1,3 @@
+DROP TRIGGER Audit_Event_append_only ON Audit_Event;
+DROP FUNCTION audit_event_append_only();
+DROP TABLE Audit_Event;
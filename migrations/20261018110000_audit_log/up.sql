@@ -0,0 +1,24 @@
+CREATE TABLE Audit_Event (
+    id BIGSERIAL NOT NULL,
+    occurred TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target_id TEXT NULL,
+    target_version TEXT NULL,
+    source_ip TEXT NULL,
+    before_metadata TEXT NULL,
+    after_metadata TEXT NULL,
+    PRIMARY KEY(id)
+);
+
+CREATE INDEX Audit_Event_actor ON Audit_Event (actor);
+CREATE INDEX Audit_Event_target ON Audit_Event (target_id, target_version);
+
+CREATE FUNCTION audit_event_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'Audit_Event is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER Audit_Event_append_only BEFORE UPDATE OR DELETE ON Audit_Event
+    FOR EACH ROW EXECUTE PROCEDURE audit_event_append_only();
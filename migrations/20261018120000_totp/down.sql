@@ -0,0 +1,3 @@
+ALTER TABLE HazelUser DROP COLUMN totp_last_step;
+ALTER TABLE HazelUser DROP COLUMN totp_recovery_codes;
+ALTER TABLE HazelUser DROP COLUMN totp_secret;
@@ -0,0 +1,3 @@
+ALTER TABLE HazelUser ADD COLUMN totp_secret TEXT NULL;
+ALTER TABLE HazelUser ADD COLUMN totp_recovery_codes TEXT NULL;
+ALTER TABLE HazelUser ADD COLUMN totp_last_step BIGINT NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE PackageVersion DROP COLUMN signer_fingerprint;
+ALTER TABLE PackageVersion DROP COLUMN signer;
+ALTER TABLE PackageVersion DROP COLUMN signature_type;
@@ -0,0 +1,3 @@
+ALTER TABLE PackageVersion ADD COLUMN signature_type TEXT NULL;
+ALTER TABLE PackageVersion ADD COLUMN signer TEXT NULL;
+ALTER TABLE PackageVersion ADD COLUMN signer_fingerprint TEXT NULL;
@@ -0,0 +1,2 @@
+DROP TABLE Webhook_Delivery;
+DROP TABLE Webhook;
@@ -0,0 +1,32 @@
+CREATE TABLE Webhook (
+    id BIGSERIAL NOT NULL,
+    package_id TEXT NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NULL,
+    events TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    PRIMARY KEY(id)
+);
+
+CREATE INDEX Webhook_package ON Webhook (package_id);
+
+CREATE TABLE Webhook_Delivery (
+    id BIGSERIAL NOT NULL,
+    webhook_id BIGINT NULL,
+    event TEXT NOT NULL,
+    package_id TEXT NOT NULL,
+    package_version TEXT NULL,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    signature TEXT NULL,
+    attempts INTEGER NOT NULL,
+    next_attempt TIMESTAMP WITHOUT TIME ZONE NULL,
+    delivered BOOLEAN NOT NULL,
+    response_status INTEGER NULL,
+    last_error TEXT NULL,
+    created TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    PRIMARY KEY(id)
+);
+
+CREATE INDEX Webhook_Delivery_package ON Webhook_Delivery (package_id);
+CREATE INDEX Webhook_Delivery_next_attempt ON Webhook_Delivery (next_attempt);
@@ -0,0 +1,2 @@
+ALTER TABLE PackageVersion DROP COLUMN scan_result;
+ALTER TABLE PackageVersion DROP COLUMN scan_status;
@@ -0,0 +1,2 @@
+ALTER TABLE PackageVersion ADD COLUMN scan_status TEXT NULL;
+ALTER TABLE PackageVersion ADD COLUMN scan_result TEXT NULL;
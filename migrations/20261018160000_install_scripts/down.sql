@@ -0,0 +1 @@
+DROP TABLE Install_Script;
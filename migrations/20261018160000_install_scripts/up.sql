@@ -0,0 +1,8 @@
+CREATE TABLE Install_Script (
+    id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    name TEXT NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY(id, version, name),
+    FOREIGN KEY(id, version) REFERENCES PackageVersion(id, version) ON DELETE CASCADE
+);
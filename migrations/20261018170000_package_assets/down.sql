@@ -0,0 +1 @@
+DROP TABLE Package_Asset;
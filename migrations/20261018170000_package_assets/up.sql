@@ -0,0 +1,10 @@
+CREATE TABLE Package_Asset (
+    id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    media_type TEXT NULL,
+    content BYTEA NULL,
+    PRIMARY KEY(id, version, kind),
+    FOREIGN KEY(id, version) REFERENCES PackageVersion(id, version) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+ALTER TABLE PackageVersion DROP COLUMN original_version;
@@ -0,0 +1 @@
+ALTER TABLE PackageVersion ADD COLUMN original_version TEXT NULL;
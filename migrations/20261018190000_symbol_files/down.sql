@@ -0,0 +1 @@
+DROP TABLE Symbol_File;
@@ -0,0 +1,11 @@
+CREATE TABLE Symbol_File (
+    id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    path TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    PRIMARY KEY(id, version, path),
+    FOREIGN KEY(id, version) REFERENCES PackageVersion(id, version) ON DELETE CASCADE
+);
+CREATE INDEX Symbol_File_key ON Symbol_File (file_name, signature);
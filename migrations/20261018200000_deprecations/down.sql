@@ -0,0 +1,2 @@
+DROP TABLE Advisory;
+DROP TABLE Deprecation;
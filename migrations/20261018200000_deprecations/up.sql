@@ -0,0 +1,18 @@
+CREATE TABLE Deprecation (
+    id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    reasons TEXT NOT NULL,
+    message TEXT NULL,
+    alternate_id TEXT NULL,
+    alternate_range TEXT NULL,
+    PRIMARY KEY(id, version),
+    FOREIGN KEY(id, version) REFERENCES PackageVersion(id, version) ON DELETE CASCADE
+);
+CREATE TABLE Advisory (
+    id TEXT NOT NULL,
+    version TEXT NOT NULL,
+    url TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    PRIMARY KEY(id, version, url),
+    FOREIGN KEY(id, version) REFERENCES PackageVersion(id, version) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+ALTER TABLE Webhook DROP CONSTRAINT Webhook_package_fkey;
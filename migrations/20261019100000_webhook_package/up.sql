@@ -0,0 +1,3 @@
+DELETE FROM Webhook_Delivery WHERE webhook_id IN (SELECT id FROM Webhook WHERE package_id NOT IN (SELECT id FROM Package));
+DELETE FROM Webhook WHERE package_id NOT IN (SELECT id FROM Package);
+ALTER TABLE Webhook ADD CONSTRAINT Webhook_package_fkey FOREIGN KEY(package_id) REFERENCES Package(id) ON DELETE CASCADE;
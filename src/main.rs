@@ -42,7 +42,6 @@ extern crate rustc_serialize;
 extern crate lazysort;
 extern crate bcrypt;
 extern crate uuid;
-extern crate toml_config;
 extern crate toml;
 extern crate cldap;
 extern crate rand;
@@ -52,6 +51,9 @@ extern crate rand;
 #[macro_use] extern crate log;
 extern crate simplelog;
 extern crate lettre;
+extern crate libc;
+extern crate tar;
+extern crate pulldown_cmark;
 
 #[macro_use] pub mod utils;
 pub mod web;
@@ -59,42 +61,26 @@ pub mod web;
 use diesel::migrations;
 use diesel::pg::PgConnection;
 use r2d2_diesel::ConnectionManager;
-use simplelog::{TermLogger, SimpleLogger, FileLogger, CombinedLogger, LogLevelFilter, SharedLogger};
 
 use web::server;
-use web::backend::Storage;
-use web::backend::db::User;
+use web::backend::{assets, retention, scanner, webhook, Storage};
+use web::backend::db::{User, PackageVersion, export_backup, import_backup};
 
-use utils::CONFIG;
+use utils::{config, logging, CONFIG};
+use utils::config::Command;
+use utils::error::BackendError;
 
-use std::cmp;
 use std::fs::File;
 use std::io;
 use std::path::PathBuf;
+use std::process;
 
 #[allow(dead_code)]
 fn main() {
 
-    let verbosity = match CONFIG.log.verbosity {
-        0 => LogLevelFilter::Error,
-        1 => LogLevelFilter::Warn,
-        2 => LogLevelFilter::Info,
-        3 => LogLevelFilter::Debug,
-        4 | _ => LogLevelFilter::Trace,
-    };
-
-    let mut logger: Vec<Box<SharedLogger>> = vec![];
-    if !CONFIG.log.quiet {
-        logger.push(match TermLogger::new(verbosity) {
-            Some(termlogger) => termlogger,
-            None => SimpleLogger::new(verbosity),
-        });
-    }
-    match CONFIG.log.logfile {
-        Some(ref path) => logger.push(FileLogger::new(cmp::max(verbosity, LogLevelFilter::Info), File::create(path).unwrap())),
-        None => {},
-    }
-    CombinedLogger::init(logger).unwrap();
+    let logfile = CONFIG.log.logfile.as_ref().map(|path| File::create(path).unwrap());
+    logging::init(CONFIG.log.verbosity, CONFIG.log.quiet, logfile).unwrap();
+    config::reload_on_sighup();
 
     let config = r2d2::Config::default();
     let manager = ConnectionManager::<PgConnection>::new(CONFIG.backend.db_url.clone());
@@ -103,11 +89,57 @@ fn main() {
     //run migrations
     migrations::run_pending_migrations_in_directory(&*pool.get().unwrap(), &*PathBuf::from(&*CONFIG.backend.migrations), &mut io::stdout()).unwrap();
 
+    //versions stored before NuGet versions were supported
+    match PackageVersion::normalize_stored_versions(&*pool.get().unwrap(), &Storage::new(PathBuf::from(&*CONFIG.backend.storage))) {
+        Ok(0) => {},
+        Ok(renamed) => info!("Normalized {} stored versions", renamed),
+        Err(err) => error!("Normalizing stored versions failed: {}", err),
+    }
+
+    if let Some(command) = config::command() {
+        let connection = pool.get().unwrap();
+        let storage = Storage::new(PathBuf::from(&*CONFIG.backend.storage));
+        let result = match command {
+            Command::Export { ref file, secrets } => File::create(file).map_err(BackendError::from)
+                .and_then(|archive| export_backup(&*connection, &storage, io::BufWriter::new(archive), secrets)),
+            Command::Import { ref file } => File::open(file).map_err(BackendError::from)
+                .and_then(|archive| import_backup(&*connection, &storage, io::BufReader::new(archive))),
+            Command::Rescan { quarantined_only } => match scanner::configured() {
+                Some(scanner) => scanner::rescan(&*connection, &storage, &*scanner, quarantined_only)
+                    .map(|(clean, infected, failed)| info!("{} clean, {} infected, {} could not be scanned", clean, infected, failed)),
+                None => {
+                    error!("No malware scanner configured");
+                    process::exit(1);
+                },
+            },
+            Command::Retention { dry_run } => match CONFIG.backend.retention {
+                Some(ref retention) => retention::apply(&*connection, &storage, retention, dry_run)
+                    .map(|count| info!("{} versions {}", count, if dry_run { "would be deleted" } else { "deleted" })),
+                None => {
+                    error!("No retention rules configured");
+                    process::exit(1);
+                },
+            },
+        };
+        match result {
+            Ok(()) => process::exit(0),
+            Err(err) => {
+                error!("{}", err);
+                process::exit(1);
+            },
+        }
+    }
+
     {
         let connection = pool.get().unwrap();
         User::ensure_admin(&*connection, CONFIG.auth.superuser_password.clone()).unwrap();
     }
 
+    retention::schedule(pool.clone(), Storage::new(PathBuf::from(&*CONFIG.backend.storage)));
+    scanner::schedule(pool.clone(), Storage::new(PathBuf::from(&*CONFIG.backend.storage)));
+    webhook::schedule(pool.clone());
+    assets::schedule(pool.clone());
+
     let _iron = server::start(pool, Storage::new(PathBuf::from(&*CONFIG.backend.storage)));
 
     // TODO server console if we want
@@ -22,7 +22,11 @@ mod backend_error {
     use super::XmlError;
     use super::LoginError;
     use super::LDAPError;
+    use super::OIDCError;
     use super::MailError;
+    use super::SignatureError;
+    use super::BackupError;
+    use super::ValidationProblem;
     use treexml::Error as XmlParseError;
     use semver::{SemVerError, ReqParseError};
     use bcrypt::BcryptError;
@@ -63,6 +67,7 @@ mod backend_error {
                 from()
                 from(e: BcryptError) -> (e.into())
                 from(e: LDAPError) -> (e.into())
+                from(e: OIDCError) -> (e.into())
             }
             PermissionDenied {
                 display("Permission Denied")
@@ -79,6 +84,24 @@ mod backend_error {
             MailError(err: MailError) {
                 from()
             }
+            SignatureError(err: SignatureError) {
+                from()
+            }
+            BackupError(err: BackupError) {
+                from()
+            }
+            QuotaExceeded(err: Cow<'static, str>) {
+                display("Storage quota exceeded: {}", &**err)
+            }
+            Infected(signature: String) {
+                display("Rejected by the malware scanner: {}", signature)
+            }
+            ValidationFailed(problems: Vec<ValidationProblem>) {
+                display("Invalid package: {}", problems.iter().map(|problem| format!("{}", problem)).collect::<Vec<_>>().join("; "))
+            }
+            EntryTooLarge(name: String) {
+                display("\"{}\" is too large to be read from the package", name)
+            }
         }
     }
 }
@@ -134,6 +157,8 @@ mod version_error {
         MultiPredicate,
         InvalidLowerBoundOp,
         InvalidUpperBoundOp,
+        InvalidVersion,
+        InvalidRange,
     }
 
     impl Error for NugetVersionError
@@ -144,6 +169,8 @@ mod version_error {
                 &NugetVersionError::MultiPredicate => "More Predicates then 2 are not allowed in Nuget Version Range Specifications",
                 &NugetVersionError::InvalidLowerBoundOp => "When using two Predicates, the first must be a lower bound (> or >=)",
                 &NugetVersionError::InvalidUpperBoundOp => "When using two Predicates, the second must be an upper bound (< or <=)",
+                &NugetVersionError::InvalidVersion => "Versions consist of up to four numbers, an optional prerelease label and optional build metadata",
+                &NugetVersionError::InvalidRange => "Version ranges are a minimum version or bounds in brackets, like \"[1.0,2.0)\"",
             }
         }
     }
@@ -160,6 +187,7 @@ mod login_error {
     use std::borrow::Cow;
     use bcrypt::BcryptError;
     use super::LDAPError;
+    use super::OIDCError;
 
     quick_error! {
         #[derive(Debug)]
@@ -176,6 +204,12 @@ mod login_error {
             LDAPError(err: LDAPError) {
                 from()
             }
+            OIDCError(err: OIDCError) {
+                from()
+            }
+            InvalidSecondFactor {
+                display("Invalid second factor code")
+            }
         }
     }
 }
@@ -204,12 +238,131 @@ mod ldap_error {
     }
 }
 
+mod oidc_error {
+    use std::borrow::Cow;
+    use std::io;
+    use hyper::error::Error as HttpError;
+
+    quick_error! {
+        #[derive(Debug)]
+        pub enum OIDCError {
+            NotConfigured {
+                display("OpenID Connect not configured")
+            }
+            UnknownState {
+                display("Unknown or expired login attempt")
+            }
+            GroupNotAllowed {
+                display("User is not member of any allowed group")
+            }
+            HttpError(err: HttpError) {
+                from()
+            }
+            IoError(err: io::Error) {
+                from()
+            }
+            InvalidResponse(err: Cow<'static, str>) {
+                display("Invalid response from identity provider: {}", &**err)
+                from (s: &'static str) -> (s.into())
+                from (s: String) -> (s.into())
+            }
+            InvalidToken(err: Cow<'static, str>) {
+                display("Invalid id token: {}", &**err)
+            }
+        }
+    }
+}
+
+mod signature_error {
+    use std::borrow::Cow;
+    use std::io;
+
+    quick_error! {
+        #[derive(Debug)]
+        pub enum SignatureError {
+            Invalid(err: Cow<'static, str>) {
+                display("Invalid package signature: {}", &**err)
+                from (s: &'static str) -> (s.into())
+                from (s: String) -> (s.into())
+            }
+            Untrusted(fingerprint: String) {
+                display("Package is signed by an untrusted certificate ({})", fingerprint)
+            }
+            Missing {
+                display("Packages with this id have to be signed")
+            }
+            OpenSSLError(err: io::Error) {
+                display("Unable to verify the package signature: {}", err)
+                from()
+            }
+        }
+    }
+}
+
+mod backup_error {
+    use std::borrow::Cow;
+
+    quick_error! {
+        #[derive(Debug)]
+        pub enum BackupError {
+            InvalidArchive(err: Cow<'static, str>) {
+                display("Invalid backup archive: {}", &**err)
+                from (s: &'static str) -> (s.into())
+                from (s: String) -> (s.into())
+            }
+            HashMismatch(file: String) {
+                display("Checksum of \"{}\" does not match the manifest", file)
+            }
+            MissingFile(file: String) {
+                display("\"{}\" is listed in the manifest, but missing in the archive", file)
+            }
+            NotEmpty {
+                display("Backups may only be imported into an instance without packages and users")
+            }
+        }
+    }
+}
+
+mod validation_problem {
+    use std::fmt;
+
+    /// A single violated nuspec or archive rule, `location` is the offending tag or archive entry
+    #[derive(Debug, Clone)]
+    pub struct ValidationProblem
+    {
+        pub location: String,
+        pub message: String,
+    }
+
+    impl ValidationProblem
+    {
+        pub fn new<L: Into<String>, M: Into<String>>(location: L, message: M) -> ValidationProblem
+        {
+            ValidationProblem {
+                location: location.into(),
+                message: message.into(),
+            }
+        }
+    }
+
+    impl fmt::Display for ValidationProblem
+    {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}: {}", self.location, self.message)
+        }
+    }
+}
+
 pub use self::backend_error::BackendError;
 pub use self::version_error::NugetVersionError;
 pub use self::mail_error::MailError;
 pub use self::xml_error::XmlError;
 pub use self::login_error::LoginError;
 pub use self::ldap_error::LDAPError;
+pub use self::oidc_error::OIDCError;
+pub use self::signature_error::SignatureError;
+pub use self::backup_error::BackupError;
+pub use self::validation_problem::ValidationProblem;
 
 pub type BackendResult<T> = Result<T, BackendError>;
 
@@ -0,0 +1,84 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use log::{self, Log, LogLevelFilter, LogMetadata, LogRecord, SetLoggerError};
+use simplelog::{TermLogger, SimpleLogger, FileLogger, SharedLogger};
+
+use std::cmp;
+use std::fs::File;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+//changed on reload, so the filtering happens here instead of in the simplelog loggers
+static VERBOSITY: AtomicUsize = ATOMIC_USIZE_INIT;
+
+fn level_filter(verbosity: usize) -> LogLevelFilter {
+    match verbosity {
+        0 => LogLevelFilter::Error,
+        1 => LogLevelFilter::Warn,
+        2 => LogLevelFilter::Info,
+        3 => LogLevelFilter::Debug,
+        4 | _ => LogLevelFilter::Trace,
+    }
+}
+
+pub fn set_verbosity(verbosity: u8) {
+    VERBOSITY.store(verbosity as usize, Ordering::SeqCst);
+}
+
+struct HazelLogger {
+    loggers: Vec<(Box<Log>, LogLevelFilter)>, //logger and the level it always receives
+}
+
+impl HazelLogger {
+    fn accepts(&self, minimum: LogLevelFilter, metadata: &LogMetadata) -> bool {
+        metadata.level() <= cmp::max(level_filter(VERBOSITY.load(Ordering::SeqCst)), minimum)
+    }
+}
+
+impl Log for HazelLogger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        self.loggers.iter().any(|&(ref logger, minimum)| self.accepts(minimum, metadata) && logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &LogRecord) {
+        for &(ref logger, minimum) in self.loggers.iter() {
+            if self.accepts(minimum, record.metadata()) {
+                logger.log(record);
+            }
+        }
+    }
+}
+
+/// Console output follows the verbosity, the logfile receives at least everything from Info upwards
+pub fn init(verbosity: u8, quiet: bool, logfile: Option<File>) -> Result<(), SetLoggerError> {
+    set_verbosity(verbosity);
+
+    let mut loggers: Vec<(Box<Log>, LogLevelFilter)> = vec![];
+    if !quiet {
+        let terminal: Box<SharedLogger> = match TermLogger::new(LogLevelFilter::Trace) {
+            Some(termlogger) => termlogger,
+            None => SimpleLogger::new(LogLevelFilter::Trace),
+        };
+        loggers.push((terminal.as_log(), LogLevelFilter::Off));
+    }
+    if let Some(file) = logfile {
+        loggers.push((FileLogger::new(LogLevelFilter::Trace, file).as_log(), LogLevelFilter::Info));
+    }
+
+    log::set_logger(|max_log_level| {
+        max_log_level.set(LogLevelFilter::Trace);
+        Box::new(HazelLogger { loggers: loggers })
+    })
+}
@@ -0,0 +1,185 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{AroundMiddleware, Handler, Request};
+use iron::method::Method;
+use persistent::Write;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::time::Instant;
+
+use ::web::server::MetricsKey;
+
+const DURATION_BUCKETS: &'static [f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+const SIZE_BUCKETS: &'static [f64] = &[10240.0, 102400.0, 1048576.0, 10485760.0, 104857600.0, 1073741824.0];
+
+pub fn escape_label(value: &str) -> String
+{
+    value.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n")
+}
+
+pub struct Histogram
+{
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram
+{
+    fn new(buckets: &'static [f64]) -> Histogram
+    {
+        Histogram {
+            buckets: buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64)
+    {
+        if let Some(index) = self.buckets.iter().position(|bound| value <= *bound) {
+            self.counts[index] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// `labels` is either empty or a comma terminated list of label pairs
+    fn render(&self, name: &str, labels: &str, out: &mut String)
+    {
+        let mut cumulative = 0;
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter()) {
+            cumulative += *count;
+            let _ = writeln!(out, "{}_bucket{{{}le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}le=\"+Inf\"}} {}", name, labels, self.count);
+        let labels = labels.trim_right_matches(',');
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+/// Counters collected while serving requests, gauges backed by the database are added by the view
+pub struct Metrics
+{
+    requests: BTreeMap<(&'static str, u16), u64>,
+    durations: BTreeMap<&'static str, Histogram>,
+    upload_sizes: Histogram,
+    failed_logins: BTreeMap<&'static str, u64>,
+}
+
+impl Metrics
+{
+    pub fn new() -> Metrics
+    {
+        Metrics {
+            requests: BTreeMap::new(),
+            durations: BTreeMap::new(),
+            upload_sizes: Histogram::new(SIZE_BUCKETS),
+            failed_logins: BTreeMap::new(),
+        }
+    }
+
+    pub fn request(&mut self, route: &'static str, status: u16, seconds: f64)
+    {
+        *self.requests.entry((route, status)).or_insert(0) += 1;
+        self.durations.entry(route).or_insert_with(|| Histogram::new(DURATION_BUCKETS)).observe(seconds);
+    }
+
+    pub fn upload(&mut self, bytes: i64)
+    {
+        self.upload_sizes.observe(bytes as f64);
+    }
+
+    /// `kind` is one of "password", "totp" or "apikey"
+    pub fn failed_login(&mut self, kind: &'static str)
+    {
+        *self.failed_logins.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn render(&self, out: &mut String)
+    {
+        let _ = writeln!(out, "# HELP hazel_http_requests_total Handled requests by route and status code");
+        let _ = writeln!(out, "# TYPE hazel_http_requests_total counter");
+        for (&(route, status), count) in self.requests.iter() {
+            let _ = writeln!(out, "hazel_http_requests_total{{route=\"{}\",status=\"{}\"}} {}", route, status, count);
+        }
+
+        let _ = writeln!(out, "# HELP hazel_http_request_duration_seconds Time spent in the request handler by route");
+        let _ = writeln!(out, "# TYPE hazel_http_request_duration_seconds histogram");
+        for (route, histogram) in self.durations.iter() {
+            histogram.render("hazel_http_request_duration_seconds", &format!("route=\"{}\",", route), out);
+        }
+
+        let _ = writeln!(out, "# HELP hazel_upload_size_bytes Size of successfully uploaded packages");
+        let _ = writeln!(out, "# TYPE hazel_upload_size_bytes histogram");
+        self.upload_sizes.render("hazel_upload_size_bytes", "", out);
+
+        let _ = writeln!(out, "# HELP hazel_failed_logins_total Rejected credentials by kind");
+        let _ = writeln!(out, "# TYPE hazel_failed_logins_total counter");
+        for (kind, count) in self.failed_logins.iter() {
+            let _ = writeln!(out, "hazel_failed_logins_total{{kind=\"{}\"}} {}", kind, count);
+        }
+    }
+}
+
+/// Groups requests into the routes the dashboards are interested in
+fn route(req: &Request) -> &'static str
+{
+    let path = &req.url.path;
+    if path.len() < 2 || path[0] != "api" || path[1] != "v2" {
+        return match path.first() {
+            Some(first) if first == "metrics" => "metrics",
+            _ => "interface",
+        };
+    }
+    match (path.get(2).map(|x| &**x), &req.method) {
+        (Some("package"), &Method::Post) | (Some("package"), &Method::Put) => "upload",
+        (Some("package"), &Method::Delete) => "delete",
+        (Some("package"), _) => "download",
+        (Some("Search"), _) | (Some("Search()"), _) => "search",
+        _ => "feed",
+    }
+}
+
+pub struct MetricsRecorder;
+impl AroundMiddleware for MetricsRecorder
+{
+    fn around(self, handler: Box<Handler>) -> Box<Handler>
+    {
+        Box::new(move |req: &mut Request| {
+            let route = route(req);
+            let started = Instant::now();
+            let result = handler.handle(req);
+            let elapsed = started.elapsed();
+            let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+
+            //iron answers with 404 if no status was set
+            let status = match result {
+                Ok(ref response) => response.status,
+                Err(ref err) => err.response.status,
+            }.map(|status| status.to_u16()).unwrap_or(404);
+
+            if let Some(metrics) = req.extensions.get::<Write<MetricsKey>>() {
+                metrics.lock().unwrap().request(route, status, seconds);
+            }
+            result
+        })
+    }
+}
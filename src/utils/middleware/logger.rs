@@ -13,21 +13,260 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use iron::{BeforeMiddleware, IronError, IronResult, Request};
+use iron::{AroundMiddleware, Handler, Request, Response};
+use iron::headers::{Referer, UserAgent};
+use iron::response::{WriteBody, ResponseBody};
+use chrono::{UTC, DateTime};
+use rustc_serialize::json::{Json, ToJson};
+use url::form_urlencoded;
 
-pub struct Logger;
-impl BeforeMiddleware for Logger
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Instant;
+
+use ::utils::CONFIG;
+use super::Authenticated;
+
+//query parameters containing any of these are never written to the log
+const REDACTED_PARAMS: &'static [&'static str] = &["apikey", "token", "code", "state", "password", "secret"];
+//routes whose parameters are secrets themselves
+const REDACTED_ROUTES: &'static [(&'static str, &'static str)] = &[("GET", "/mail_confirmation/:key")];
+
+fn redact_query(query: &str) -> String
+{
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        let lower = key.to_lowercase();
+        if REDACTED_PARAMS.iter().any(|param| lower.contains(param)) {
+            serializer.append_pair(&key, "REDACTED");
+        } else {
+            serializer.append_pair(&key, &value);
+        }
+    }
+    serializer.finish()
+}
+
+fn redact_path(method: &str, path: &str) -> String
+{
+    let segments: Vec<&str> = path.split('/').collect();
+    for &(route_method, route) in REDACTED_ROUTES.iter() {
+        let pattern: Vec<&str> = route.split('/').collect();
+        if route_method == method && pattern.len() == segments.len() &&
+           pattern.iter().zip(segments.iter()).all(|(expected, segment)| expected.starts_with(':') || expected == segment) {
+            return pattern.iter().zip(segments.iter())
+                .map(|(expected, segment)| if expected.starts_with(':') { "REDACTED" } else { *segment })
+                .collect::<Vec<_>>().join("/");
+        }
+    }
+    String::from(path)
+}
+
+/// Referers are followed links, so their path is redacted like a GET request
+fn redact_url(url: &str) -> String
+{
+    let (url, query) = match url.find('?') {
+        Some(index) => (&url[..index], Some(&url[index + 1..])),
+        None => (url, None),
+    };
+    let path_start = match url.find("://") {
+        Some(index) => url[index + 3..].find('/').map(|path| index + 3 + path).unwrap_or(url.len()),
+        None => 0,
+    };
+    let redacted = format!("{}{}", &url[..path_start], redact_path("GET", &url[path_start..]));
+    match query {
+        Some(query) => format!("{}?{}", redacted, redact_query(query)),
+        None => redacted,
+    }
+}
+
+/// Escapes quotes, backslashes and non-printable bytes as `\xHH` like nginx does,
+/// so header values cannot end their field early or forge log lines
+fn escape(value: &str) -> String
+{
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'"' | b'\\' | 0...0x1f | 0x7f...0xff => escaped.push_str(&format!("\\x{:02X}", byte)),
+            _ => escaped.push(byte as char),
+        }
+    }
+    escaped
+}
+
+struct AccessEntry
+{
+    timestamp: DateTime<UTC>,
+    started: Instant,
+    method: String,
+    path: String,
+    client_ip: String,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    user: Option<String>,
+    status: u16,
+}
+
+impl AccessEntry
+{
+    fn new(req: &Request) -> AccessEntry
+    {
+        AccessEntry {
+            timestamp: UTC::now(),
+            started: Instant::now(),
+            method: format!("{}", req.method),
+            path: {
+                let path = redact_path(&format!("{}", req.method), &format!("/{}", req.url.path.join("/")));
+                match req.url.query {
+                    Some(ref query) => format!("{}?{}", path, redact_query(query)),
+                    None => path,
+                }
+            },
+            client_ip: format!("{}", req.remote_addr.ip()),
+            referer: req.headers.get::<Referer>().map(|referer| redact_url(&referer.0)),
+            user_agent: req.headers.get::<UserAgent>().map(|agent| agent.0.clone()),
+            user: None,
+            status: 404,
+        }
+    }
+
+    /// Defers the log line until the body is written, to know its size and the full duration
+    fn attach(mut self, mut response: Response) -> Response
+    {
+        //iron answers with 404 if no status was set
+        self.status = response.status.map(|status| status.to_u16()).unwrap_or(404);
+        match response.body.take() {
+            Some(body) => response.body = Some(Box::new(LoggedBody {
+                body: body,
+                entry: self,
+                bytes: 0,
+            })),
+            None => self.log(0),
+        }
+        response
+    }
+
+    fn log(&self, bytes: u64)
+    {
+        let elapsed = self.started.elapsed();
+        let duration_ms = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1000000) as u64;
+
+        match &*CONFIG.log.access_log_format {
+            "off" => {},
+            "json" => {
+                let mut object = BTreeMap::new();
+                object.insert(String::from("timestamp"), self.timestamp.to_rfc3339().to_json());
+                object.insert(String::from("method"), self.method.to_json());
+                object.insert(String::from("path"), self.path.to_json());
+                object.insert(String::from("status"), self.status.to_json());
+                object.insert(String::from("bytes"), bytes.to_json());
+                object.insert(String::from("duration_ms"), duration_ms.to_json());
+                object.insert(String::from("user"), self.user.to_json());
+                object.insert(String::from("client_ip"), self.client_ip.to_json());
+                object.insert(String::from("referer"), self.referer.to_json());
+                object.insert(String::from("user_agent"), self.user_agent.to_json());
+                info!(target: "hazel::access", "{}", Json::Object(object));
+            },
+            //apache combined log format, followed by the duration in milliseconds
+            _ => info!(target: "hazel::access", "{} - {} [{}] \"{} {}\" {} {} \"{}\" \"{}\" {}",
+                self.client_ip,
+                self.user.as_ref().map(|user| escape(user)).unwrap_or(String::from("-")),
+                self.timestamp.format("%d/%b/%Y:%H:%M:%S %z"),
+                self.method,
+                escape(&self.path),
+                self.status,
+                bytes,
+                self.referer.as_ref().map(|referer| escape(referer)).unwrap_or(String::from("-")),
+                self.user_agent.as_ref().map(|agent| escape(agent)).unwrap_or(String::from("-")),
+                duration_ms),
+        }
+    }
+}
+
+struct CountingWriter<'a, W: Write + 'a>
+{
+    inner: &'a mut W,
+    bytes: &'a mut u64,
+}
+
+impl<'a, W: Write + 'a> Write for CountingWriter<'a, W>
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+    {
+        let written = try!(self.inner.write(buf));
+        *self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()>
+    {
+        self.inner.flush()
+    }
+}
+
+struct LoggedBody
 {
-    fn before(&self, req: &mut Request) -> IronResult<()>
+    body: Box<WriteBody + Send>,
+    entry: AccessEntry,
+    bytes: u64,
+}
+
+impl WriteBody for LoggedBody
+{
+    fn write_body(&mut self, res: &mut ResponseBody) -> io::Result<()>
     {
-        info!("Requested: {:?}: {:?} ({:?})\n",
-                 req.method,
-                 req.url,
-                 req.headers);
-        Ok(())
+        self.body.write_body(&mut ResponseBody::new(CountingWriter {
+            inner: res,
+            bytes: &mut self.bytes,
+        }))
     }
-    fn catch(&self, _: &mut Request, _: IronError) -> IronResult<()>
+}
+
+//the body is dropped after writing, or unwritten for HEAD requests and broken connections
+impl Drop for LoggedBody
+{
+    fn drop(&mut self)
     {
-        Ok(())
+        self.entry.log(self.bytes);
     }
 }
+
+/// Writes one access log line per request, with credentials redacted
+pub struct AccessLogger;
+impl AroundMiddleware for AccessLogger
+{
+    fn around(self, handler: Box<Handler>) -> Box<Handler>
+    {
+        Box::new(move |req: &mut Request| {
+            let mut entry = AccessEntry::new(req);
+            let result = handler.handle(req);
+            entry.user = match req.extensions.get::<Authenticated>() {
+                Some(&(true, Some(ref username))) => Some(username.clone()),
+                _ => None,
+            };
+            match result {
+                Ok(response) => Ok(entry.attach(response)),
+                Err(mut err) => {
+                    err.response = entry.attach(err.response);
+                    Err(err)
+                },
+            }
+        })
+    }
+}
+
+#[test]
+fn access_log_redaction() {
+    assert_eq!(redact_path("GET", "/mail_confirmation/s3cr3t"), "/mail_confirmation/REDACTED");
+    assert_eq!(redact_path("POST", "/mail_confirmation/resend"), "/mail_confirmation/resend");
+    assert_eq!(redact_path("GET", "/packages/mail_confirmation"), "/packages/mail_confirmation");
+    assert_eq!(redact_url("https://hazel.example.org/mail_confirmation/s3cr3t?apikey=abc&page=2"),
+               "https://hazel.example.org/mail_confirmation/REDACTED?apikey=REDACTED&page=2");
+    assert_eq!(redact_url("https://hazel.example.org"), "https://hazel.example.org");
+}
+
+#[test]
+fn access_log_escaping() {
+    assert_eq!(escape("Mozilla/5.0 (X11; Linux x86_64)"), "Mozilla/5.0 (X11; Linux x86_64)");
+    assert_eq!(escape("a\" \"-\" 200\nforged"), "a\\x22 \\x22-\\x22 200\\x0Aforged");
+    assert_eq!(escape("C:\\\u{fc}"), "C:\\x5C\\xC3\\xBC");
+}
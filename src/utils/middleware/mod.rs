@@ -16,8 +16,11 @@
 mod path_normalize;
 pub use self::path_normalize::PathNormalizer;
 
+mod proxy;
+pub use self::proxy::{ProxyHeaders, BaseUrl, base_url};
+
 mod logger;
-pub use self::logger::Logger;
+pub use self::logger::AccessLogger;
 
 mod session;
 pub use self::session::*;
@@ -0,0 +1,270 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{BeforeMiddleware, AfterMiddleware, IronResult, Request, Response, Url};
+use iron::headers::Location;
+use iron::typemap::Key;
+
+use std::net::{IpAddr, SocketAddr};
+
+use ::utils::CONFIG;
+use ::web::listener::UnixSocketPeer;
+
+/// Public url of the server root (scheme, host and path prefix, without trailing slash)
+pub struct BaseUrl;
+impl Key for BaseUrl { type Value = String; }
+
+fn origin(url: &Url) -> String
+{
+    if (&*url.scheme == "http" && url.port == 80) || (&*url.scheme == "https" && url.port == 443) {
+        format!("{}://{}", url.scheme, url.host)
+    } else {
+        format!("{}://{}:{}", url.scheme, url.host, url.port)
+    }
+}
+
+/// Base for all generated absolute urls, e.g. the feed's download links
+pub fn base_url(req: &Request) -> String
+{
+    match req.extensions.get::<BaseUrl>() {
+        Some(base) => base.clone(),
+        None => origin(&req.url),
+    }
+}
+
+fn raw_header(req: &Request, name: &str) -> Option<String>
+{
+    req.headers.get_raw(name)
+        .map(|values| values.iter().map(|value| String::from_utf8_lossy(value).into_owned()).collect::<Vec<_>>().join(","))
+}
+
+fn is_trusted(trusted: &[String], address: &IpAddr) -> bool
+{
+    trusted.iter().any(|proxy| proxy.parse::<IpAddr>().ok().as_ref() == Some(address))
+}
+
+/// Accepts "1.2.3.4", "1.2.3.4:80", "[::1]" and "[::1]:80"
+fn parse_node(node: &str) -> Option<IpAddr>
+{
+    let node = node.trim().trim_matches('"');
+    let address = if node.starts_with('[') {
+        node[1..].split(']').next().unwrap_or("")
+    } else if node.matches(':').count() == 1 {
+        node.split(':').next().unwrap_or("")
+    } else {
+        node
+    };
+    address.parse().ok()
+}
+
+/// The nearest address that is not one of our own proxies, as everything further left may be spoofed
+fn client_address<'a, I: DoubleEndedIterator<Item=&'a str>>(nodes: I, trusted: &[String]) -> Option<IpAddr>
+{
+    for node in nodes.rev() {
+        match parse_node(node) {
+            Some(ref address) if is_trusted(trusted, address) => continue,
+            Some(address) => return Some(address),
+            None => return None,
+        }
+    }
+    None
+}
+
+struct Forwarded
+{
+    proto: Option<String>,
+    host: Option<String>,
+    prefix: Option<String>,
+    client: Option<IpAddr>,
+}
+
+/// Reads the rfc 7239 `Forwarded` header, falling back to the `X-Forwarded-*` headers.
+/// Proto, host and prefix are taken from the rightmost entry, the one of the nearest proxy,
+/// as proxies appending to the headers pass on whatever the client sent further left.
+fn forwarded<H: Fn(&str) -> Option<String>>(header: H, trusted: &[String]) -> Forwarded
+{
+    let last = |name: &str| header(name)
+        .and_then(|value| value.rsplit(',').next().map(|x| String::from(x.trim())))
+        .and_then(|value| if value.is_empty() { None } else { Some(value) });
+
+    let mut result = Forwarded {
+        proto: None,
+        host: None,
+        prefix: last("X-Forwarded-Prefix"),
+        client: None,
+    };
+
+    if let Some(header) = header("Forwarded") {
+        let elements: Vec<&str> = header.split(',').collect();
+        let mut nodes = Vec::new();
+        for (i, element) in elements.iter().enumerate() {
+            for pair in element.split(';') {
+                let mut pair = pair.splitn(2, '=');
+                let key = pair.next().unwrap_or("").trim().to_lowercase();
+                let value = pair.next().unwrap_or("").trim().trim_matches('"');
+                match &*key {
+                    "for" => nodes.push(value),
+                    "proto" if i == elements.len() - 1 => result.proto = Some(String::from(value)),
+                    "host" if i == elements.len() - 1 => result.host = Some(String::from(value)),
+                    _ => {},
+                }
+            }
+        }
+        result.client = client_address(nodes.into_iter(), trusted);
+    } else {
+        result.proto = last("X-Forwarded-Proto");
+        result.host = last("X-Forwarded-Host");
+        result.client = header("X-Forwarded-For").and_then(|value| client_address(value.split(','), trusted));
+    }
+    result
+}
+
+fn segments(path: &str) -> Vec<String>
+{
+    path.split('/').filter(|segment| !segment.is_empty()).map(String::from).collect()
+}
+
+/// Applies the configured public url or the headers of trusted reverse proxies to the request,
+/// so redirects, cookies and feed urls use the address the client sees.
+pub struct ProxyHeaders;
+impl BeforeMiddleware for ProxyHeaders
+{
+    fn before(&self, req: &mut Request) -> IronResult<()>
+    {
+        let trusted = &CONFIG.server.trusted_proxies;
+        //all clients of the unix socket would share one address otherwise
+        let forwarded = if req.extensions.contains::<UnixSocketPeer>() || is_trusted(trusted, &req.remote_addr.ip()) {
+            Some(forwarded(|name| raw_header(req, name), trusted))
+        } else {
+            None
+        };
+
+        let (public, prefix) = match CONFIG.server.public_url {
+            Some(ref public_url) => (Url::parse(public_url).ok(), segments(&public_url.splitn(4, '/').nth(3).unwrap_or(""))),
+            None => match forwarded {
+                Some(ref forwarded) => {
+                    let proto = forwarded.proto.clone().unwrap_or(req.url.scheme.clone());
+                    let host = forwarded.host.clone().unwrap_or(match (&*req.url.scheme, req.url.port) {
+                        ("http", 80) | ("https", 443) => format!("{}", req.url.host),
+                        (_, port) => format!("{}:{}", req.url.host, port),
+                    });
+                    (Url::parse(&format!("{}://{}/", proto, host)).ok(), forwarded.prefix.as_ref().map(|prefix| segments(prefix)).unwrap_or(Vec::new()))
+                },
+                None => (None, Vec::new()),
+            },
+        };
+
+        if let Some(mut public) = public {
+            public.path = req.url.path.clone();
+            public.query = req.url.query.clone();
+            req.url = public;
+        }
+        if let Some(client) = forwarded.and_then(|forwarded| forwarded.client) {
+            req.remote_addr = SocketAddr::new(client, 0);
+        }
+
+        //the proxy may pass the prefix on instead of stripping it
+        if !prefix.is_empty() && req.url.path.starts_with(&prefix) {
+            req.url.path = req.url.path.split_off(prefix.len());
+            if req.url.path.is_empty() {
+                req.url.path.push(String::new());
+            }
+        }
+
+        let base = if prefix.is_empty() {
+            origin(&req.url)
+        } else {
+            format!("{}/{}", origin(&req.url), prefix.join("/"))
+        };
+        req.extensions.insert::<BaseUrl>(base);
+        Ok(())
+    }
+}
+
+impl AfterMiddleware for ProxyHeaders
+{
+    /// Redirects are built from the request url, which lacks the prefix
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response>
+    {
+        let origin = origin(&req.url);
+        let base = base_url(req);
+        if base != origin {
+            let location = res.headers.get::<Location>().map(|location| location.0.clone());
+            if let Some(location) = location {
+                if location.starts_with(&format!("{}/", origin)) && location != base && !location.starts_with(&format!("{}/", base)) {
+                    res.headers.set(Location(format!("{}{}", base, &location[origin.len()..])));
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[test]
+fn proxy_nodes() {
+    let address = |text: &str| Some(text.parse::<IpAddr>().unwrap());
+    assert_eq!(parse_node("192.0.2.60"), address("192.0.2.60"));
+    assert_eq!(parse_node(" 192.0.2.60:4711"), address("192.0.2.60"));
+    assert_eq!(parse_node("[2001:db8::1]"), address("2001:db8::1"));
+    assert_eq!(parse_node("\"[2001:db8::1]:4711\""), address("2001:db8::1"));
+    assert_eq!(parse_node("2001:db8::1"), address("2001:db8::1"));
+    assert_eq!(parse_node("unknown"), None);
+    assert_eq!(parse_node("_hidden"), None);
+}
+
+#[test]
+fn proxy_client_address() {
+    let trusted = vec![String::from("10.0.0.1"), String::from("10.0.0.2")];
+    let address = |text: &str| Some(text.parse::<IpAddr>().unwrap());
+    assert_eq!(client_address(vec!["192.0.2.60", "10.0.0.1"].into_iter(), &trusted), address("192.0.2.60"));
+    //everything left of the first untrusted address is up to the client
+    assert_eq!(client_address(vec!["198.51.100.1", "192.0.2.60", "10.0.0.2", "10.0.0.1"].into_iter(), &trusted), address("192.0.2.60"));
+    assert_eq!(client_address(vec!["10.0.0.2", "10.0.0.1"].into_iter(), &trusted), None);
+    assert_eq!(client_address(vec!["192.0.2.60", "unknown"].into_iter(), &trusted), None);
+    assert_eq!(client_address(vec!["10.0.0.1"].into_iter(), &[]), address("10.0.0.1"));
+}
+
+#[test]
+fn proxy_headers() {
+    use std::collections::HashMap;
+
+    let trusted = vec![String::from("10.0.0.1")];
+    let mut headers = HashMap::new();
+    headers.insert("Forwarded", "for=198.51.100.1;proto=http;host=evil.example, for=192.0.2.60;proto=https;host=packages.example.org");
+    headers.insert("X-Forwarded-Host", "ignored.example");
+    let result = forwarded(|name| headers.get(name).map(|value| String::from(*value)), &trusted);
+    assert_eq!(result.proto, Some(String::from("https")));
+    assert_eq!(result.host, Some(String::from("packages.example.org")));
+    assert_eq!(result.client, Some("192.0.2.60".parse().unwrap()));
+
+    //values of the client are not used if the nearest proxy does not set them
+    headers.insert("Forwarded", "for=198.51.100.1;proto=http;host=evil.example, for=192.0.2.60");
+    let result = forwarded(|name| headers.get(name).map(|value| String::from(*value)), &trusted);
+    assert_eq!((result.proto, result.host), (None, None));
+
+    let mut headers = HashMap::new();
+    headers.insert("X-Forwarded-Proto", "http, https");
+    headers.insert("X-Forwarded-Host", "evil.example,packages.example.org");
+    headers.insert("X-Forwarded-Prefix", "/evil, /hazel");
+    headers.insert("X-Forwarded-For", "198.51.100.1, 192.0.2.60, 10.0.0.1");
+    let result = forwarded(|name| headers.get(name).map(|value| String::from(*value)), &trusted);
+    assert_eq!(result.proto, Some(String::from("https")));
+    assert_eq!(result.host, Some(String::from("packages.example.org")));
+    assert_eq!(result.prefix, Some(String::from("/hazel")));
+    assert_eq!(result.client, Some("192.0.2.60".parse().unwrap()));
+
+    let result = forwarded(|_| None, &trusted);
+    assert_eq!((result.proto, result.host, result.prefix, result.client), (None, None, None, None));
+}
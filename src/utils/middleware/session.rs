@@ -17,13 +17,15 @@ use iron::{AroundMiddleware, Request, Handler, Set};
 use iron::headers::{Cookie, SetCookie};
 use iron::modifiers::Header;
 use iron::typemap::Key;
+use cookie::{Cookie as CookiePair, CookieJar};
 
 use persistent::Write;
+use uuid::Uuid;
 
 use chrono::*;
 
 use ::utils::CONFIG;
-use ::web::server::SessionStoreKey;
+use ::web::server::{SessionStoreKey, TwoFactorStoreKey};
 
 pub struct Authenticated {}
 impl Key for Authenticated {
@@ -38,6 +40,93 @@ pub struct SessionInfo
     pub remember: bool,
 }
 
+/// Login with a valid password, that still waits for its second factor
+#[derive(Clone)]
+pub struct PendingSecondFactor
+{
+    pub username: String,
+    pub remember: bool,
+    pub enrolment: bool, //the user has to set up a second factor first
+    pub expires: DateTime<UTC>,
+}
+
+/// Parks a login until the second factor is provided and returns the token identifying it
+pub fn defer_session(req: &Request, username: String, remember: bool, enrolment: bool) -> String
+{
+    let store_mutex = req.extensions.get::<Write<TwoFactorStoreKey>>().unwrap().clone();
+    let mut store = store_mutex.lock().unwrap();
+
+    //garbage collect abandoned logins
+    let now = UTC::now();
+    let expired: Vec<String> = store.iter().filter(|&(_, pending)| pending.expires < now).map(|(token, _)| token.clone()).collect();
+    for token in expired {
+        store.remove(&token);
+    }
+
+    let token = Uuid::new_v4().simple().to_string();
+    store.insert(token.clone(), PendingSecondFactor {
+        username: username,
+        remember: remember,
+        enrolment: enrolment,
+        expires: now + Duration::minutes(10),
+    });
+    token
+}
+
+pub fn pending_second_factor(req: &Request, token: &str) -> Option<PendingSecondFactor>
+{
+    let store_mutex = req.extensions.get::<Write<TwoFactorStoreKey>>().unwrap().clone();
+    let store = store_mutex.lock().unwrap();
+    store.get(token).cloned().and_then(|pending| if pending.expires >= UTC::now() { Some(pending) } else { None })
+}
+
+/// Turns a pending login into a session, after the second factor was verified
+pub fn finish_second_factor(req: &Request, token: &str) -> Option<Header<SetCookie>>
+{
+    let pending = {
+        let store_mutex = req.extensions.get::<Write<TwoFactorStoreKey>>().unwrap().clone();
+        let mut store = store_mutex.lock().unwrap();
+        store.remove(token)
+    };
+    match pending {
+        Some(pending) => if pending.expires >= UTC::now() { Some(create_session(req, pending.username, pending.remember)) } else { None },
+        None => None,
+    }
+}
+
+/// Creates a new session for `username` and returns the cookies to be set on the response
+pub fn create_session(req: &Request, username: String, remember: bool) -> Header<SetCookie>
+{
+    let session_store_mutex = req.extensions.get::<Write<SessionStoreKey>>().unwrap().clone();
+    let mut session_store = session_store_mutex.lock().unwrap();
+    let session_id = Uuid::new_v4().simple().to_string();
+    let session_info = SessionInfo {
+        expires: match remember {
+            true  => UTC::now() + Duration::weeks(1),
+            false => UTC::now() + Duration::hours(1),
+        },
+        session_id: session_id.clone(),
+        remember: remember,
+    };
+    let root_jar = CookieJar::new(&*CONFIG.auth.cookie_key.as_bytes());
+    let jar = root_jar.encrypted();
+    let mut user_cookie = CookiePair::new(String::from("hazel_username"), username.clone());
+    let mut session_cookie = CookiePair::new(String::from("hazel_sessionid"), session_id);
+
+    session_cookie.max_age = Some((session_info.expires - UTC::now()).num_seconds() as u64);
+    user_cookie.max_age = Some((session_info.expires - UTC::now()).num_seconds() as u64);
+    session_cookie.path = Some(String::from("/"));
+    user_cookie.path = Some(String::from("/"));
+    session_cookie.domain = Some(req.url.host.to_string());
+    user_cookie.domain = Some(req.url.host.to_string());
+
+    jar.add(user_cookie);
+    jar.add(session_cookie);
+
+    session_store.insert(username, session_info);
+    Header(SetCookie::from_cookie_jar(&root_jar))
+}
+
 pub struct SessionManager;
 impl AroundMiddleware for SessionManager
 {
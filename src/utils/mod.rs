@@ -15,5 +15,9 @@
 
 pub mod middleware;
 #[macro_use] pub mod error;
-mod config;
+pub mod config;
+pub mod logging;
+pub mod throttle;
+pub mod totp;
+pub mod metrics;
 pub use self::config::CONFIG;
@@ -0,0 +1,159 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::Response;
+use iron::status;
+use iron::modifiers::Header;
+use chrono::*;
+
+use std::cmp;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use ::utils::CONFIG;
+
+header! { (RetryAfter, "Retry-After") => [u64] }
+
+/// How often failures are checked for expired targets
+const PRUNE_INTERVAL_SECS: i64 = 60;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ThrottleTarget
+{
+    Account(String),
+    Address(IpAddr),
+    ApiKeyAddress(IpAddr),
+}
+
+struct Attempts
+{
+    failures: u32,
+    last_failure: DateTime<UTC>,
+    locked_until: Option<DateTime<UTC>>,
+}
+
+/// Tracks failed authentication attempts and locks out accounts and addresses with exponential backoff
+#[derive(Default)]
+pub struct Throttle
+{
+    attempts: HashMap<ThrottleTarget, Attempts>,
+    last_pruned: Option<DateTime<UTC>>,
+}
+
+impl Throttle
+{
+    pub fn new() -> Throttle
+    {
+        Throttle::default()
+    }
+
+    fn threshold(target: &ThrottleTarget) -> u32
+    {
+        match target {
+            &ThrottleTarget::Account(_) => CONFIG.auth.max_login_attempts_per_account,
+            &ThrottleTarget::Address(_) => CONFIG.auth.max_login_attempts_per_ip,
+            &ThrottleTarget::ApiKeyAddress(_) => CONFIG.auth.max_apikey_attempts_per_ip,
+        }
+    }
+
+    /// Returns the remaining seconds of a lockout, if the target is currently locked
+    pub fn check(&self, target: &ThrottleTarget) -> Option<i64>
+    {
+        match self.attempts.get(target).and_then(|attempts| attempts.locked_until) {
+            Some(until) if until > UTC::now() => Some(cmp::max((until - UTC::now()).num_seconds(), 1)),
+            _ => None,
+        }
+    }
+
+    /// Removes targets whose failures would be forgotten anyway, so addresses that failed once do not accumulate
+    fn prune(&mut self, now: DateTime<UTC>, max_lockout: i64)
+    {
+        let expired: Vec<ThrottleTarget> = self.attempts.iter()
+            .filter(|&(_, attempts)| (now - attempts.last_failure).num_seconds() > max_lockout &&
+                                     attempts.locked_until.map(|until| until <= now).unwrap_or(true))
+            .map(|(target, _)| target.clone())
+            .collect();
+        for target in expired {
+            self.attempts.remove(&target);
+        }
+        self.last_pruned = Some(now);
+    }
+
+    /// Records a failed attempt. Returns the lockout duration in seconds, if the target got locked
+    pub fn failure(&mut self, target: ThrottleTarget) -> Option<i64>
+    {
+        let now = UTC::now();
+        let threshold = Throttle::threshold(&target);
+        let max_lockout = CONFIG.auth.lockout_max_secs as i64;
+
+        if self.last_pruned.map(|last| (now - last).num_seconds() >= PRUNE_INTERVAL_SECS).unwrap_or(true) {
+            self.prune(now, max_lockout);
+        }
+
+        let attempts = self.attempts.entry(target.clone()).or_insert(Attempts {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+
+        //forget old failures
+        if (now - attempts.last_failure).num_seconds() > max_lockout {
+            attempts.failures = 0;
+        }
+        attempts.failures += 1;
+        attempts.last_failure = now;
+
+        if threshold > 0 && attempts.failures >= threshold {
+            let exponent = cmp::min(attempts.failures - threshold, 16);
+            let lockout = cmp::min((CONFIG.auth.lockout_base_secs as i64) << exponent, max_lockout);
+            attempts.locked_until = Some(now + Duration::seconds(lockout));
+            warn!(target: "hazel::security", "Locked {:?} for {}s after {} failed attempts", target, lockout, attempts.failures);
+            Some(lockout)
+        } else {
+            None
+        }
+    }
+
+    pub fn success(&mut self, target: &ThrottleTarget)
+    {
+        self.attempts.remove(target);
+    }
+}
+
+pub fn too_many_requests(retry_after: i64) -> Response
+{
+    Response::with((status::TooManyRequests, Header(RetryAfter(retry_after as u64)), "Too many failed attempts, please try again later"))
+}
+
+#[test]
+fn prune_expired_targets() {
+    let now = UTC::now();
+    let mut throttle = Throttle::new();
+    let attempts = |last_failure_secs: i64, locked_secs: Option<i64>| Attempts {
+        failures: 3,
+        last_failure: now - Duration::seconds(last_failure_secs),
+        locked_until: locked_secs.map(|secs| now + Duration::seconds(secs)),
+    };
+    throttle.attempts.insert(ThrottleTarget::Account(String::from("recent")), attempts(10, None));
+    throttle.attempts.insert(ThrottleTarget::Account(String::from("old")), attempts(7200, None));
+    throttle.attempts.insert(ThrottleTarget::Account(String::from("expired lock")), attempts(7200, Some(-3600)));
+    throttle.attempts.insert(ThrottleTarget::Account(String::from("locked")), attempts(7200, Some(60)));
+
+    throttle.prune(now, 3600);
+    let mut remaining: Vec<ThrottleTarget> = throttle.attempts.keys().cloned().collect();
+    remaining.sort_by_key(|target| format!("{:?}", target));
+    assert_eq!(remaining, vec![ThrottleTarget::Account(String::from("locked")), ThrottleTarget::Account(String::from("recent"))]);
+    assert_eq!(throttle.last_pruned, Some(now));
+}
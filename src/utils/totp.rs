@@ -0,0 +1,169 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha1::Sha1;
+use crypto::sha2::Sha256;
+use rand::{self, Rng};
+use url::form_urlencoded;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use chrono::UTC;
+
+use std::ascii::AsciiExt;
+
+const ISSUER: &'static str = "Hazel";
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+const RECOVERY_CODES: usize = 10;
+const BASE32_ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn base32_encode(data: &[u8]) -> String
+{
+    let mut result = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    result
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>>
+{
+    let mut result = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in text.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = match BASE32_ALPHABET.iter().position(|x| *x as char == c.to_ascii_uppercase()) {
+            Some(value) => value as u32,
+            None => return None,
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push(((buffer >> bits) & 0xff) as u8);
+        }
+    }
+    Some(result)
+}
+
+/// HOTP value (RFC 4226) of the given counter
+fn hotp(key: &[u8], counter: u64) -> u32
+{
+    let mut message = [0u8; 8];
+    for i in 0..8 {
+        message[i] = (counter >> (8 * (7 - i))) as u8;
+    }
+    let mut mac = Hmac::new(Sha1::new(), key);
+    mac.input(&message);
+    let result = mac.result();
+    let hash = result.code();
+
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let binary = ((hash[offset] as u32 & 0x7f) << 24)
+               | ((hash[offset + 1] as u32) << 16)
+               | ((hash[offset + 2] as u32) << 8)
+               | (hash[offset + 3] as u32);
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Random 160 bit key, base32 encoded as expected by authenticator apps
+pub fn generate_secret() -> String
+{
+    let mut key = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut key);
+    base32_encode(&key)
+}
+
+/// otpauth uri to be encoded as QR code for authenticator apps
+pub fn provisioning_uri(username: &str, secret: &str) -> String
+{
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("secret", secret)
+        .append_pair("issuer", ISSUER)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &format!("{}", DIGITS))
+        .append_pair("period", &format!("{}", STEP_SECS))
+        .finish();
+    format!("otpauth://totp/{}:{}?{}", ISSUER, utf8_percent_encode(username, PATH_SEGMENT_ENCODE_SET), query)
+}
+
+/// Checks a code against the current time step, allowing one step of clock drift.
+/// Returns the matched step, which has to be later than `last_step` to prevent reuse of a code.
+pub fn verify(secret: &str, code: &str, last_step: Option<i64>) -> Option<i64>
+{
+    let key = match base32_decode(secret) {
+        Some(key) => key,
+        None => return None,
+    };
+    let code = match code.trim().parse::<u32>() {
+        Ok(code) => code,
+        Err(_) => return None,
+    };
+    let current = UTC::now().timestamp() / STEP_SECS;
+
+    for step in (current - 1)..(current + 2) {
+        if last_step.map(|last| step <= last).unwrap_or(false) {
+            continue;
+        }
+        if hotp(&key, step as u64) == code {
+            return Some(step);
+        }
+    }
+    None
+}
+
+pub fn generate_recovery_codes() -> Vec<String>
+{
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODES).map(|_| rng.gen_ascii_chars().take(10).collect::<String>().to_lowercase()).collect()
+}
+
+/// Recovery codes are only stored hashed
+pub fn hash_recovery_code(code: &str) -> String
+{
+    let mut hasher = Sha256::new();
+    hasher.input_str(&code.trim().to_lowercase());
+    hasher.result_str()
+}
+
+#[test]
+fn rfc6238() {
+    let key = b"12345678901234567890";
+    assert_eq!(hotp(key, 59 / 30), 287082);
+    assert_eq!(hotp(key, 1111111109 / 30), 81804);
+    assert_eq!(hotp(key, 1234567890 / 30), 5924);
+    assert_eq!(hotp(key, 2000000000 / 30), 279037);
+}
+
+#[test]
+fn base32() {
+    let secret = base32_encode(b"12345678901234567890");
+    assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    assert_eq!(base32_decode(&secret).unwrap(), b"12345678901234567890".to_vec());
+    assert_eq!(base32_decode("gezdgnbvgy3tqojq").unwrap(), b"1234567890".to_vec());
+    assert!(base32_decode("1").is_none());
+}
@@ -0,0 +1,252 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use diesel::pg::PgConnection;
+use hyper::Client;
+use hyper::header::ContentType;
+use pulldown_cmark::{Parser, Event, Tag, html};
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+use treexml::Element;
+use url::percent_encoding::percent_decode;
+use zip::ZipArchive;
+
+use std::borrow::Cow;
+use std::cmp;
+use std::io::{Read, Seek};
+use std::str::FromStr;
+use std::thread;
+use std::time;
+
+use super::db::PackageAsset;
+use ::utils::CONFIG;
+
+/// Embedded files are limited to the same size nuget.org accepts for icons
+pub const MAX_ASSET_SIZE: u64 = 1024 * 1024;
+
+const ICON_TYPES: &'static [(&'static str, &'static str)] = &[("png", "image/png"), ("jpg", "image/jpeg"),
+                                                              ("jpeg", "image/jpeg"), ("gif", "image/gif")];
+
+/// Files a package may embed and reference from its nuspec
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind
+{
+    Icon,
+    Readme,
+    License,
+}
+
+impl AssetKind
+{
+    pub fn all() -> Vec<AssetKind>
+    {
+        vec![AssetKind::Icon, AssetKind::Readme, AssetKind::License]
+    }
+
+    /// Name of the nuspec element, also used to store the kind
+    pub fn as_str(&self) -> &'static str
+    {
+        match *self {
+            AssetKind::Icon => "icon",
+            AssetKind::Readme => "readme",
+            AssetKind::License => "license",
+        }
+    }
+
+    /// Icons are identified by their extension, other types are unknown to the nuspec
+    pub fn media_type(&self, name: &str) -> Option<&'static str>
+    {
+        match *self {
+            AssetKind::Icon => {
+                let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
+                ICON_TYPES.iter().find(|&&(ext, _)| ext == extension).map(|&(_, media_type)| media_type)
+            },
+            AssetKind::Readme => Some("text/markdown; charset=utf-8"),
+            AssetKind::License => Some("text/plain; charset=utf-8"),
+        }
+    }
+}
+
+impl FromStr for AssetKind
+{
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<AssetKind, ()>
+    {
+        AssetKind::all().into_iter().find(|kind| kind.as_str() == s).ok_or(())
+    }
+}
+
+/// Path inside of the package the nuspec refers to, license expressions are no files
+pub fn referenced_file(metadata: &Element, kind: AssetKind) -> Option<String>
+{
+    let element = match metadata.find_child(|tag| tag.name == kind.as_str()) {
+        Some(element) => element,
+        None => return None,
+    };
+    if kind == AssetKind::License && element.attributes.get("type").map(|kind| &**kind) != Some("file") {
+        return None;
+    }
+    element.text.as_ref()
+        .map(|path| String::from(path.trim().replace('\\', "/").trim_left_matches("./").trim_left_matches('/')))
+        .and_then(|path| if path.is_empty() { None } else { Some(path) })
+}
+
+/// Index of the archive entry at `path`, nuget resolves the paths case-insensitively
+pub fn find_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, path: &str) -> Option<usize>
+{
+    let path = path.to_lowercase();
+    (0..zip.len()).find(|&i| match zip.by_index(i) {
+        Ok(file) => percent_decode(file.name().as_bytes()).decode_utf8_lossy().to_lowercase() == path,
+        Err(_) => false,
+    })
+}
+
+/// Embedded files referenced by the nuspec, broken references are reported by the validation
+pub fn extract<R: Read + Seek>(zip: &mut ZipArchive<R>, metadata: &Element) -> Vec<(AssetKind, String, &'static str, Vec<u8>)>
+{
+    let mut assets = Vec::new();
+    for kind in AssetKind::all() {
+        let (path, media_type) = match referenced_file(metadata, kind) {
+            Some(path) => match kind.media_type(&path) {
+                Some(media_type) => (path, media_type),
+                None => continue,
+            },
+            None => continue,
+        };
+        let index = match find_entry(zip, &path) {
+            Some(index) => index,
+            None => continue,
+        };
+        let mut content = Vec::new();
+        match zip.by_index(index) {
+            Ok(file) => if let Err(err) = file.take(MAX_ASSET_SIZE).read_to_end(&mut content) {
+                warn!(target: "hazel::assets", "Failed to read {}: {}", path, err);
+                continue;
+            },
+            Err(_) => continue,
+        }
+        assets.push((kind, path, media_type, content));
+    }
+    assets
+}
+
+/// Only web and mail links are kept, everything else (e.g. `javascript:`) could run in the page
+fn safe_url<'a>(url: Cow<'a, str>) -> Cow<'a, str>
+{
+    let scheme_end = url.find(|c| c == ':' || c == '/' || c == '?' || c == '#');
+    match scheme_end {
+        Some(index) if url[index..].starts_with(':') => {
+            let scheme = url[..index].to_lowercase();
+            if scheme == "http" || scheme == "https" || scheme == "mailto" {
+                url
+            } else {
+                Cow::Borrowed("#")
+            }
+        },
+        _ => url,
+    }
+}
+
+/// Renders README markdown to html, embedded html is shown as text
+pub fn render_markdown(text: &str) -> String
+{
+    let parser = Parser::new(text).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link(url, title)) => Event::Start(Tag::Link(safe_url(url), title)),
+        Event::End(Tag::Link(url, title)) => Event::End(Tag::Link(safe_url(url), title)),
+        Event::Start(Tag::Image(url, title)) => Event::Start(Tag::Image(safe_url(url), title)),
+        Event::End(Tag::Image(url, title)) => Event::End(Tag::Image(safe_url(url), title)),
+        event => event,
+    });
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+    output
+}
+
+/// Downloads a remote icon, returns its media type and content
+fn fetch_icon(client: &Client, url: &str, max_size: u64) -> Result<(String, Vec<u8>), String>
+{
+    let response = try!(client.get(url).send().map_err(|err| format!("{}", err)));
+    if !response.status.is_success() {
+        return Err(format!("Server answered with {}", response.status));
+    }
+    let media_type = match response.headers.get::<ContentType>() {
+        Some(&ContentType(ref mime)) => {
+            let mime = format!("{}", mime);
+            String::from(mime.split(';').next().unwrap_or("").trim())
+        },
+        None => String::new(),
+    };
+    if !ICON_TYPES.iter().any(|&(_, icon_type)| icon_type == media_type) {
+        return Err(format!("Unsupported media type \"{}\"", media_type));
+    }
+
+    let mut content = Vec::new();
+    try!(response.take(max_size + 1).read_to_end(&mut content).map_err(|err| format!("{}", err)));
+    if content.len() as u64 > max_size {
+        return Err(format!("Larger than {} KiB", max_size / 1024));
+    }
+    Ok((media_type, content))
+}
+
+/// Fetches the iconUrl of every version once, so the web interface does not depend on the remote server.
+/// Failed downloads are recorded and not repeated.
+pub fn schedule(pool: Pool<ConnectionManager<PgConnection>>)
+{
+    let config = match CONFIG.backend.icons {
+        Some(ref config) => config,
+        None => return,
+    };
+
+    thread::spawn(move || {
+        let mut client = Client::new();
+        client.set_read_timeout(Some(time::Duration::from_secs(cmp::max(config.timeout_secs, 1))));
+        client.set_write_timeout(Some(time::Duration::from_secs(cmp::max(config.timeout_secs, 1))));
+
+        loop {
+            match pool.get() {
+                Ok(connection) => match PackageAsset::missing_remote_icons(&*connection) {
+                    Ok(versions) => for version in versions {
+                        let url = match version.icon_url.clone() {
+                            Some(url) => url,
+                            None => continue,
+                        };
+                        let result = fetch_icon(&client, &url, config.max_size_kb * 1024);
+                        if let Err(ref err) = result {
+                            warn!(target: "hazel::assets", "Fetching the icon of {} {} from {} failed: {}", version.id(), version.version(), url, err);
+                        }
+                        if let Err(err) = PackageAsset::store_remote_icon(&*connection, &version, url, result.ok()) {
+                            error!(target: "hazel::assets", "{:?}", err);
+                        }
+                    },
+                    Err(err) => error!(target: "hazel::assets", "{:?}", err),
+                },
+                Err(err) => error!("{:?}", err),
+            }
+            thread::sleep(time::Duration::from_secs(cmp::max(config.interval_mins, 1) * 60));
+        }
+    });
+}
+
+#[test]
+fn markdown_is_sanitized() {
+    let html = render_markdown("# Title\n\n<script>alert(1)</script>\n\n[ok](https://example.com) [bad](javascript:alert(1))\n");
+    assert!(html.contains("<h1>Title</h1>"));
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("href=\"https://example.com\""));
+    assert!(!html.contains("javascript:"));
+}
@@ -0,0 +1,224 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use url::percent_encoding::percent_decode;
+use zip::{ZipArchive, CompressionMethod};
+use zip::read::ZipFile;
+use zip::result::ZipError;
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, Write};
+
+use ::utils::error::*;
+
+const TEXT_EXTENSIONS: &'static [&'static str] = &["nuspec", "ps1", "psm1", "psd1", "bat", "cmd", "sh", "txt", "md", "xml",
+                                                  "json", "config", "props", "targets", "rels", "psmdcp", "yml", "yaml", "ini",
+                                                  "cs", "vb", "fs", "html", "css", "js"];
+const TEXT_NAMES: &'static [&'static str] = &["readme", "license", "licence", "copying", "notice", "changelog", "verification"];
+
+/// Text files bigger than this can only be downloaded
+pub const MAX_INLINE_SIZE: u64 = 1024 * 1024;
+
+/// Largest file read from a package into memory, uploads only limit the compressed size
+pub const MAX_READ_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Bytes decompressed for hashing a whole archive, files beyond it are compared by size only
+pub const MAX_HASH_SIZE: u64 = 512 * 1024 * 1024;
+
+/// File inside of a stored package
+#[derive(Debug, Clone)]
+pub struct Entry
+{
+    /// name as stored in the archive, nuget percent-encodes special characters
+    pub path: String,
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub compression: &'static str,
+}
+
+impl Entry
+{
+    pub fn is_text(&self) -> bool
+    {
+        is_text(&self.name)
+    }
+}
+
+/// Decides by name only, the content is checked when it gets displayed
+pub fn is_text(name: &str) -> bool
+{
+    let file_name = name.rsplit('/').next().unwrap_or("").to_lowercase();
+    let (stem, extension) = match file_name.rfind('.') {
+        Some(index) => (&file_name[..index], &file_name[index + 1..]),
+        None => (&*file_name, ""),
+    };
+    TEXT_EXTENSIONS.contains(&extension) || TEXT_NAMES.contains(&stem)
+}
+
+fn entry(file: &ZipFile) -> Entry
+{
+    Entry {
+        path: String::from(file.name()),
+        name: String::from(percent_decode(file.name().as_bytes()).decode_utf8_lossy()),
+        size: file.size(),
+        compressed_size: file.compressed_size(),
+        compression: compression_name(file.compression()),
+    }
+}
+
+fn compression_name(method: CompressionMethod) -> &'static str
+{
+    match method {
+        CompressionMethod::Stored => "stored",
+        CompressionMethod::Deflated => "deflated",
+        _ => "other",
+    }
+}
+
+/// Lists all files of the archive ordered by path, directory entries are skipped
+pub fn list<R: Read + Seek>(reader: R) -> BackendResult<Vec<Entry>>
+{
+    let mut zip = try!(ZipArchive::new(reader));
+    let mut entries = Vec::new();
+    for i in 0..zip.len() {
+        let file = try!(zip.by_index(i));
+        if file.name().ends_with('/') {
+            continue;
+        }
+        entries.push(entry(&file));
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// Reads a single file of the archive, returns `None` if there is no file at `path`
+pub fn read<R: Read + Seek>(reader: R, path: &str) -> BackendResult<Option<(Entry, Vec<u8>)>>
+{
+    read_limited(reader, path, MAX_READ_SIZE)
+}
+
+fn read_limited<R: Read + Seek>(reader: R, path: &str, limit: u64) -> BackendResult<Option<(Entry, Vec<u8>)>>
+{
+    let mut zip = try!(ZipArchive::new(reader));
+    let mut file = match zip.by_name(path) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    if file.name().ends_with('/') {
+        return Ok(None);
+    }
+    let entry = entry(&file);
+    //the size is taken from the archive, the reader is limited as well in case it lies
+    if entry.size > limit {
+        return Err(BackendError::EntryTooLarge(entry.name));
+    }
+    let mut content = Vec::new();
+    try!(file.by_ref().take(limit + 1).read_to_end(&mut content));
+    if content.len() as u64 > limit {
+        return Err(BackendError::EntryTooLarge(entry.name));
+    }
+    Ok(Some((entry, content)))
+}
+
+struct HashWriter(Sha256);
+
+impl Write for HashWriter
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+    {
+        self.0.input(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+/// Sha256 of every file by its decoded name, used to compare the archives of two versions.
+/// Files are streamed through the hasher, once `MAX_HASH_SIZE` bytes are hashed the rest is represented by their size.
+pub fn hashes<R: Read + Seek>(zip: &mut ZipArchive<R>) -> BackendResult<BTreeMap<String, String>>
+{
+    let mut hashes = BTreeMap::new();
+    let mut budget = MAX_HASH_SIZE;
+    for i in 0..zip.len() {
+        let mut file = try!(zip.by_index(i));
+        if file.name().ends_with('/') {
+            continue;
+        }
+        let name = String::from(percent_decode(file.name().as_bytes()).decode_utf8_lossy());
+        if file.size() > budget {
+            hashes.insert(name, format!("{} bytes", file.size()));
+            continue;
+        }
+        let mut hasher = HashWriter(Sha256::new());
+        let hashed = try!(io::copy(&mut file.by_ref().take(budget + 1), &mut hasher));
+        if hashed > budget {
+            //the archive understated the size
+            budget = 0;
+            hashes.insert(name, format!("{} bytes", file.size()));
+        } else {
+            budget -= hashed;
+            hashes.insert(name, hasher.0.result_str());
+        }
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+fn archive(name: &str, content: &[u8]) -> Vec<u8>
+{
+    use zip::ZipWriter;
+
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer.start_file(name, CompressionMethod::Deflated).unwrap();
+    writer.write_all(content).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn read_limit() {
+    let zip = archive("tools/data.txt", b"0123456789abcdef");
+    match read_limited(io::Cursor::new(zip.clone()), "tools/data.txt", 16) {
+        Ok(Some((entry, content))) => {
+            assert_eq!(entry.size, 16);
+            assert_eq!(content, b"0123456789abcdef".to_vec());
+        },
+        _ => panic!("readable entry"),
+    }
+    assert!(read_limited(io::Cursor::new(zip.clone()), "tools/missing.txt", 16).unwrap().is_none());
+    match read_limited(io::Cursor::new(zip), "tools/data.txt", 15) {
+        Err(BackendError::EntryTooLarge(name)) => assert_eq!(name, "tools/data.txt"),
+        _ => panic!("entry above the limit"),
+    }
+}
+
+#[test]
+fn read_limit_forged_size() {
+    let mut zip = archive("data.txt", &[b'x'; 64]);
+    //claim 8 bytes in the central directory, the entry still inflates to 64
+    let central = (0..zip.len() - 4).rev().find(|&i| &zip[i..i + 4] == b"PK\x01\x02").unwrap();
+    zip[central + 24..central + 28].copy_from_slice(&[8, 0, 0, 0]);
+    match read_limited(io::Cursor::new(zip), "data.txt", 16) {
+        Err(BackendError::EntryTooLarge(name)) => assert_eq!(name, "data.txt"),
+        _ => panic!("forged entry above the limit"),
+    }
+}
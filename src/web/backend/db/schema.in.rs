@@ -25,6 +25,9 @@ mod tables {
             Timestamp , description -> Nullable<Text> , version_download_count
             -> Int8 , release_notes -> Nullable<Text> , hash -> Nullable<Text>
             , hash_algorithm -> Nullable<Text> , size -> Int8 , icon_url ->
+            Nullable<Text> , signature_type -> Nullable<Text> , signer ->
+            Nullable<Text> , signer_fingerprint -> Nullable<Text> , scan_status ->
+            Nullable<Text> , scan_result -> Nullable<Text> , original_version ->
             Nullable<Text> , });
     table! (packageversion_has_author {
             id -> Text , version -> Text , author_id -> Text , });
@@ -37,7 +40,38 @@ mod tables {
             Nullable<Text> , report_abuse_url -> Nullable<Text> , maintainer -> Text , });
     table! (package_has_tag { id -> Text , package_id -> Text , });
     table! (hazeluser { id -> Text , name -> Text , mail -> Nullable<Text>, mail_key -> Nullable<Text>, confirmed -> Bool, provider -> Text ,
-            password -> Nullable<Text> , apikey -> Nullable<Text> , });
+            password -> Nullable<Text> , apikey -> Nullable<Text> , admin -> Bool ,
+            totp_secret -> Nullable<Text> , totp_recovery_codes -> Nullable<Text> ,
+            totp_last_step -> Nullable<Int8> , });
+    table! (audit_event {
+            id -> Int8 , occurred -> Timestamp , actor -> Text , action -> Text ,
+            target_id -> Nullable<Text> , target_version -> Nullable<Text> ,
+            source_ip -> Nullable<Text> , before_metadata -> Nullable<Text> ,
+            after_metadata -> Nullable<Text> , });
+    table! (webhook {
+            id -> Int8 , package_id -> Text , url -> Text , secret -> Nullable<Text> ,
+            events -> Text , created_by -> Text , });
+    table! (webhook_delivery {
+            id -> Int8 , webhook_id -> Nullable<Int8> , event -> Text , package_id -> Text ,
+            package_version -> Nullable<Text> , url -> Text , payload -> Text ,
+            signature -> Nullable<Text> , attempts -> Int4 , next_attempt -> Nullable<Timestamp> ,
+            delivered -> Bool , response_status -> Nullable<Int4> , last_error -> Nullable<Text> ,
+            created -> Timestamp , });
+    table! (install_script {
+            id -> Text , version -> Text , name -> Text , content -> Text , });
+    table! (package_asset {
+            id -> Text , version -> Text , kind -> Text , name -> Text ,
+            media_type -> Nullable<Text> , content -> Nullable<Binary> , });
+    table! (symbol_file {
+            id -> Text , version -> Text , path -> Text , file_name -> Text ,
+            signature -> Text , size -> Int8 , });
+    table! (deprecation {
+            id -> Text , version -> Text , reasons -> Text , message -> Nullable<Text> ,
+            alternate_id -> Nullable<Text> , alternate_range -> Nullable<Text> , });
+    table! (advisory {
+            id -> Text , version -> Text , url -> Text , severity -> Text , });
+    table! (__diesel_schema_migrations (version) {
+            version -> VarChar , run_on -> Timestamp , });
 }
 
 use self::tables::package;
@@ -49,8 +83,17 @@ use self::tables::packageversion_has_author;
 use self::tables::tag;
 use self::tables::package_has_tag;
 use self::tables::hazeluser;
-
-use chrono::{UTC, NaiveDateTime};
+use self::tables::audit_event;
+use self::tables::webhook;
+use self::tables::webhook_delivery;
+use self::tables::install_script;
+use self::tables::package_asset;
+use self::tables::symbol_file;
+use self::tables::deprecation;
+use self::tables::advisory;
+use self::tables::__diesel_schema_migrations;
+
+use chrono::{UTC, NaiveDateTime, Duration};
 
 use crypto::digest::Digest;
 use crypto::sha2::Sha256;
@@ -58,6 +101,8 @@ use crypto::sha2::Sha256;
 use diesel::prelude::*;
 use diesel::pg::Pg;
 use diesel::{insert, update, delete};
+use diesel::expression::dsl::sql;
+use diesel::types::{BigInt, Text};
 
 use zip::{ZipArchive, ZipWriter, CompressionMethod};
 use zip::result::ZipError;
@@ -65,8 +110,6 @@ use zip::result::ZipError;
 use treexml::Document as XmlDocument;
 use treexml::Element;
 
-use semver::{Version, VersionReq};
-
 use bcrypt;
 
 use cldap::RustLDAP;
@@ -77,6 +120,9 @@ use lazysort::SortedBy;
 use uuid::Uuid;
 
 use rustc_serialize::{Encoder, Encodable};
+use rustc_serialize::json::{self, Json, ToJson};
+
+use tar::{Archive, Builder, Header};
 
 use lettre::email::EmailBuilder;
 use lettre::transport::smtp::{SecurityLevel, SmtpTransportBuilder};
@@ -85,18 +131,35 @@ use lettre::transport::smtp::SUBMISSION_PORT;
 use lettre::transport::EmailTransport;
 
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::iter::{Iterator, FlatMap};
+use std::mem;
 use std::fs;
 use std::ptr;
-use std::io::{self, Read, Seek, SeekFrom};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::net::IpAddr;
+use std::path::Path;
 use std::str::FromStr;
 use std::vec::IntoIter;
 
 use ::utils::CONFIG;
+use ::utils::config::live;
+use ::utils::totp;
 use ::utils::error::*;
-use super::version::NugetToSemver;
+use super::version::{NugetVersion, VersionRange};
 use super::storage::Storage;
-use super::xml::FromNugetXml;
+use super::signature;
+use super::scanner::{self, Verdict};
+use super::scripts;
+use super::contents;
+use super::diff;
+use super::assets::{self, AssetKind};
+use super::symbols;
+use super::validation::{self, is_root_nuspec};
+use super::webhook::sign as sign_webhook;
+use super::xml::{FromNugetXml, ToNugetXml, merge_nuspec};
+use super::oidc::Identity;
 
 include!("schema/package.rs");
 include!("schema/packageversion.rs");
@@ -107,3 +170,13 @@ include!("schema/packageversionhasauthor.rs");
 include!("schema/tag.rs");
 include!("schema/user.rs");
 include!("schema/packagehastag.rs");
+include!("schema/auditevent.rs");
+include!("schema/migration.rs");
+include!("schema/backup.rs");
+include!("schema/webhook.rs");
+include!("schema/installscript.rs");
+include!("schema/packagediff.rs");
+include!("schema/packageasset.rs");
+include!("schema/symbolfile.rs");
+include!("schema/deprecation.rs");
+include!("schema/advisory.rs");
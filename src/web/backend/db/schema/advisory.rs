@@ -0,0 +1,190 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AdvisorySeverity
+{
+    Low,
+    Moderate,
+    High,
+    Critical,
+}
+
+impl AdvisorySeverity
+{
+    pub fn all() -> Vec<AdvisorySeverity>
+    {
+        vec![AdvisorySeverity::Low, AdvisorySeverity::Moderate, AdvisorySeverity::High, AdvisorySeverity::Critical]
+    }
+
+    pub fn as_str(&self) -> &'static str
+    {
+        match *self {
+            AdvisorySeverity::Low => "Low",
+            AdvisorySeverity::Moderate => "Moderate",
+            AdvisorySeverity::High => "High",
+            AdvisorySeverity::Critical => "Critical",
+        }
+    }
+
+    /// Severity as NuGet's vulnerability metadata counts it, 0 (low) to 3 (critical)
+    pub fn level(&self) -> i32
+    {
+        match *self {
+            AdvisorySeverity::Low => 0,
+            AdvisorySeverity::Moderate => 1,
+            AdvisorySeverity::High => 2,
+            AdvisorySeverity::Critical => 3,
+        }
+    }
+}
+
+impl FromStr for AdvisorySeverity
+{
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<AdvisorySeverity, ()>
+    {
+        AdvisorySeverity::all().into_iter().find(|severity| severity.as_str() == s).ok_or(())
+    }
+}
+
+/// Known security issue of a version, `url` points to the published advisory
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
+#[insertable_into(advisory)]
+pub struct Advisory
+{
+    id: String,
+    version: String,
+    url: String,
+    severity: String,
+}
+
+impl Advisory
+{
+    /// Attaches an advisory to `version`, an advisory with the same url is updated
+    pub fn new<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion, url: String, severity: AdvisorySeverity) -> BackendResult<Self>
+    {
+        let this = Advisory {
+            id: version.id.clone(),
+            version: version.version.clone(),
+            url: url,
+            severity: String::from(severity.as_str()),
+        };
+        match connection.transaction(|| {
+            try!(this.remove(connection));
+            err!(insert(&this).into(advisory::table).get_result(connection))
+        }) {
+            Ok(this) => Ok(this),
+            Err(TransactionError::CouldntCreateTransaction(err)) => Err(BackendError::DBError(err)),
+            Err(TransactionError::UserReturnedError(err)) => Err(err),
+        }
+    }
+
+    pub fn get<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion, url: &str) -> BackendResult<Option<Self>>
+    {
+        let found: Vec<Self> = try!(advisory::table.filter(
+            advisory::id.eq(&version.id)
+            .and(advisory::version.eq(&version.version))
+            .and(advisory::url.eq(url))
+        ).load(connection));
+        Ok(found.into_iter().next())
+    }
+
+    pub fn for_version<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion) -> BackendResult<Vec<Self>>
+    {
+        err!(advisory::table.filter(
+            advisory::id.eq(&version.id)
+            .and(advisory::version.eq(&version.version))
+        ).order(advisory::url.asc()).load(connection))
+    }
+
+    pub fn for_package<C: Connection<Backend=Pg>>(connection: &C, package_id: &str) -> BackendResult<Vec<Self>>
+    {
+        err!(advisory::table.filter(advisory::id.eq(package_id)).order(advisory::url.asc()).load(connection))
+    }
+
+    pub fn remove<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<()>
+    {
+        err_discard!(delete(advisory::table.filter(
+            advisory::id.eq(&self.id)
+            .and(advisory::version.eq(&self.version))
+            .and(advisory::url.eq(&self.url))
+        )).execute(connection))
+    }
+
+    /// Stores the advisory unchanged, after its version was replaced by a new upload or from a backup
+    fn restore<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<()>
+    {
+        err_discard!(insert(self).into(advisory::table).execute(connection))
+    }
+
+    pub fn version(&self) -> &str
+    {
+        &self.version
+    }
+
+    pub fn url(&self) -> &str
+    {
+        &self.url
+    }
+
+    pub fn severity(&self) -> AdvisorySeverity
+    {
+        AdvisorySeverity::from_str(&self.severity).unwrap_or(AdvisorySeverity::Critical)
+    }
+
+    /// One line summary for clients, e.g. "Contoso.Legacy 1.0.0 has a known High severity vulnerability: https://..."
+    pub fn describe(&self) -> String
+    {
+        format!("{} {} has a known {} severity vulnerability: {}", self.id, self.version, self.severity().as_str(), self.url)
+    }
+}
+
+impl ToJson for Advisory
+{
+    fn to_json(&self) -> Json
+    {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("url"), self.url.to_json());
+        object.insert(String::from("severity"), self.severity.to_json());
+        Json::Object(object)
+    }
+}
+
+#[test]
+fn advisory_severities() {
+    assert_eq!(AdvisorySeverity::from_str("Low"), Ok(AdvisorySeverity::Low));
+    assert_eq!(AdvisorySeverity::from_str("Moderate"), Ok(AdvisorySeverity::Moderate));
+    assert_eq!(AdvisorySeverity::from_str("High"), Ok(AdvisorySeverity::High));
+    assert_eq!(AdvisorySeverity::from_str("Critical"), Ok(AdvisorySeverity::Critical));
+    assert_eq!(AdvisorySeverity::from_str("critical"), Err(()));
+    assert_eq!(AdvisorySeverity::from_str("3"), Err(()));
+    for (level, severity) in AdvisorySeverity::all().into_iter().enumerate() {
+        assert_eq!(AdvisorySeverity::from_str(severity.as_str()), Ok(severity));
+        assert_eq!(severity.level(), level as i32);
+    }
+    assert!(AdvisorySeverity::Low < AdvisorySeverity::Critical);
+
+    //unknown stored severities are treated as the worst case
+    let advisory = Advisory {
+        id: String::from("Contoso.Legacy"),
+        version: String::from("1.0.0"),
+        url: String::from("https://example.com/advisories/1"),
+        severity: String::from("Severe"),
+    };
+    assert_eq!(advisory.severity(), AdvisorySeverity::Critical);
+    assert_eq!(advisory.describe(), "Contoso.Legacy 1.0.0 has a known Critical severity vulnerability: https://example.com/advisories/1");
+}
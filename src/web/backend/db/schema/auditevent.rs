@@ -0,0 +1,227 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction
+{
+    Upload,
+    Delete,
+    PackageUpdate,
+    VersionUpdate,
+    Transfer,
+    ApiKeyReset,
+    ApiKeyRevoke,
+    UserRegister,
+    UserUpdate,
+    TotpEnable,
+    TotpDisable,
+    Retention,
+    Quarantine,
+    SymbolUpload,
+    Deprecate,
+    Advisory,
+}
+
+impl AuditAction
+{
+    pub fn all() -> Vec<AuditAction>
+    {
+        vec![AuditAction::Upload, AuditAction::Delete, AuditAction::PackageUpdate, AuditAction::VersionUpdate, AuditAction::Transfer,
+             AuditAction::ApiKeyReset, AuditAction::ApiKeyRevoke, AuditAction::UserRegister, AuditAction::UserUpdate,
+             AuditAction::TotpEnable, AuditAction::TotpDisable, AuditAction::Retention, AuditAction::Quarantine,
+             AuditAction::SymbolUpload, AuditAction::Deprecate, AuditAction::Advisory]
+    }
+
+    pub fn as_str(&self) -> &'static str
+    {
+        match *self {
+            AuditAction::Upload => "upload",
+            AuditAction::Delete => "delete",
+            AuditAction::PackageUpdate => "package_update",
+            AuditAction::VersionUpdate => "version_update",
+            AuditAction::Transfer => "transfer",
+            AuditAction::ApiKeyReset => "apikey_reset",
+            AuditAction::ApiKeyRevoke => "apikey_revoke",
+            AuditAction::UserRegister => "user_register",
+            AuditAction::UserUpdate => "user_update",
+            AuditAction::TotpEnable => "totp_enable",
+            AuditAction::TotpDisable => "totp_disable",
+            AuditAction::Retention => "retention",
+            AuditAction::Quarantine => "quarantine",
+            AuditAction::SymbolUpload => "symbol_upload",
+            AuditAction::Deprecate => "deprecate",
+            AuditAction::Advisory => "advisory",
+        }
+    }
+}
+
+impl FromStr for AuditAction
+{
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<AuditAction, ()>
+    {
+        AuditAction::all().into_iter().find(|action| action.as_str() == s).ok_or(())
+    }
+}
+
+/// Restricts the audit events returned by `AuditEvent::find`, unset fields match everything
+#[derive(Default, Debug)]
+pub struct AuditFilter
+{
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub target_id: Option<String>,
+}
+
+#[insertable_into(audit_event)]
+struct NewAuditEvent
+{
+    occurred: NaiveDateTime,
+    actor: String,
+    action: String,
+    target_id: Option<String>,
+    target_version: Option<String>,
+    source_ip: Option<String>,
+    before_metadata: Option<String>,
+    after_metadata: Option<String>,
+}
+
+/// Append-only record of a mutating action, the table rejects updates and deletes
+#[derive(Queryable, Debug)]
+pub struct AuditEvent
+{
+    id: i64,
+    occurred: NaiveDateTime,
+    actor: String,
+    action: String,
+    target_id: Option<String>,
+    target_version: Option<String>,
+    source_ip: Option<String>,
+    before_metadata: Option<String>,
+    after_metadata: Option<String>,
+}
+
+impl AuditEvent
+{
+    /// Records an already performed action, failures are logged instead of failing the action
+    pub fn record<C: Connection<Backend=Pg>>(connection: &C,
+                  actor: &User,
+                  action: AuditAction,
+                  source_ip: IpAddr,
+                  target_id: Option<&str>,
+                  target_version: Option<&str>,
+                  before: Option<Json>,
+                  after: Option<Json>,
+                 )
+    {
+        let event = NewAuditEvent {
+            occurred: UTC::now().naive_utc(),
+            actor: actor.id.clone(),
+            action: String::from(action.as_str()),
+            target_id: target_id.map(String::from),
+            target_version: target_version.map(String::from),
+            source_ip: Some(format!("{}", source_ip)),
+            before_metadata: before.map(|json| json.to_string()),
+            after_metadata: after.map(|json| json.to_string()),
+        };
+        if let Err(err) = insert(&event).into(audit_event::table).execute(connection) {
+            error!(target: "hazel::audit", "Failed to record audit event: {:?}", err);
+        }
+    }
+
+    /// Returns matching events, newest first
+    pub fn find<C: Connection<Backend=Pg>>(connection: &C, filter: &AuditFilter, offset: i64, limit: Option<i64>) -> BackendResult<Vec<Self>>
+    {
+        let mut query = audit_event::table.into_boxed();
+        if let Some(ref actor) = filter.actor {
+            query = query.filter(audit_event::actor.eq(actor.clone()));
+        }
+        if let Some(action) = filter.action {
+            query = query.filter(audit_event::action.eq(action.as_str()));
+        }
+        if let Some(ref target_id) = filter.target_id {
+            query = query.filter(audit_event::target_id.eq(target_id.clone()));
+        }
+        query = query.order(audit_event::id.desc()).offset(offset);
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        err!(query.load(connection))
+    }
+
+    pub fn occurred(&self) -> &NaiveDateTime
+    {
+        &self.occurred
+    }
+
+    pub fn actor(&self) -> &str
+    {
+        &self.actor
+    }
+
+    pub fn action(&self) -> &str
+    {
+        &self.action
+    }
+
+    pub fn target_id(&self) -> Option<&String>
+    {
+        self.target_id.as_ref()
+    }
+
+    pub fn target_version(&self) -> Option<&String>
+    {
+        self.target_version.as_ref()
+    }
+
+    pub fn source_ip(&self) -> Option<&String>
+    {
+        self.source_ip.as_ref()
+    }
+
+    pub fn before_metadata(&self) -> Option<&String>
+    {
+        self.before_metadata.as_ref()
+    }
+
+    pub fn after_metadata(&self) -> Option<&String>
+    {
+        self.after_metadata.as_ref()
+    }
+}
+
+impl ToJson for AuditEvent
+{
+    fn to_json(&self) -> Json
+    {
+        let metadata = |text: &Option<String>| match *text {
+            Some(ref text) => Json::from_str(text).unwrap_or(Json::String(text.clone())),
+            None => Json::Null,
+        };
+
+        let mut object = BTreeMap::new();
+        object.insert(String::from("id"), self.id.to_json());
+        object.insert(String::from("occurred"), format!("{}Z", self.occurred.format("%Y-%m-%dT%H:%M:%S%.f")).to_json());
+        object.insert(String::from("actor"), self.actor.to_json());
+        object.insert(String::from("action"), self.action.to_json());
+        object.insert(String::from("target_id"), self.target_id.to_json());
+        object.insert(String::from("target_version"), self.target_version.to_json());
+        object.insert(String::from("source_ip"), self.source_ip.to_json());
+        object.insert(String::from("before"), metadata(&self.before_metadata));
+        object.insert(String::from("after"), metadata(&self.after_metadata));
+        Json::Object(object)
+    }
+}
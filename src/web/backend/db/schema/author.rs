@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-#[derive(Queryable, Debug, PartialEq, Eq)]
+#[derive(Queryable, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
 #[insertable_into(author)]
 pub struct Author
 {
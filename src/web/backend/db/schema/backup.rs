@@ -0,0 +1,426 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+const BACKUP_FORMAT: u32 = 1;
+const BACKUP_TIMESTAMP_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%.f";
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct BackupManifest
+{
+    format: u32,
+    hazel_version: String,
+    created: String,
+    secrets: bool,
+    files: BTreeMap<String, String>, //path inside the archive -> sha256
+}
+
+/// PackageVersion with its timestamps as strings
+#[derive(RustcEncodable, RustcDecodable)]
+struct BackupVersion
+{
+    id: String,
+    version: String,
+    creation_date: String,
+    title: Option<String>,
+    summary: Option<String>,
+    updated: String,
+    description: Option<String>,
+    version_download_count: i64,
+    release_notes: Option<String>,
+    hash: Option<String>,
+    hash_algorithm: Option<String>,
+    size: i64,
+    icon_url: Option<String>,
+    signature_type: Option<String>,
+    signer: Option<String>,
+    signer_fingerprint: Option<String>,
+    scan_status: Option<String>,
+    scan_result: Option<String>,
+    original_version: Option<String>,
+}
+
+impl BackupVersion
+{
+    fn from_version(version: &PackageVersion) -> BackupVersion
+    {
+        BackupVersion {
+            id: version.id.clone(),
+            version: version.version.clone(),
+            creation_date: version.creation_date.format(BACKUP_TIMESTAMP_FORMAT).to_string(),
+            title: version.title.clone(),
+            summary: version.summary.clone(),
+            updated: version.updated.format(BACKUP_TIMESTAMP_FORMAT).to_string(),
+            description: version.description.clone(),
+            version_download_count: version.version_download_count,
+            release_notes: version.release_notes.clone(),
+            hash: version.hash.clone(),
+            hash_algorithm: version.hash_algorithm.clone(),
+            size: version.size,
+            icon_url: version.icon_url.clone(),
+            signature_type: version.signature_type.clone(),
+            signer: version.signer.clone(),
+            signer_fingerprint: version.signer_fingerprint.clone(),
+            scan_status: version.scan_status.clone(),
+            scan_result: version.scan_result.clone(),
+            original_version: version.original_version.clone(),
+        }
+    }
+
+    fn into_version(self) -> BackendResult<PackageVersion>
+    {
+        let timestamp = |value: &str| NaiveDateTime::parse_from_str(value, BACKUP_TIMESTAMP_FORMAT)
+            .map_err(|_| BackendError::from(BackupError::InvalidArchive(format!("Invalid timestamp \"{}\"", value).into())));
+        Ok(PackageVersion {
+            creation_date: try!(timestamp(&self.creation_date)),
+            updated: try!(timestamp(&self.updated)),
+            id: self.id,
+            version: self.version,
+            title: self.title,
+            summary: self.summary,
+            description: self.description,
+            version_download_count: self.version_download_count,
+            release_notes: self.release_notes,
+            hash: self.hash,
+            hash_algorithm: self.hash_algorithm,
+            size: self.size,
+            icon_url: self.icon_url,
+            signature_type: self.signature_type,
+            signer: self.signer,
+            signer_fingerprint: self.signer_fingerprint,
+            scan_status: self.scan_status,
+            scan_result: self.scan_result,
+            original_version: self.original_version,
+        })
+    }
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct BackupData
+{
+    users: Vec<User>,
+    packages: Vec<Package>,
+    versions: Vec<BackupVersion>,
+    tags: Vec<Tag>,
+    package_tags: Vec<PackageHasTag>,
+    authors: Vec<Author>,
+    version_authors: Vec<PackageVersionHasAuthor>,
+    dependencies: Vec<Dependency>,
+    version_dependencies: Vec<PackageVersionHasDependency>,
+    //missing in backups of older versions
+    install_scripts: Option<Vec<InstallScript>>,
+    assets: Option<Vec<PackageAsset>>,
+    deprecations: Option<Vec<Deprecation>>,
+    advisories: Option<Vec<Advisory>>,
+    symbol_files: Option<Vec<SymbolFile>>,
+    webhooks: Option<Vec<Webhook>>,
+}
+
+struct HashingReader<R: Read>
+{
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        let read = try!(self.inner.read(buf));
+        self.hasher.input(&buf[..read]);
+        Ok(read)
+    }
+}
+
+fn sha256<R: Read>(reader: R) -> io::Result<String>
+{
+    let mut reader = HashingReader { inner: reader, hasher: Sha256::new() };
+    try!(io::copy(&mut reader, &mut io::sink()));
+    Ok(reader.hasher.result_str())
+}
+
+fn blob_path(version: &PackageVersion) -> String
+{
+    format!("packages/{}/{}.nupkg", version.id, version.version)
+}
+
+fn symbols_blob_path(version: &PackageVersion) -> String
+{
+    format!("packages/{}/{}.snupkg", version.id, version.version)
+}
+
+fn append_file<W: Write, R: Read>(builder: &mut Builder<W>, path: &str, size: u64, data: R) -> io::Result<()>
+{
+    let mut header = Header::new_gnu();
+    try!(header.set_path(path));
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(UTC::now().timestamp() as u64);
+    header.set_cksum();
+    builder.append(&header, data)
+}
+
+fn invalid_json<E: fmt::Display>(file: &str, err: E) -> BackendError
+{
+    BackupError::InvalidArchive(format!("{}: {}", file, err).into()).into()
+}
+
+/// Writes all users, packages and their files to a tar archive, starting with a manifest of sha256 sums.
+/// Without `secrets` password hashes, API-Keys, second factors and webhook secrets are left out.
+pub fn export_backup<C: Connection<Backend=Pg>, W: Write>(connection: &C, storage: &Storage, writer: W, secrets: bool) -> BackendResult<()>
+{
+    let mut users: Vec<User> = try!(hazeluser::table.load(connection));
+    if !secrets {
+        for user in users.iter_mut() {
+            user.password = None;
+            user.apikey = None;
+            user.totp_secret = None;
+            user.totp_recovery_codes = None;
+            user.totp_last_step = None;
+        }
+    }
+    let mut webhooks: Vec<Webhook> = try!(webhook::table.load(connection));
+    if !secrets {
+        for webhook in webhooks.iter_mut() {
+            webhook.secret = None;
+        }
+    }
+    let versions: Vec<PackageVersion> = try!(packageversion::table.load(connection));
+    let symbol_files: Vec<SymbolFile> = try!(symbol_file::table.load(connection));
+    //versions with a stored symbol package
+    let with_symbols: Vec<&PackageVersion> = versions.iter()
+        .filter(|version| symbol_files.iter().any(|symbol| symbol.id == version.id && symbol.version == version.version))
+        .collect();
+
+    let data = BackupData {
+        users: users,
+        packages: try!(package::table.load(connection)),
+        versions: versions.iter().map(BackupVersion::from_version).collect(),
+        tags: try!(tag::table.load(connection)),
+        package_tags: try!(package_has_tag::table.load(connection)),
+        authors: try!(author::table.load(connection)),
+        version_authors: try!(packageversion_has_author::table.load(connection)),
+        dependencies: try!(dependency::table.load(connection)),
+        version_dependencies: try!(packageversion_has_dependency::table.load(connection)),
+        install_scripts: Some(try!(install_script::table.load(connection))),
+        assets: Some(try!(package_asset::table.load(connection))),
+        deprecations: Some(try!(deprecation::table.load(connection))),
+        advisories: Some(try!(advisory::table.load(connection))),
+        symbol_files: Some(symbol_files),
+        webhooks: Some(webhooks),
+    };
+    let data = try!(json::encode(&data).map_err(|err| invalid_json("data.json", err)));
+
+    let mut files = BTreeMap::new();
+    files.insert(String::from("data.json"), try!(sha256(data.as_bytes())));
+    for version in versions.iter() {
+        files.insert(blob_path(version), try!(sha256(try!(storage.get(version)))));
+    }
+    for version in with_symbols.iter() {
+        files.insert(symbols_blob_path(version), try!(sha256(try!(storage.get_symbols(version)))));
+    }
+
+    let manifest = BackupManifest {
+        format: BACKUP_FORMAT,
+        hazel_version: String::from(env!("CARGO_PKG_VERSION")),
+        created: UTC::now().naive_utc().format(BACKUP_TIMESTAMP_FORMAT).to_string(),
+        secrets: secrets,
+        files: files,
+    };
+    let manifest = try!(json::encode(&manifest).map_err(|err| invalid_json("manifest.json", err)));
+
+    let mut builder = Builder::new(writer);
+    try!(append_file(&mut builder, "manifest.json", manifest.len() as u64, manifest.as_bytes()));
+    try!(append_file(&mut builder, "data.json", data.len() as u64, data.as_bytes()));
+    for version in versions.iter() {
+        let file = try!(storage.get(version));
+        let size = try!(file.metadata()).len();
+        try!(append_file(&mut builder, &blob_path(version), size, file));
+    }
+    for version in with_symbols.iter() {
+        let file = try!(storage.get_symbols(version));
+        let size = try!(file.metadata()).len();
+        try!(append_file(&mut builder, &symbols_blob_path(version), size, file));
+    }
+    try!(builder.into_inner());
+    Ok(())
+}
+
+/// Reads the archive, storing package files as they come. `versions` and `stored` allow the caller to clean up on failure.
+fn read_backup<R: Read>(archive: &mut Archive<R>, storage: &Storage, versions: &mut Vec<PackageVersion>, stored: &mut Vec<usize>) -> BackendResult<BackupData>
+{
+    let mut manifest: Option<BackupManifest> = None;
+    let mut data: Option<BackupData> = None;
+    let mut blobs = BTreeMap::new();
+    let mut symbol_blobs = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+
+    for entry in try!(archive.entries()) {
+        let mut entry = try!(entry);
+        let path = try!(entry.path()).to_string_lossy().into_owned();
+
+        if manifest.is_none() {
+            if path != "manifest.json" {
+                return Err(BackupError::InvalidArchive("manifest.json has to be the first file".into()).into());
+            }
+            let mut content = String::new();
+            try!(entry.read_to_string(&mut content));
+            let parsed: BackupManifest = try!(json::decode(&content).map_err(|err| invalid_json("manifest.json", err)));
+            if parsed.format != BACKUP_FORMAT {
+                return Err(BackupError::InvalidArchive(format!("Unsupported format version {}", parsed.format).into()).into());
+            }
+            manifest = Some(parsed);
+            continue;
+        }
+
+        let expected = match manifest.as_ref().unwrap().files.get(&path) {
+            Some(hash) => hash.clone(),
+            None => return Err(BackupError::InvalidArchive(format!("\"{}\" is not listed in the manifest", path).into()).into()),
+        };
+
+        if path == "data.json" {
+            let mut content = String::new();
+            try!(entry.read_to_string(&mut content));
+            if try!(sha256(content.as_bytes())) != expected {
+                return Err(BackupError::HashMismatch(path).into());
+            }
+            let mut parsed: BackupData = try!(json::decode(&content).map_err(|err| invalid_json("data.json", err)));
+            for version in parsed.versions.drain(..) {
+                let version = try!(version.into_version());
+                blobs.insert(blob_path(&version), versions.len());
+                symbol_blobs.insert(symbols_blob_path(&version), versions.len());
+                versions.push(version);
+            }
+            data = Some(parsed);
+        } else {
+            //data.json is written before the package files, so all versions are known here
+            let (index, symbols) = match (blobs.get(&path), symbol_blobs.get(&path)) {
+                (Some(index), _) => (*index, false),
+                (None, Some(index)) => (*index, true),
+                (None, None) => return Err(BackupError::InvalidArchive(format!("\"{}\" belongs to no package version", path).into()).into()),
+            };
+            let mut reader = HashingReader { inner: &mut entry, hasher: Sha256::new() };
+            stored.push(index);
+            if symbols {
+                try!(storage.store_symbols(&versions[index], &mut reader));
+            } else {
+                try!(storage.store(&versions[index], &mut reader));
+            }
+            if reader.hasher.result_str() != expected {
+                return Err(BackupError::HashMismatch(path).into());
+            }
+        }
+        seen.insert(path);
+    }
+
+    let manifest = match manifest {
+        Some(manifest) => manifest,
+        None => return Err(BackupError::InvalidArchive("Archive is empty".into()).into()),
+    };
+    for file in manifest.files.keys() {
+        if !seen.contains(file) {
+            return Err(BackupError::MissingFile(file.clone()).into());
+        }
+    }
+    match data {
+        Some(data) => Ok(data),
+        None => Err(BackupError::MissingFile(String::from("data.json")).into()),
+    }
+}
+
+fn insert_backup<C: Connection<Backend=Pg>>(connection: &C, data: &BackupData, versions: &[PackageVersion]) -> BackendResult<()>
+{
+    //the superuser is created on startup, the archived one replaces it
+    if data.users.iter().any(|user| user.id == "admin") {
+        try!(delete(hazeluser::table.filter(hazeluser::id.eq("admin"))).execute(connection));
+    }
+    for user in data.users.iter() {
+        try!(insert(user).into(hazeluser::table).execute(connection));
+    }
+    for package in data.packages.iter() {
+        try!(insert(package).into(package::table).execute(connection));
+    }
+    for tag in data.tags.iter() {
+        try!(insert(tag).into(tag::table).execute(connection));
+    }
+    for author in data.authors.iter() {
+        try!(insert(author).into(author::table).execute(connection));
+    }
+    for dependency in data.dependencies.iter() {
+        try!(insert(dependency).into(dependency::table).execute(connection));
+    }
+    for version in versions.iter() {
+        try!(insert(version).into(packageversion::table).execute(connection));
+    }
+    for package_tag in data.package_tags.iter() {
+        try!(insert(package_tag).into(package_has_tag::table).execute(connection));
+    }
+    for version_author in data.version_authors.iter() {
+        try!(insert(version_author).into(packageversion_has_author::table).execute(connection));
+    }
+    for version_dependency in data.version_dependencies.iter() {
+        try!(insert(version_dependency).into(packageversion_has_dependency::table).execute(connection));
+    }
+    for install_script in data.install_scripts.iter().flat_map(|scripts| scripts.iter()) {
+        try!(insert(install_script).into(install_script::table).execute(connection));
+    }
+    for asset in data.assets.iter().flat_map(|assets| assets.iter()) {
+        try!(insert(asset).into(package_asset::table).execute(connection));
+    }
+    for deprecation in data.deprecations.iter().flat_map(|deprecations| deprecations.iter()) {
+        try!(deprecation.restore(connection));
+    }
+    for advisory in data.advisories.iter().flat_map(|advisories| advisories.iter()) {
+        try!(advisory.restore(connection));
+    }
+    for symbol in data.symbol_files.iter().flat_map(|symbols| symbols.iter()) {
+        try!(insert(symbol).into(symbol_file::table).execute(connection));
+    }
+    for webhook in data.webhooks.iter().flat_map(|webhooks| webhooks.iter()) {
+        try!(webhook.restore(connection));
+    }
+    Ok(())
+}
+
+/// Restores an archive written by `export_backup`, verifying every file against the manifest.
+/// Only instances without packages and users (besides the superuser) are accepted.
+pub fn import_backup<C: Connection<Backend=Pg>, R: Read>(connection: &C, storage: &Storage, reader: R) -> BackendResult<()>
+{
+    let packages: Vec<Package> = try!(package::table.limit(1).load(connection));
+    let users: Vec<User> = try!(hazeluser::table.filter(hazeluser::id.ne("admin")).limit(1).load(connection));
+    if !packages.is_empty() || !users.is_empty() {
+        return Err(BackupError::NotEmpty.into());
+    }
+
+    let mut archive = Archive::new(reader);
+    let mut versions = Vec::new();
+    let mut stored = Vec::new();
+    let result = read_backup(&mut archive, storage, &mut versions, &mut stored).and_then(|data| {
+        match connection.transaction(|| insert_backup(connection, &data, &versions)) {
+            Ok(()) => Ok(()),
+            Err(TransactionError::CouldntCreateTransaction(err)) => Err(BackendError::DBError(err)),
+            Err(TransactionError::UserReturnedError(err)) => Err(err),
+        }
+    });
+
+    if result.is_err() {
+        //deletes the symbol package of a version as well
+        stored.sort();
+        stored.dedup();
+        for index in stored {
+            storage.delete(&versions[index]);
+        }
+    }
+    result
+}
@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-#[derive(Queryable, Debug, PartialEq, Eq)]
+#[derive(Queryable, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
 #[insertable_into(dependency)]
 #[changeset_for(dependency)]
 pub struct Dependency
@@ -27,7 +27,7 @@ impl Dependency
     pub fn new<C: Connection<Backend=Pg>>(connection: &C,
                 package_version: &PackageVersion,
                 package: &Package,
-                version_req: &VersionReq,
+                version_req: &VersionRange,
            ) -> BackendResult<Self>
     {
         let this = Dependency {
@@ -39,7 +39,7 @@ impl Dependency
         err!(insert(&this).into(dependency::table).get_result(connection))
     }
 
-    pub fn get<C: Connection<Backend=Pg>>(connection: &C, id: &str, version_req: &VersionReq) -> BackendResult<Self>
+    pub fn get<C: Connection<Backend=Pg>>(connection: &C, id: &str, version_req: &VersionRange) -> BackendResult<Self>
     {
         err!(dependency::table.filter(
                 dependency::id.eq(id)
@@ -109,9 +109,9 @@ impl Dependency
         Package::get(connection, &self.id)
     }
 
-    pub fn version_req(&self) -> VersionReq
+    pub fn version_req(&self) -> VersionRange
     {
-        VersionReq::from_str(&self.version_req).unwrap()
+        VersionRange::from_str(&self.version_req).unwrap()
     }
 
     pub fn possible_resolutions<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<Vec<PackageVersion>>
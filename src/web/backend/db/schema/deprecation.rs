@@ -0,0 +1,216 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeprecationReason
+{
+    Legacy,
+    CriticalBugs,
+    Other,
+}
+
+impl DeprecationReason
+{
+    pub fn all() -> Vec<DeprecationReason>
+    {
+        vec![DeprecationReason::Legacy, DeprecationReason::CriticalBugs, DeprecationReason::Other]
+    }
+
+    /// Names used by NuGet's deprecation metadata
+    pub fn as_str(&self) -> &'static str
+    {
+        match *self {
+            DeprecationReason::Legacy => "Legacy",
+            DeprecationReason::CriticalBugs => "CriticalBugs",
+            DeprecationReason::Other => "Other",
+        }
+    }
+}
+
+impl FromStr for DeprecationReason
+{
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<DeprecationReason, ()>
+    {
+        DeprecationReason::all().into_iter().find(|reason| reason.as_str() == s).ok_or(())
+    }
+}
+
+/// Marks a version as deprecated, optionally naming a package to use instead.
+/// `reasons` is a comma separated list of `DeprecationReason`s.
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
+#[insertable_into(deprecation)]
+pub struct Deprecation
+{
+    id: String,
+    version: String,
+    reasons: String,
+    message: Option<String>,
+    alternate_id: Option<String>,
+    alternate_range: Option<String>,
+}
+
+impl Deprecation
+{
+    /// Deprecates `version`, replacing an earlier deprecation of it.
+    /// The alternate package has to exist, its range defaults to any version.
+    pub fn set<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion, reasons: &[DeprecationReason], message: Option<String>, alternate: Option<(String, VersionRange)>) -> BackendResult<Self>
+    {
+        if reasons.is_empty() {
+            return Err(BackendError::ValidationFailed(vec![ValidationProblem::new("reasons", "At least one reason is required")]));
+        }
+        let alternate = match alternate {
+            Some((ref id, _)) if id == &version.id => return Err(BackendError::ValidationFailed(vec![
+                ValidationProblem::new("alternate_id", "A package cannot be its own alternate")
+            ])),
+            Some((id, range)) => match Package::get(connection, &id) {
+                Ok(package) => Some((package.id, range)),
+                //mostlikely the package was not found
+                Err(BackendError::DBError(_)) => return Err(BackendError::ValidationFailed(vec![
+                    ValidationProblem::new("alternate_id", format!("Package \"{}\" does not exist", id))
+                ])),
+                Err(err) => return Err(err),
+            },
+            None => None,
+        };
+
+        let this = Deprecation {
+            id: version.id.clone(),
+            version: version.version.clone(),
+            reasons: reasons.iter().map(|reason| reason.as_str()).collect::<Vec<_>>().join(","),
+            message: message,
+            alternate_id: alternate.as_ref().map(|&(ref id, _)| id.clone()),
+            alternate_range: alternate.map(|(_, range)| format!("{}", range)),
+        };
+        match connection.transaction(|| {
+            try!(Deprecation::remove(connection, version));
+            err!(insert(&this).into(deprecation::table).get_result(connection))
+        }) {
+            Ok(this) => Ok(this),
+            Err(TransactionError::CouldntCreateTransaction(err)) => Err(BackendError::DBError(err)),
+            Err(TransactionError::UserReturnedError(err)) => Err(err),
+        }
+    }
+
+    pub fn get<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion) -> BackendResult<Option<Self>>
+    {
+        let found: Vec<Self> = try!(deprecation::table.filter(
+            deprecation::id.eq(&version.id)
+            .and(deprecation::version.eq(&version.version))
+        ).load(connection));
+        Ok(found.into_iter().next())
+    }
+
+    pub fn for_package<C: Connection<Backend=Pg>>(connection: &C, package_id: &str) -> BackendResult<Vec<Self>>
+    {
+        err!(deprecation::table.filter(deprecation::id.eq(package_id)).load(connection))
+    }
+
+    pub fn remove<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion) -> BackendResult<()>
+    {
+        err_discard!(delete(deprecation::table.filter(
+            deprecation::id.eq(&version.id)
+            .and(deprecation::version.eq(&version.version))
+        )).execute(connection))
+    }
+
+    /// Stores the deprecation unchanged, after its version was replaced by a new upload or from a backup
+    fn restore<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<()>
+    {
+        err_discard!(insert(self).into(deprecation::table).execute(connection))
+    }
+
+    pub fn version(&self) -> &str
+    {
+        &self.version
+    }
+
+    pub fn reasons(&self) -> Vec<DeprecationReason>
+    {
+        self.reasons.split(',').filter_map(|reason| DeprecationReason::from_str(reason.trim()).ok()).collect()
+    }
+
+    pub fn message(&self) -> Option<&String>
+    {
+        self.message.as_ref()
+    }
+
+    pub fn alternate_id(&self) -> Option<&String>
+    {
+        self.alternate_id.as_ref()
+    }
+
+    pub fn alternate_range(&self) -> Option<VersionRange>
+    {
+        self.alternate_range.as_ref().and_then(|range| VersionRange::convert(range).ok())
+    }
+
+    /// One line summary for clients, e.g. "Contoso.Legacy 1.0.0 is deprecated (Legacy), use Contoso.Core"
+    pub fn describe(&self) -> String
+    {
+        let mut description = format!("{} {} is deprecated ({})", self.id, self.version, self.reasons.replace(",", ", "));
+        if let Some(ref message) = self.message {
+            description.push_str(&format!(": {}", message));
+        }
+        if let Some(ref alternate_id) = self.alternate_id {
+            description.push_str(&format!(", use {}", alternate_id));
+            match self.alternate_range() {
+                Some(ref range) if range.to_nuget().is_some() => description.push_str(&format!(" {}", range)),
+                _ => {},
+            }
+        }
+        description
+    }
+}
+
+impl ToJson for Deprecation
+{
+    fn to_json(&self) -> Json
+    {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("reasons"), self.reasons().iter().map(|reason| String::from(reason.as_str())).collect::<Vec<_>>().to_json());
+        object.insert(String::from("message"), self.message.to_json());
+        object.insert(String::from("alternate_id"), self.alternate_id.to_json());
+        object.insert(String::from("alternate_range"), self.alternate_range.to_json());
+        Json::Object(object)
+    }
+}
+
+#[test]
+fn deprecation_reasons() {
+    assert_eq!(DeprecationReason::from_str("Legacy"), Ok(DeprecationReason::Legacy));
+    assert_eq!(DeprecationReason::from_str("CriticalBugs"), Ok(DeprecationReason::CriticalBugs));
+    assert_eq!(DeprecationReason::from_str("Other"), Ok(DeprecationReason::Other));
+    assert_eq!(DeprecationReason::from_str("legacy"), Err(()));
+    assert_eq!(DeprecationReason::from_str(""), Err(()));
+    for reason in DeprecationReason::all() {
+        assert_eq!(DeprecationReason::from_str(reason.as_str()), Ok(reason));
+    }
+
+    //unknown names and surrounding whitespace in the stored list are skipped
+    let mut deprecation = Deprecation {
+        id: String::from("Contoso.Legacy"),
+        version: String::from("1.0.0"),
+        reasons: String::from("Legacy, Unknown,CriticalBugs,"),
+        message: Some(String::from("Unmaintained")),
+        alternate_id: Some(String::from("Contoso.Core")),
+        alternate_range: None,
+    };
+    assert_eq!(deprecation.reasons(), vec![DeprecationReason::Legacy, DeprecationReason::CriticalBugs]);
+
+    deprecation.reasons = String::from("Legacy,CriticalBugs");
+    assert_eq!(deprecation.describe(), "Contoso.Legacy 1.0.0 is deprecated (Legacy, CriticalBugs): Unmaintained, use Contoso.Core");
+}
@@ -0,0 +1,63 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+/// Chocolatey script extracted from a package on upload
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
+#[insertable_into(install_script)]
+pub struct InstallScript
+{
+    id: String,
+    version: String,
+    name: String,
+    content: String,
+}
+
+impl InstallScript
+{
+    fn new<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion, name: String, content: String) -> BackendResult<Self>
+    {
+        let this = InstallScript {
+            id: version.id.clone(),
+            version: version.version.clone(),
+            name: name,
+            content: content,
+        };
+        err!(insert(&this).into(install_script::table).get_result(connection))
+    }
+
+    pub fn for_version<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion) -> BackendResult<Vec<Self>>
+    {
+        err!(install_script::table.filter(
+            install_script::id.eq(&version.id)
+            .and(install_script::version.eq(&version.version))
+        ).order(install_script::name.asc()).load(connection))
+    }
+
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    pub fn content(&self) -> &str
+    {
+        &self.content
+    }
+
+    pub fn findings(&self) -> Vec<scripts::Finding>
+    {
+        scripts::inspect(&self.content)
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+/// Versions of the migrations in `directory` that were not applied to the database yet
+pub fn pending_migrations<C: Connection<Backend=Pg>>(connection: &C, directory: &Path) -> BackendResult<Vec<String>>
+{
+    let applied: Vec<String> = try!(__diesel_schema_migrations::table.select(__diesel_schema_migrations::version).load(connection));
+
+    let mut pending = Vec::new();
+    for entry in try!(fs::read_dir(directory)) {
+        let entry = try!(entry);
+        if !try!(entry.file_type()).is_dir() {
+            continue;
+        }
+        //diesel uses the part of the directory name before the first underscore as version
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let version = String::from(name.split('_').next().unwrap_or(""));
+        if !version.is_empty() && !applied.contains(&version) {
+            pending.push(version);
+        }
+    }
+    pending.sort();
+    Ok(pending)
+}
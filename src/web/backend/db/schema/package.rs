@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-#[derive(Queryable, Debug, RustcEncodable)]
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
 #[insertable_into(package)]
 #[changeset_for(package, treat_none_as_null="true")]
 pub struct Package
@@ -63,7 +63,7 @@ impl Package
     pub fn update<C: Connection<Backend=Pg>>(&self, connection: &C, storage: &Storage) -> BackendResult<Self>
     {
         let result = try!(update(package::table.filter(package::id.eq(&self.id))).set(self).get_result(connection));
-        for ver in try!(self.versions(connection)) {
+        for mut ver in try!(self.versions(connection)) {
             try!(ver.update(connection, storage));
         }
         Ok(result)
@@ -74,6 +74,17 @@ impl Package
         User::get(connection, &self.maintainer)
     }
 
+    pub fn maintainer_id(&self) -> &str
+    {
+        &self.maintainer
+    }
+
+    /// Total size of all versions in bytes
+    pub fn storage_usage<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<i64>
+    {
+        Ok(try!(self.versions(connection)).iter().fold(0, |sum, version| sum + version.size))
+    }
+
     pub fn update_maintainer<C: Connection<Backend=Pg>>(&mut self, connection: &C, maintainer: &User) -> BackendResult<Self>
     {
         self.maintainer = maintainer.id.clone();
@@ -86,6 +97,12 @@ impl Package
             for tag in try!(self.tags(connection)) {
                 try!(tag.disconnect(connection, &self));
             }
+            //registrations may not carry over to whoever uploads a package with this id next
+            try!(delete(webhook_delivery::table.filter(
+                webhook_delivery::package_id.eq(&self.id)
+                .and(webhook_delivery::webhook_id.is_not_null())
+            )).execute(connection));
+            try!(delete(webhook::table.filter(webhook::package_id.eq(&self.id))).execute(connection));
             err_discard!(delete(package::table.filter(package::id.eq(&self.id))).execute(connection))
         }) {
             Ok(_) => Ok(()),
@@ -137,3 +154,23 @@ impl PartialEq for Package
     }
 }
 impl Eq for Package {}
+
+impl ToJson for Package
+{
+    fn to_json(&self) -> Json
+    {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("id"), self.id.to_json());
+        object.insert(String::from("project_url"), self.project_url.to_json());
+        object.insert(String::from("license_url"), self.license_url.to_json());
+        object.insert(String::from("license_acceptance"), self.license_acceptance.to_json());
+        object.insert(String::from("project_source_url"), self.project_source_url.to_json());
+        object.insert(String::from("package_source_url"), self.package_source_url.to_json());
+        object.insert(String::from("docs_url"), self.docs_url.to_json());
+        object.insert(String::from("mailing_list_url"), self.mailing_list_url.to_json());
+        object.insert(String::from("bug_tracker_url"), self.bug_tracker_url.to_json());
+        object.insert(String::from("report_abuse_url"), self.report_abuse_url.to_json());
+        object.insert(String::from("maintainer"), self.maintainer.to_json());
+        Json::Object(object)
+    }
+}
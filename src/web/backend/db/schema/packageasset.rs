@@ -0,0 +1,88 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+/// Icon, readme or license of a version, either embedded in the package or fetched from its iconUrl.
+/// Fetched icons are named after their url, failed fetches are stored without content.
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
+#[insertable_into(package_asset)]
+pub struct PackageAsset
+{
+    id: String,
+    version: String,
+    kind: String,
+    name: String,
+    media_type: Option<String>,
+    content: Option<Vec<u8>>,
+}
+
+impl PackageAsset
+{
+    fn new<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion, kind: AssetKind, name: String, media_type: Option<String>, content: Option<Vec<u8>>) -> BackendResult<Self>
+    {
+        let this = PackageAsset {
+            id: version.id.clone(),
+            version: version.version.clone(),
+            kind: String::from(kind.as_str()),
+            name: name,
+            media_type: media_type,
+            content: content,
+        };
+        err!(insert(&this).into(package_asset::table).get_result(connection))
+    }
+
+    pub fn get<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion, kind: AssetKind) -> BackendResult<Option<Self>>
+    {
+        let assets: Vec<Self> = try!(package_asset::table.filter(
+            package_asset::id.eq(&version.id)
+            .and(package_asset::version.eq(&version.version))
+            .and(package_asset::kind.eq(kind.as_str()))
+        ).load(connection));
+        Ok(assets.into_iter().next())
+    }
+
+    /// Versions with an iconUrl, that was never fetched and that do not embed their icon
+    pub fn missing_remote_icons<C: Connection<Backend=Pg>>(connection: &C) -> BackendResult<Vec<PackageVersion>>
+    {
+        let icons: Vec<Self> = try!(package_asset::table.filter(package_asset::kind.eq(AssetKind::Icon.as_str())).load(connection));
+        let icons: BTreeSet<(String, String)> = icons.into_iter().map(|icon| (icon.id, icon.version)).collect();
+        Ok(try!(PackageVersion::all(connection)).into_iter()
+            .filter(|version| version.icon_url.is_some() && !icons.contains(&(version.id.clone(), version.version.clone())))
+            .collect())
+    }
+
+    pub fn store_remote_icon<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion, url: String, icon: Option<(String, Vec<u8>)>) -> BackendResult<Self>
+    {
+        match icon {
+            Some((media_type, content)) => PackageAsset::new(connection, version, AssetKind::Icon, url, Some(media_type), Some(content)),
+            None => PackageAsset::new(connection, version, AssetKind::Icon, url, None, None),
+        }
+    }
+
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    pub fn media_type(&self) -> Option<&String>
+    {
+        self.media_type.as_ref()
+    }
+
+    pub fn content(&self) -> Option<&Vec<u8>>
+    {
+        self.content.as_ref()
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+/// Metadata field with differing values
+pub struct FieldChange
+{
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Dependency that was added, removed or got a different version requirement
+pub struct DependencyChange
+{
+    pub id: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// File of the archive that was added, removed or modified
+pub struct FileChange
+{
+    pub path: String,
+    pub change: &'static str,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+}
+
+/// Unified diff of a text file, the nuspec or an install script
+pub struct TextDiff
+{
+    pub name: String,
+    pub diff: String,
+}
+
+/// Changes from `base` to `version` of the same package
+pub struct PackageDiff
+{
+    pub id: String,
+    pub base: String,
+    pub version: String,
+    pub metadata: Vec<FieldChange>,
+    pub dependencies: Vec<DependencyChange>,
+    pub files: Vec<FileChange>,
+    pub texts: Vec<TextDiff>,
+}
+
+/// Fields compared besides the authors, id and version always differ or never do
+const DIFF_FIELDS: &'static [&'static str] = &["title", "summary", "description", "release_notes", "icon_url",
+                                               "size", "signature_type", "signer_fingerprint", "scan_status"];
+
+fn json_value(json: Option<&Json>) -> Option<String>
+{
+    match json {
+        None | Some(&Json::Null) => None,
+        Some(&Json::String(ref value)) => Some(value.clone()),
+        Some(other) => Some(format!("{}", other)),
+    }
+}
+
+/// Hashes, nuspec and install scripts of a stored package
+fn snapshot(version: &PackageVersion, storage: &Storage) -> BackendResult<(BTreeMap<String, String>, BTreeMap<String, String>)>
+{
+    let mut zip = try!(ZipArchive::new(try!(version.download(storage))));
+    let hashes = try!(contents::hashes(&mut zip));
+
+    let mut texts = BTreeMap::new();
+    for i in 0..zip.len() {
+        let mut file = try!(zip.by_index(i));
+        if is_root_nuspec(file.name()) {
+            let mut content = Vec::new();
+            try!(file.take(contents::MAX_READ_SIZE).read_to_end(&mut content));
+            texts.insert(format!("{}.nuspec", version.id), String::from_utf8_lossy(&content).into_owned());
+            break;
+        }
+    }
+    for (name, content) in scripts::extract(&mut zip) {
+        texts.insert(name, content);
+    }
+    Ok((hashes, texts))
+}
+
+impl PackageDiff
+{
+    pub fn new<C: Connection<Backend=Pg>>(connection: &C, storage: &Storage, base: &PackageVersion, version: &PackageVersion) -> BackendResult<Self>
+    {
+        let mut metadata = Vec::new();
+        let (old_json, new_json) = (base.to_json(), version.to_json());
+        for field in DIFF_FIELDS.iter() {
+            let old = json_value(old_json.find(*field));
+            let new = json_value(new_json.find(*field));
+            if old != new {
+                metadata.push(FieldChange { field: String::from(*field), old: old, new: new });
+            }
+        }
+        let authors = |version: &PackageVersion| -> BackendResult<Option<String>> {
+            let names: Vec<String> = try!(version.authors(connection)).iter().map(|author| String::from(author.name())).collect();
+            Ok(if names.is_empty() { None } else { Some(names.join(", ")) })
+        };
+        let (old_authors, new_authors) = (try!(authors(base)), try!(authors(version)));
+        if old_authors != new_authors {
+            metadata.push(FieldChange { field: String::from("authors"), old: old_authors, new: new_authors });
+        }
+
+        let requirements = |version: &PackageVersion| -> BackendResult<BTreeMap<String, String>> {
+            Ok(try!(version.dependencies(connection)).into_iter().map(|dependency| (dependency.id.clone(), format!("{}", dependency.version_req()))).collect())
+        };
+        let (old_dependencies, new_dependencies) = (try!(requirements(base)), try!(requirements(version)));
+        let mut dependencies = Vec::new();
+        for id in old_dependencies.keys().chain(new_dependencies.keys()).collect::<BTreeSet<_>>() {
+            let (old, new) = (old_dependencies.get(id).cloned(), new_dependencies.get(id).cloned());
+            if old != new {
+                dependencies.push(DependencyChange { id: id.clone(), old: old, new: new });
+            }
+        }
+
+        let (old_hashes, old_texts) = try!(snapshot(base, storage));
+        let (new_hashes, new_texts) = try!(snapshot(version, storage));
+        let mut files = Vec::new();
+        for path in old_hashes.keys().chain(new_hashes.keys()).collect::<BTreeSet<_>>() {
+            let (old, new) = (old_hashes.get(path).cloned(), new_hashes.get(path).cloned());
+            let change = match (&old, &new) {
+                (&None, &Some(_)) => "added",
+                (&Some(_), &None) => "removed",
+                _ if old != new => "modified",
+                _ => continue,
+            };
+            files.push(FileChange { path: path.clone(), change: change, old_hash: old, new_hash: new });
+        }
+
+        let mut texts = Vec::new();
+        for name in old_texts.keys().chain(new_texts.keys()).collect::<BTreeSet<_>>() {
+            let old = old_texts.get(name).map(|text| &**text).unwrap_or("");
+            let new = new_texts.get(name).map(|text| &**text).unwrap_or("");
+            if let Some(diff) = diff::unified(&format!("{} {}", name, base.version), &format!("{} {}", name, version.version), old, new) {
+                texts.push(TextDiff { name: name.clone(), diff: diff });
+            }
+        }
+
+        Ok(PackageDiff {
+            id: version.id.clone(),
+            base: base.version.clone(),
+            version: version.version.clone(),
+            metadata: metadata,
+            dependencies: dependencies,
+            files: files,
+            texts: texts,
+        })
+    }
+}
+
+impl ToJson for PackageDiff
+{
+    fn to_json(&self) -> Json
+    {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("id"), self.id.to_json());
+        object.insert(String::from("base"), self.base.to_json());
+        object.insert(String::from("version"), self.version.to_json());
+        object.insert(String::from("metadata"), Json::Array(self.metadata.iter().map(|change| {
+            let mut object = BTreeMap::new();
+            object.insert(String::from("field"), change.field.to_json());
+            object.insert(String::from("old"), change.old.to_json());
+            object.insert(String::from("new"), change.new.to_json());
+            Json::Object(object)
+        }).collect()));
+        object.insert(String::from("dependencies"), Json::Array(self.dependencies.iter().map(|change| {
+            let mut object = BTreeMap::new();
+            object.insert(String::from("id"), change.id.to_json());
+            object.insert(String::from("old"), change.old.to_json());
+            object.insert(String::from("new"), change.new.to_json());
+            Json::Object(object)
+        }).collect()));
+        object.insert(String::from("files"), Json::Array(self.files.iter().map(|change| {
+            let mut object = BTreeMap::new();
+            object.insert(String::from("path"), change.path.to_json());
+            object.insert(String::from("change"), change.change.to_json());
+            object.insert(String::from("old_hash"), change.old_hash.to_json());
+            object.insert(String::from("new_hash"), change.new_hash.to_json());
+            Json::Object(object)
+        }).collect()));
+        object.insert(String::from("texts"), Json::Array(self.texts.iter().map(|text| {
+            let mut object = BTreeMap::new();
+            object.insert(String::from("name"), text.name.to_json());
+            object.insert(String::from("diff"), text.diff.to_json());
+            Json::Object(object)
+        }).collect()));
+        Json::Object(object)
+    }
+}
@@ -13,6 +13,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub const SCAN_CLEAN: &'static str = "clean";
+pub const SCAN_QUARANTINED: &'static str = "quarantined";
+pub const SCAN_INFECTED: &'static str = "infected";
+/// Advisory lock held by uploads while they check and use the storage quotas
+const QUOTA_LOCK: i64 = 0x68617a656c;
+
 #[derive(Queryable, Debug)]
 #[insertable_into(packageversion)]
 #[changeset_for(packageversion, treat_none_as_null="true")]
@@ -31,6 +37,12 @@ pub struct PackageVersion
     hash_algorithm: Option<String>,
     size: i64,
     pub icon_url: Option<String>,
+    signature_type: Option<String>,
+    signer: Option<String>,
+    signer_fingerprint: Option<String>,
+    scan_status: Option<String>,
+    scan_result: Option<String>,
+    original_version: Option<String>,
 }
 
 impl PartialEq for PackageVersion
@@ -47,7 +59,7 @@ impl PartialOrd for PackageVersion
     fn partial_cmp(&self, other: &Self) -> Option<Ordering>
     {
         Some(match self.id.cmp(&other.id) {
-            Ordering::Equal => Version::parse(&self.version).unwrap().cmp(&Version::parse(&other.version).unwrap()),
+            Ordering::Equal => self.version().cmp(&other.version()),
             x => x,
         })
     }
@@ -75,29 +87,27 @@ impl PackageVersion
         try!(file.seek(io::SeekFrom::Start(0)));
         hasher.input(&buffer);
         let hash = hasher.result_str();
+        let signature = try!(signature::verify(&buffer));
 
         let mut zip: ZipArchive<R> = try!(ZipArchive::new(file));
-        let nuspec = {
-            let mut nuspec = None;
-            for i in 0..zip.len()
-            {
-                let file = zip.by_index(i).unwrap();
-                if file.name().contains(".nuspec") {
-                    let doc: XmlDocument = try!(XmlDocument::parse(file)).clone();
-                    nuspec = Some(try!(doc.root.ok_or(BackendError::InvalidXml("Empty Xml".into()))));
-                }
-            }
-            match nuspec {
-                Some(nuspec) => nuspec,
-                None => return err!(Err(ZipError::FileNotFound)),
-            }
+        let (nuspec, problems) = validation::validate_archive(&mut zip);
+        let nuspec = match (nuspec, problems.is_empty()) {
+            (Some(nuspec), true) => nuspec,
+            _ => return Err(BackendError::ValidationFailed(problems)),
         };
+        let install_scripts = scripts::extract(&mut zip);
 
         let metadata = try!(nuspec.find_child(|entry| entry.name == "metadata").cloned().ok_or(BackendError::InvalidXml("Xml does not contain \"metadata\" tag".into())));
 
+        let embedded_assets = assets::extract(&mut zip, &metadata);
+
         let id = try!(try!(metadata.find_child(|tag| tag.name == "id").cloned().ok_or(BackendError::InvalidXml("Xml does not contain \"id\" tag".into()))).text.ok_or(BackendError::InvalidXml("\"id\" tag is empty".into())));
         let version_str = try!(try!(metadata.find_child(|tag| tag.name == "version").cloned().ok_or(BackendError::InvalidXml("Xml does not contain \"version\" tag".into()))).text.ok_or(BackendError::InvalidXml("\"version\" tag is empty".into())));
-        let version = try!(Version::best_efford_parse(&version_str));
+        let version = try!(NugetVersion::parse(&version_str).map_err(|_| BackendError::InvalidXml(format!("\"{}\" is no valid NuGet version", version_str).into())));
+
+        if signature.is_none() && signature::required(&id) {
+            return Err(BackendError::SignatureError(SignatureError::Missing));
+        }
 
         let mut this = PackageVersion {
             id: id.clone(),
@@ -113,26 +123,54 @@ impl PackageVersion
             hash_algorithm: Some(String::from("Sha256")),
             size: buffer.len() as i64,
             icon_url: None,
+            signature_type: signature.as_ref().map(|signature| String::from(signature.signature_type.as_str())),
+            signer: signature.as_ref().map(|signature| signature.signer.clone()),
+            signer_fingerprint: signature.map(|signature| signature.fingerprint),
+            scan_status: None,
+            scan_result: None,
+            original_version: Some(String::from(version_str.trim())),
         };
         try!(this.set_from_xml(&nuspec));
 
+        //scanned before anything is stored, packages are quarantined if the scanner is unavailable
+        if let Some(scanner) = scanner::configured() {
+            match scanner.scan(&buffer) {
+                Ok(Verdict::Clean) => this.scan_status = Some(String::from(SCAN_CLEAN)),
+                Ok(Verdict::Infected(name)) => return Err(BackendError::Infected(name)),
+                Err(err) => {
+                    warn!(target: "hazel::scanner", "Quarantining {} {}, scanning failed: {}", this.id, this.version, err);
+                    this.scan_status = Some(String::from(SCAN_QUARANTINED));
+                    this.scan_result = Some(format!("{}", err));
+                },
+            }
+        }
+
         match connection.transaction(|| {
-            match PackageVersion::get(connection, &id, &version) {
-                Ok(pkgver) => { try!(pkgver.delete(connection, &storage)); },
-                Err(_) => {},
+            //only the maintainer may touch an existing package, its quota is checked after that and within the transaction
+            if let Ok(pkg) = Package::get(connection, &id) {
+                if &try!(pkg.maintainer(connection)) != user {
+                    return Err(BackendError::PermissionDenied)
+                }
+            }
+            try!(this.check_quota(connection, user));
+
+            //deprecation and advisories of a replaced upload stay with the version
+            let (deprecation, advisories) = match PackageVersion::get(connection, &id, &version) {
+                Ok(pkgver) => {
+                    let kept = (try!(Deprecation::get(connection, &pkgver)), try!(Advisory::for_version(connection, &pkgver)));
+                    try!(pkgver.remove(connection, &storage, false));
+                    kept
+                },
+                Err(_) => (None, Vec::new()),
             };
 
             let package = match Package::get(connection, &id) {
                 Ok(mut pkg) => {
-                    if &try!(pkg.maintainer(connection)) == user {
-                        if try!(pkg.versions(connection)).into_iter().all(|ver| this > ver) {
-                            try!(pkg.set_from_xml(&nuspec));
-                            try!(pkg.update(connection, storage))
-                        } else {
-                            pkg
-                        }
+                    if try!(pkg.versions(connection)).into_iter().all(|ver| this > ver) {
+                        try!(pkg.set_from_xml(&nuspec));
+                        try!(pkg.update(connection, storage))
                     } else {
-                        return Err(BackendError::PermissionDenied)
+                        pkg
                     }
                 },
                 Err(_) => {
@@ -144,6 +182,19 @@ impl PackageVersion
 
             let this = try!(insert(&this).into(packageversion::table).get_result(connection));
 
+            for (name, content) in install_scripts {
+                try!(InstallScript::new(connection, &this, name, content));
+            }
+            for (kind, name, media_type, content) in embedded_assets {
+                try!(PackageAsset::new(connection, &this, kind, name, Some(String::from(media_type)), Some(content)));
+            }
+            if let Some(deprecation) = deprecation {
+                try!(deprecation.restore(connection));
+            }
+            for advisory in advisories {
+                try!(advisory.restore(connection));
+            }
+
             let tags: Option<&Element> = metadata.find_child(|entry| entry.name == "tags");
             let authors: Option<&Element> = metadata.find_child(|entry| entry.name == "authors");
             let dependencies: Option<&Element> = metadata.find_child(|entry| entry.name == "dependencies");
@@ -162,8 +213,8 @@ impl PackageVersion
 
                         let found_id = try!(dependency.attributes.get("id").ok_or(BackendError::InvalidXml("Invalid Dependency, \"id\" attribute is missing".into())));
                         let req = match dependency.attributes.get("version") {
-                            Some(ver) => try!(VersionReq::convert(ver)),
-                            None => VersionReq::any(),
+                            Some(ver) => try!(VersionRange::convert(ver)),
+                            None => VersionRange::any(),
                         };
 
                         match Dependency::get(connection, &*found_id, &req) {
@@ -217,7 +268,51 @@ impl PackageVersion
         }
     }
 
-    pub fn get<C: Connection<Backend=Pg>>(connection: &C, id: &str, version: &Version) -> BackendResult<Self>
+    /// Rejects the upload, if it would exceed the quota of its package or of the package maintainer.
+    /// An existing upload of the same version gets replaced and does not count.
+    /// Has to run inside the upload transaction, concurrent uploads wait for it to finish.
+    fn check_quota<C: Connection<Backend=Pg>>(&self, connection: &C, user: &User) -> BackendResult<()>
+    {
+        let live = live();
+        if live.max_package_storage_mb.is_none() && live.max_user_storage_mb.is_none() {
+            return Ok(());
+        }
+        try!(connection.batch_execute(&format!("SELECT pg_advisory_xact_lock({})", QUOTA_LOCK)));
+
+        let megabytes = |bytes: i64| bytes as f64 / 1048576.0;
+        let usage = |versions: Vec<PackageVersion>| versions.iter()
+            .filter(|version| !(version.id == self.id && version.version == self.version))
+            .fold(0, |sum, version| sum + version.size);
+
+        if let Some(limit) = live.max_package_storage_mb {
+            let used = usage(try!(packageversion::table.filter(packageversion::id.eq(&self.id)).load(connection)));
+            if used + self.size > limit as i64 * 1048576 {
+                return Err(BackendError::QuotaExceeded(format!("Package \"{}\" may use {} MB, {:.1} MB are used already and the upload has {:.1} MB",
+                    self.id, limit, megabytes(used), megabytes(self.size)).into()));
+            }
+        }
+
+        if let Some(limit) = live.max_user_storage_mb {
+            //uploads of an existing package count for its maintainer
+            let maintainer = match Package::get(connection, &self.id) {
+                Ok(package) => package.maintainer,
+                Err(_) => user.id.clone(),
+            };
+            let packages: Vec<Package> = try!(package::table.filter(package::maintainer.eq(&maintainer)).load(connection));
+            let mut used = 0;
+            for package in packages {
+                used += usage(try!(package.versions(connection)));
+            }
+            if used + self.size > limit as i64 * 1048576 {
+                return Err(BackendError::QuotaExceeded(format!("\"{}\" may use {} MB for all maintained packages, {:.1} MB are used already and the upload has {:.1} MB",
+                    maintainer, limit, megabytes(used), megabytes(self.size)).into()));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get<C: Connection<Backend=Pg>>(connection: &C, id: &str, version: &NugetVersion) -> BackendResult<Self>
     {
         err!(packageversion::table.filter(packageversion::id.eq(id).and(packageversion::version.eq(format!("{}", version)))).first(connection))
     }
@@ -227,37 +322,157 @@ impl PackageVersion
         err!(packageversion::table.load(connection))
     }
 
-    pub fn update<C: Connection<Backend=Pg>>(&self, connection: &C, storage: &Storage) -> BackendResult<Self>
+    /// Number of stored versions and their total size in bytes
+    pub fn totals<C: Connection<Backend=Pg>>(connection: &C) -> BackendResult<(i64, i64)>
     {
-        match connection.transaction(|| {
-            let file = try!(storage.get(self));
-            let mut archive = try!(ZipArchive::new(file));
+        err!(packageversion::table.select(sql::<(BigInt, BigInt)>("COUNT(*), CAST(COALESCE(SUM(size), 0) AS BIGINT)")).first(connection))
+    }
 
-            let stream = io::Cursor::new(Vec::new());
-            let mut new_archive = ZipWriter::new(stream);
+    /// Downloads of all versions per package id, ordered by id
+    pub fn downloads_per_package<C: Connection<Backend=Pg>>(connection: &C) -> BackendResult<Vec<(String, i64)>>
+    {
+        //diesel has no GROUP BY, the window sums are the same for every row of a package
+        err!(packageversion::table.select(sql::<(Text, BigInt)>("DISTINCT id, CAST(SUM(version_download_count) OVER (PARTITION BY id) AS BIGINT)"))
+             .order(packageversion::id.asc()).load(connection))
+    }
 
-            for i in 0..archive.len()
-            {
-                let mut file = try!(archive.by_index(i));
-                try!(new_archive.start_file(file.name(), CompressionMethod::Stored)); //TODO configurable?
-                try!(io::copy(&mut file, &mut new_archive));
+    /// Renames versions stored before NuGet versions were supported, whose version is no longer normalized
+    /// (e.g. "1.0.0+build" or "1.2.3.0") and therefore cannot be found. Their files are renamed as well.
+    /// Returns the number of renamed versions.
+    pub fn normalize_stored_versions<C: Connection<Backend=Pg>>(connection: &C, storage: &Storage) -> BackendResult<usize>
+    {
+        let mut renamed = 0;
+        for mut version in try!(PackageVersion::all(connection)) {
+            let normalized = match NugetVersion::parse(&version.version) {
+                Ok(parsed) => format!("{}", parsed),
+                Err(_) => {
+                    warn!("{} {} is no valid NuGet version and cannot be normalized", version.id, version.version);
+                    continue;
+                },
+            };
+            if normalized == version.version {
+                continue;
+            }
+            if PackageVersion::get(connection, &version.id, &version.version()).is_ok() {
+                warn!("{} {} cannot be renamed to {}, that version exists already", version.id, version.version, normalized);
+                continue;
             }
-            let mut stream = try!(new_archive.finish());
-            try!(stream.seek(io::SeekFrom::Start(0)));
 
-            //if these fail we have a broken package, so delete it
-            let mut file = match storage.rewrite(self, archive.into_inner()) {
-                Ok(x) => x,
-                Err(_) => return Err(BackendError::CriticalUpdateFailure("Unable to rewrite the package".into())),
-            };
-            match io::copy(&mut stream, &mut file) {
-                Ok(x) => x,
-                Err(_) => return Err(BackendError::CriticalUpdateFailure("Unable to write to the rewriten package".into())),
-            };
-            match file.sync_all() {
-                Ok(x) => x,
-                Err(_) => return Err(BackendError::CriticalUpdateFailure("Unable to sync all changes".into())),
-            };
+            let old = mem::replace(&mut version.version, normalized);
+            if version.original_version.is_none() {
+                version.original_version = Some(old.clone());
+            }
+            //the rows referencing the version are moved to the renamed copy before the old one is removed
+            match connection.transaction(|| {
+                try!(insert(&version).into(packageversion::table).execute(connection));
+                try!(update(packageversion_has_dependency::table.filter(packageversion_has_dependency::id.eq(&version.id).and(packageversion_has_dependency::version.eq(&old))))
+                     .set(packageversion_has_dependency::version.eq(&version.version)).execute(connection));
+                try!(update(packageversion_has_author::table.filter(packageversion_has_author::id.eq(&version.id).and(packageversion_has_author::version.eq(&old))))
+                     .set(packageversion_has_author::version.eq(&version.version)).execute(connection));
+                try!(update(install_script::table.filter(install_script::id.eq(&version.id).and(install_script::version.eq(&old))))
+                     .set(install_script::version.eq(&version.version)).execute(connection));
+                try!(update(package_asset::table.filter(package_asset::id.eq(&version.id).and(package_asset::version.eq(&old))))
+                     .set(package_asset::version.eq(&version.version)).execute(connection));
+                try!(update(symbol_file::table.filter(symbol_file::id.eq(&version.id).and(symbol_file::version.eq(&old))))
+                     .set(symbol_file::version.eq(&version.version)).execute(connection));
+                try!(update(deprecation::table.filter(deprecation::id.eq(&version.id).and(deprecation::version.eq(&old))))
+                     .set(deprecation::version.eq(&version.version)).execute(connection));
+                try!(update(advisory::table.filter(advisory::id.eq(&version.id).and(advisory::version.eq(&old))))
+                     .set(advisory::version.eq(&version.version)).execute(connection));
+                try!(delete(packageversion::table.filter(packageversion::id.eq(&version.id).and(packageversion::version.eq(&old)))).execute(connection));
+                try!(storage.rename_version(&version, &old));
+                Ok(())
+            }) {
+                Ok(()) => {
+                    info!("Renamed {} {} to {}", version.id, old, version.version);
+                    renamed += 1;
+                },
+                Err(TransactionError::CouldntCreateTransaction(err)) => return Err(BackendError::DBError(err)),
+                Err(TransactionError::UserReturnedError(err)) => return Err(err),
+            }
+        }
+        Ok(renamed)
+    }
+
+    /// Whether merging `description` into the stored nuspec changes it
+    fn nuspec_outdated(&self, storage: &Storage, description: &Element) -> BackendResult<bool>
+    {
+        let render = |nuspec: Element| format!("{}", XmlDocument {
+            root: Some(nuspec),
+            .. XmlDocument::default()
+        });
+
+        let mut archive = try!(ZipArchive::new(try!(storage.get(self))));
+        for i in 0..archive.len() {
+            let mut file = try!(archive.by_index(i));
+            if is_root_nuspec(file.name()) {
+                let stored = try!(try!(XmlDocument::parse(&mut file)).root.ok_or(BackendError::InvalidXml("Empty Xml".into())));
+                let mut merged = stored.clone();
+                merge_nuspec(&mut merged, description);
+                return Ok(render(merged) != render(stored));
+            }
+        }
+        Ok(false)
+    }
+
+    /// Saves changed metadata. Unsigned packages get their nuspec rewritten to match,
+    /// so clients reading the downloaded package see the same values as the feed.
+    pub fn update<C: Connection<Backend=Pg>>(&mut self, connection: &C, storage: &Storage) -> BackendResult<Self>
+    {
+        match connection.transaction(|| {
+            let description = try!(self.xml_description(connection));
+            //rewriting the archive would invalidate its signature, unchanged ones keep their hash and size
+            if !self.is_signed() && try!(self.nuspec_outdated(storage, &description)) {
+                let file = try!(storage.get(self));
+                let mut archive = try!(ZipArchive::new(file));
+
+                let stream = io::Cursor::new(Vec::new());
+                let mut new_archive = ZipWriter::new(stream);
+
+                for i in 0..archive.len()
+                {
+                    let mut file = try!(archive.by_index(i));
+                    //we can only write stored and deflated entries
+                    let compression = match file.compression() {
+                        CompressionMethod::Stored => CompressionMethod::Stored,
+                        _ => CompressionMethod::Deflated,
+                    };
+                    try!(new_archive.start_file(file.name(), compression));
+                    if is_root_nuspec(file.name()) {
+                        let mut nuspec = try!(try!(XmlDocument::parse(&mut file)).root.ok_or(BackendError::InvalidXml("Empty Xml".into())));
+                        merge_nuspec(&mut nuspec, &description);
+                        let document = XmlDocument {
+                            root: Some(nuspec),
+                            .. XmlDocument::default()
+                        };
+                        try!(write!(new_archive, "{}", document));
+                    } else {
+                        try!(io::copy(&mut file, &mut new_archive));
+                    }
+                }
+                let buffer = try!(new_archive.finish()).into_inner();
+
+                let mut hasher = Sha256::new();
+                hasher.input(&buffer);
+                self.hash = Some(hasher.result_str());
+                self.hash_algorithm = Some(String::from("Sha256"));
+                self.size = buffer.len() as i64;
+                let mut stream = io::Cursor::new(buffer);
+
+                //if these fail we have a broken package, so delete it
+                let mut file = match storage.rewrite(self, archive.into_inner()) {
+                    Ok(x) => x,
+                    Err(_) => return Err(BackendError::CriticalUpdateFailure("Unable to rewrite the package".into())),
+                };
+                match io::copy(&mut stream, &mut file) {
+                    Ok(x) => x,
+                    Err(_) => return Err(BackendError::CriticalUpdateFailure("Unable to write to the rewriten package".into())),
+                };
+                match file.sync_all() {
+                    Ok(x) => x,
+                    Err(_) => return Err(BackendError::CriticalUpdateFailure("Unable to sync all changes".into())),
+                };
+            }
 
             err!(update(packageversion::table.filter(
                     packageversion::id.eq(&self.id)
@@ -277,6 +492,13 @@ impl PackageVersion
     }
 
     pub fn delete<C: Connection<Backend=Pg>>(&self, connection: &C, storage: &Storage) -> BackendResult<()>
+    {
+        self.remove(connection, storage, true)
+    }
+
+    /// Removes the version, its package only goes with it if `remove_empty_package` is set.
+    /// A replaced upload keeps the package, so its webhooks survive.
+    fn remove<C: Connection<Backend=Pg>>(&self, connection: &C, storage: &Storage, remove_empty_package: bool) -> BackendResult<()>
     {
         match connection.transaction(|| {
             {
@@ -310,7 +532,7 @@ impl PackageVersion
                 packageversion::id.eq(&self.id)
                 .and(packageversion::version.eq(&self.version))
             )).execute(connection));
-            if try!(pkg.versions(&*connection)).len() == 0 {
+            if remove_empty_package && try!(pkg.versions(&*connection)).len() == 0 {
                 try!(pkg.delete(&*connection));
             }
             storage.delete(&self);
@@ -441,9 +663,19 @@ impl PackageVersion
         &self.id
     }
 
-    pub fn version(&self) -> Version
+    /// Normalized version, used to identify the package version
+    pub fn version(&self) -> NugetVersion
     {
-        Version::from_str(&self.version).unwrap()
+        NugetVersion::parse(&self.version).unwrap()
+    }
+
+    /// Version as written in the nuspec, uploads of older hazel versions only know the normalized one
+    pub fn original_version(&self) -> &str
+    {
+        match self.original_version {
+            Some(ref original) => original,
+            None => &self.version,
+        }
     }
 
     pub fn creation_date(&self) -> &NaiveDateTime
@@ -451,6 +683,33 @@ impl PackageVersion
         &self.creation_date
     }
 
+    /// Stored version without files or metadata, without a database
+    #[cfg(test)]
+    pub fn uploaded(id: &str, version: &str, created: NaiveDateTime) -> PackageVersion
+    {
+        PackageVersion {
+            id: String::from(id),
+            version: String::from(version),
+            creation_date: created,
+            title: None,
+            summary: None,
+            updated: created,
+            description: None,
+            version_download_count: 0,
+            release_notes: None,
+            hash: None,
+            hash_algorithm: None,
+            size: 0,
+            icon_url: None,
+            signature_type: None,
+            signer: None,
+            signer_fingerprint: None,
+            scan_status: None,
+            scan_result: None,
+            original_version: None,
+        }
+    }
+
     pub fn last_updated(&self) -> &NaiveDateTime
     {
         &self.updated
@@ -476,8 +735,90 @@ impl PackageVersion
         self.size
     }
 
+    pub fn is_signed(&self) -> bool
+    {
+        self.signature_type.is_some()
+    }
+
+    pub fn signature_type(&self) -> Option<&String>
+    {
+        self.signature_type.as_ref()
+    }
+
+    pub fn signer(&self) -> Option<&String>
+    {
+        self.signer.as_ref()
+    }
+
+    pub fn signer_fingerprint(&self) -> Option<&String>
+    {
+        self.signer_fingerprint.as_ref()
+    }
+
+    /// "clean", "quarantined" (not scanned yet) or "infected", None if no scanner was configured
+    pub fn scan_status(&self) -> Option<&String>
+    {
+        self.scan_status.as_ref()
+    }
+
+    /// Signature found or the reason the scan failed
+    pub fn scan_result(&self) -> Option<&String>
+    {
+        self.scan_result.as_ref()
+    }
+
+    /// Quarantined versions are not listed in the feed and cannot be downloaded
+    pub fn is_quarantined(&self) -> bool
+    {
+        match self.scan_status.as_ref().map(|status| &**status) {
+            Some(SCAN_QUARANTINED) | Some(SCAN_INFECTED) => true,
+            _ => false,
+        }
+    }
+
+    /// Deprecation and advisories of this version as one line descriptions for clients
+    pub fn warnings<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<Vec<String>>
+    {
+        let mut warnings: Vec<String> = try!(Deprecation::get(connection, self)).iter().map(|deprecation| deprecation.describe()).collect();
+        warnings.extend(try!(Advisory::for_version(connection, self)).iter().map(|advisory| advisory.describe()));
+        Ok(warnings)
+    }
+
+    pub fn set_scan_result<C: Connection<Backend=Pg>>(&mut self, connection: &C, status: &str, result: Option<String>) -> BackendResult<()>
+    {
+        self.scan_status = Some(String::from(status));
+        self.scan_result = result;
+        err_discard!(update(packageversion::table.filter(
+                packageversion::id.eq(&self.id)
+                .and(packageversion::version.eq(&self.version))
+            )).set(&*self).execute(connection))
+    }
+
     pub fn download(&self, storage: &Storage) -> BackendResult<fs::File>
     {
         err!(storage.get(self))
     }
 }
+
+impl ToJson for PackageVersion
+{
+    fn to_json(&self) -> Json
+    {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("id"), self.id.to_json());
+        object.insert(String::from("version"), self.version.to_json());
+        object.insert(String::from("original_version"), self.original_version().to_json());
+        object.insert(String::from("title"), self.title.to_json());
+        object.insert(String::from("summary"), self.summary.to_json());
+        object.insert(String::from("description"), self.description.to_json());
+        object.insert(String::from("release_notes"), self.release_notes.to_json());
+        object.insert(String::from("icon_url"), self.icon_url.to_json());
+        object.insert(String::from("hash"), self.hash.to_json());
+        object.insert(String::from("hash_algorithm"), self.hash_algorithm.to_json());
+        object.insert(String::from("size"), self.size.to_json());
+        object.insert(String::from("signature_type"), self.signature_type.to_json());
+        object.insert(String::from("signer_fingerprint"), self.signer_fingerprint.to_json());
+        object.insert(String::from("scan_status"), self.scan_status.to_json());
+        Json::Object(object)
+    }
+}
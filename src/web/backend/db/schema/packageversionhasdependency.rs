@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-#[derive(Queryable, Debug)]
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
 #[insertable_into(packageversion_has_dependency)]
 #[changeset_for(packageversion_has_dependency)]
 struct PackageVersionHasDependency
@@ -0,0 +1,156 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+/// PDB of the symbol package (.snupkg) of a version, debuggers find it by file name and signature
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
+#[insertable_into(symbol_file)]
+pub struct SymbolFile
+{
+    id: String,
+    version: String,
+    path: String,
+    file_name: String,
+    signature: String,
+    size: i64,
+}
+
+impl SymbolFile
+{
+    /// Stores the symbol package of an uploaded version, symbols pushed before for that version get replaced.
+    /// Only the maintainer of the package may push symbols, every PDB has to belong to a binary of the package.
+    pub fn upload<R: Read + Seek, C: Connection<Backend=Pg>>(
+                        connection: &C,
+                        user: &User,
+                        storage: &Storage,
+                        file: R,
+                    ) -> BackendResult<(PackageVersion, Vec<Self>)>
+    {
+        let mut zip: ZipArchive<R> = try!(ZipArchive::new(file));
+        let (nuspec, problems) = validation::validate_symbol_archive(&mut zip);
+        let metadata = match (nuspec.as_ref().and_then(|nuspec| nuspec.find_child(|tag| tag.name == "metadata")), problems.is_empty()) {
+            (Some(metadata), true) => metadata.clone(),
+            _ => return Err(BackendError::ValidationFailed(problems)),
+        };
+        let text = |name: &str| metadata.find_child(|tag| tag.name == name)
+                                        .and_then(|tag| tag.text.clone())
+                                        .map(|text| String::from(text.trim()))
+                                        .unwrap_or(String::new());
+
+        let id = text("id");
+        let version = try!(NugetVersion::parse(&text("version")).map_err(|err| BackendError::InvalidXml(format!("{}", err).into())));
+        let pkgver = match PackageVersion::get(connection, &id, &version) {
+            Ok(pkgver) => pkgver,
+            //mostlikely the version was not found
+            Err(BackendError::DBError(_)) => return Err(BackendError::ValidationFailed(vec![
+                ValidationProblem::new("version", format!("{} {} does not exist, please push the package before its symbols", id, version))
+            ])),
+            Err(err) => return Err(err),
+        };
+        if &try!(try!(pkgver.package(connection)).maintainer(connection)) != user {
+            return Err(BackendError::PermissionDenied);
+        }
+
+        let (found, problems) = {
+            let mut package = try!(ZipArchive::new(try!(pkgver.download(storage))));
+            symbols::check(&mut zip, &mut package)
+        };
+        if !problems.is_empty() {
+            return Err(BackendError::ValidationFailed(problems));
+        }
+
+        let mut file = zip.into_inner();
+        match connection.transaction(|| {
+            try!(delete(symbol_file::table.filter(
+                symbol_file::id.eq(&pkgver.id)
+                .and(symbol_file::version.eq(&pkgver.version))
+            )).execute(connection));
+
+            let mut stored: Vec<SymbolFile> = Vec::new();
+            for symbol in found.iter() {
+                let this = SymbolFile {
+                    id: pkgver.id.clone(),
+                    version: pkgver.version.clone(),
+                    path: symbol.path.clone(),
+                    file_name: symbol.file_name.clone(),
+                    signature: symbol.signature.clone(),
+                    size: symbol.size as i64,
+                };
+                stored.push(try!(insert(&this).into(symbol_file::table).get_result(connection)));
+            }
+
+            try!(file.seek(SeekFrom::Start(0)));
+            try!(storage.store_symbols(&pkgver, &mut file));
+
+            Ok(stored)
+        }) {
+            Ok(stored) => Ok((pkgver, stored)),
+            Err(TransactionError::CouldntCreateTransaction(err)) => Err(BackendError::DBError(err)),
+            Err(TransactionError::UserReturnedError(err)) => Err(err),
+        }
+    }
+
+    pub fn for_version<C: Connection<Backend=Pg>>(connection: &C, version: &PackageVersion) -> BackendResult<Vec<Self>>
+    {
+        err!(symbol_file::table.filter(
+            symbol_file::id.eq(&version.id)
+            .and(symbol_file::version.eq(&version.version))
+        ).order(symbol_file::path.asc()).load(connection))
+    }
+
+    /// Symbol a debugger asks for, `signature` is the guid of its debug id
+    pub fn find<C: Connection<Backend=Pg>>(connection: &C, file_name: &str, signature: &str) -> BackendResult<Option<Self>>
+    {
+        let found: Vec<Self> = try!(symbol_file::table.filter(
+            symbol_file::file_name.eq(file_name.to_lowercase())
+            .and(symbol_file::signature.eq(signature))
+        ).load(connection));
+        Ok(found.into_iter().next())
+    }
+
+    pub fn package_version<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<PackageVersion>
+    {
+        err!(packageversion::table.filter(
+            packageversion::id.eq(&self.id)
+            .and(packageversion::version.eq(&self.version))
+        ).first(connection))
+    }
+
+    /// Reads the PDB from the stored symbol package of `version`
+    pub fn read(&self, storage: &Storage, version: &PackageVersion) -> BackendResult<Option<Vec<u8>>>
+    {
+        let file = try!(storage.get_symbols(version));
+        Ok(try!(contents::read(file, &self.path)).map(|(_, content)| content))
+    }
+
+    pub fn path(&self) -> &str
+    {
+        &self.path
+    }
+
+    pub fn file_name(&self) -> &str
+    {
+        &self.file_name
+    }
+
+    pub fn signature(&self) -> &str
+    {
+        &self.signature
+    }
+
+    pub fn size(&self) -> i64
+    {
+        self.size
+    }
+}
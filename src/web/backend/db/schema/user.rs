@@ -15,11 +15,12 @@
 
 pub enum Authentication {
     LDAP,
+    OIDC,
     //Plain(password: String)
     Plain(String)
 }
 
-#[derive(Queryable, Debug, RustcEncodable)]
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
 #[insertable_into(hazeluser)]
 #[changeset_for(hazeluser)]
 pub struct User
@@ -32,6 +33,10 @@ pub struct User
     provider: String,
     password: Option<String>,
     apikey: Option<String>,
+    admin: bool,
+    totp_secret: Option<String>,
+    totp_recovery_codes: Option<String>,
+    totp_last_step: Option<i64>,
 }
 
 impl PartialEq for User {
@@ -48,6 +53,7 @@ impl User
         let (provider, password) = match authentication
         {
             Authentication::LDAP => (String::from("LDAP"), None),
+            Authentication::OIDC => (String::from("OIDC"), None),
             Authentication::Plain(password) => (String::from("Plain"), Some(password)),
         };
 
@@ -59,7 +65,11 @@ impl User
             confirmed: if mail.is_some() { false } else { true },
             provider: provider,
             password: password,
-            apikey: apikey
+            apikey: apikey,
+            admin: false,
+            totp_secret: None,
+            totp_recovery_codes: None,
+            totp_last_step: None,
         };
         err!(insert(&this).into(hazeluser::table).get_result(connection))
     }
@@ -82,7 +92,7 @@ impl User
             "Plain" => {
                 match connection.transaction(|| {
                     self.mail = Some(mail);
-                    self.confirmed = CONFIG.auth.mail.is_none();
+                    self.confirmed = live().mail.is_none();
                     self.mail_key = Some(Uuid::new_v4().simple().to_string());
                     self.apikey = None;
                     let update = try!(self.update(&*connection));
@@ -121,7 +131,7 @@ impl User
 
     pub fn send_mail(&self) -> Result<(), MailError>
     {
-        match CONFIG.auth.mail.as_ref() {
+        match live().mail {
             Some(ref config) => {
                 let email = EmailBuilder::new()
                                     .to(&*match self.mail.as_ref() {
@@ -253,9 +263,20 @@ impl User
         }
     }
 
+    /// Total size of all versions of the packages maintained by this user in bytes
+    pub fn storage_usage<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<i64>
+    {
+        let packages: Vec<Package> = try!(package::table.filter(package::maintainer.eq(&self.id)).load(connection));
+        let mut usage = 0;
+        for package in packages {
+            usage += try!(package.storage_usage(connection));
+        }
+        Ok(usage)
+    }
+
     pub fn is_admin(&self) -> bool
     {
-        self.id == "admin"
+        self.id == "admin" || self.admin
     }
 
     pub fn register<C: Connection<Backend=Pg>>(connection: &C, username: String, fullname: String, mail: String, password: String) -> BackendResult<Self>
@@ -271,7 +292,7 @@ impl User
                 } else {
                     match connection.transaction(|| {
                         let mut user = try!(User::new(connection, username, fullname, Some(mail), Authentication::Plain(try!(bcrypt::hash(&*password, bcrypt::DEFAULT_COST))), None));
-                        if CONFIG.auth.mail.is_some() {
+                        if live().mail.is_some() {
                             user = try!(user.set_confirmed(connection, false));
                             user = try!(user.send_mail().map(|_| user));
                         } else {
@@ -305,6 +326,68 @@ impl User
         }
     }
 
+    pub fn totp_enabled(&self) -> bool
+    {
+        self.totp_secret.is_some()
+    }
+
+    /// Stores the secret, if the code proves the authenticator app was set up correctly.
+    /// Returns the recovery codes, which are only stored hashed.
+    pub fn enable_totp<C: Connection<Backend=Pg>>(&mut self, connection: &C, secret: String, code: &str) -> BackendResult<Vec<String>>
+    {
+        if !self.is_plainauth() {
+            return Err(BackendError::InvalidProviderForOP);
+        }
+        let step = try!(totp::verify(&secret, code, None).ok_or(LoginError::InvalidSecondFactor));
+
+        let recovery_codes = totp::generate_recovery_codes();
+        self.totp_secret = Some(secret);
+        self.totp_recovery_codes = Some(recovery_codes.iter().map(|code| totp::hash_recovery_code(code)).collect::<Vec<String>>().join(" "));
+        self.totp_last_step = Some(step);
+        try!(self.update(connection));
+        Ok(recovery_codes)
+    }
+
+    pub fn disable_totp<C: Connection<Backend=Pg>>(&mut self, connection: &C) -> BackendResult<Self>
+    {
+        self.totp_secret = None;
+        self.totp_recovery_codes = None;
+        self.totp_last_step = None;
+        //the changeset skips None values, so clear the columns explicitly
+        err!(update(hazeluser::table.filter(hazeluser::id.eq(&self.id))).set((
+            hazeluser::totp_secret.eq(None::<String>),
+            hazeluser::totp_recovery_codes.eq(None::<String>),
+            hazeluser::totp_last_step.eq(None::<i64>),
+        )).get_result(connection))
+    }
+
+    /// Accepts either a current code of the authenticator app or an unused recovery code
+    pub fn verify_second_factor<C: Connection<Backend=Pg>>(&mut self, connection: &C, code: &str) -> BackendResult<bool>
+    {
+        let secret = match self.totp_secret {
+            Some(ref secret) => secret.clone(),
+            None => return Ok(false),
+        };
+
+        if let Some(step) = totp::verify(&secret, code, self.totp_last_step) {
+            self.totp_last_step = Some(step);
+            try!(self.update(connection));
+            return Ok(true);
+        }
+
+        let hash = totp::hash_recovery_code(code);
+        let remaining: Vec<String> = self.totp_recovery_codes.as_ref().map(|codes| codes.split(' ').map(String::from).collect()).unwrap_or(Vec::new());
+        if remaining.contains(&hash) {
+            let remaining: Vec<String> = remaining.into_iter().filter(|x| *x != hash).collect();
+            warn!(target: "hazel::security", "Recovery code used for \"{}\", {} remaining", self.id, remaining.len());
+            self.totp_recovery_codes = Some(remaining.join(" "));
+            try!(self.update(connection));
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
     pub fn login<C: Connection<Backend=Pg>>(connection: &C, username: &String, password: &String) -> BackendResult<bool>
     {
         match try!(hazeluser::table.filter(
@@ -316,6 +399,10 @@ impl User
                     "LDAP" => {
                         Ok(User::ldap_login(username, password).is_ok())
                     },
+                    "OIDC" => {
+                        //only via the identity provider
+                        Ok(false)
+                    },
                     "Plain" => {
                         match user.password {
                             Some(stored_hash) => {
@@ -352,6 +439,38 @@ impl User
         }
     }
 
+    /// Logs in a user authenticated by the OpenID Connect provider,
+    /// provisioning the account on first login and syncing name, mail and admin role afterwards
+    pub fn oidc_login<C: Connection<Backend=Pg>>(connection: &C, identity: &Identity) -> BackendResult<Self>
+    {
+        if !identity.is_allowed() {
+            return Err(LoginError::OIDCError(OIDCError::GroupNotAllowed).into());
+        }
+
+        let mut user = match try!(hazeluser::table.filter(
+                hazeluser::id.eq(&identity.username)
+            ).first(connection).optional()) as Option<User>
+        {
+            Some(user) => {
+                if &*user.provider != "OIDC" {
+                    //do not allow taking over local or ldap accounts
+                    return Err(BackendError::InvalidProviderForOP);
+                }
+                user
+            },
+            None => try!(User::new(connection, identity.username.clone(), identity.fullname.clone(), None, Authentication::OIDC, None)),
+        };
+
+        user.name = identity.fullname.clone();
+        if identity.mail.is_some() {
+            //the identity provider is trusted to have verified the address
+            user.mail = identity.mail.clone();
+        }
+        user.confirmed = true;
+        user.admin = identity.is_admin();
+        user.update(connection)
+    }
+
     fn ldap_common_name(username: &String) -> Result<String, LDAPError>
     {
         match CONFIG.auth.ldap {
@@ -400,3 +519,21 @@ impl User
         }
     }
 }
+
+/// Account state for audit records, leaves out the password hash and api key
+impl ToJson for User
+{
+    fn to_json(&self) -> Json
+    {
+        let mut object = BTreeMap::new();
+        object.insert(String::from("id"), self.id.to_json());
+        object.insert(String::from("name"), self.name.to_json());
+        object.insert(String::from("mail"), self.mail.to_json());
+        object.insert(String::from("confirmed"), self.confirmed.to_json());
+        object.insert(String::from("provider"), self.provider.to_json());
+        object.insert(String::from("admin"), self.admin.to_json());
+        object.insert(String::from("apikey"), self.apikey.is_some().to_json());
+        object.insert(String::from("totp"), self.totp_secret.is_some().to_json());
+        Json::Object(object)
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookEvent
+{
+    Push,
+    Delete,
+    MetadataEdit,
+    Transfer,
+}
+
+impl WebhookEvent
+{
+    pub fn all() -> Vec<WebhookEvent>
+    {
+        vec![WebhookEvent::Push, WebhookEvent::Delete, WebhookEvent::MetadataEdit, WebhookEvent::Transfer]
+    }
+
+    pub fn as_str(&self) -> &'static str
+    {
+        match *self {
+            WebhookEvent::Push => "push",
+            WebhookEvent::Delete => "delete",
+            WebhookEvent::MetadataEdit => "metadata_edit",
+            WebhookEvent::Transfer => "transfer",
+        }
+    }
+}
+
+impl FromStr for WebhookEvent
+{
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<WebhookEvent, ()>
+    {
+        WebhookEvent::all().into_iter().find(|event| event.as_str() == s).ok_or(())
+    }
+}
+
+/// `events` is a comma separated list, empty subscribes to everything
+fn subscribes(events: &str, event: WebhookEvent) -> bool
+{
+    events.trim().is_empty() || events.split(',').any(|name| name.trim() == event.as_str())
+}
+
+#[insertable_into(webhook)]
+struct NewWebhook
+{
+    package_id: String,
+    url: String,
+    secret: Option<String>,
+    events: String,
+    created_by: String,
+}
+
+/// Webhook registered by the maintainer of a package, global ones are part of the config
+#[derive(Queryable, Debug, RustcEncodable, RustcDecodable)]
+pub struct Webhook
+{
+    id: i64,
+    package_id: String,
+    url: String,
+    secret: Option<String>,
+    events: String,
+    created_by: String,
+}
+
+impl Webhook
+{
+    pub fn new<C: Connection<Backend=Pg>>(connection: &C, package: &Package, user: &User, url: String, secret: Option<String>, events: &[WebhookEvent]) -> BackendResult<Self>
+    {
+        let webhook = NewWebhook {
+            package_id: package.id.clone(),
+            url: url,
+            secret: secret,
+            events: events.iter().map(|event| event.as_str()).collect::<Vec<_>>().join(","),
+            created_by: user.id.clone(),
+        };
+        err!(insert(&webhook).into(webhook::table).get_result(connection))
+    }
+
+    pub fn get<C: Connection<Backend=Pg>>(connection: &C, id: i64) -> BackendResult<Self>
+    {
+        err!(webhook::table.filter(webhook::id.eq(id)).first(connection))
+    }
+
+    pub fn for_package<C: Connection<Backend=Pg>>(connection: &C, package_id: &str) -> BackendResult<Vec<Self>>
+    {
+        err!(webhook::table.filter(webhook::package_id.eq(package_id)).order(webhook::id.asc()).load(connection))
+    }
+
+    pub fn delete<C: Connection<Backend=Pg>>(self, connection: &C) -> BackendResult<()>
+    {
+        err_discard!(delete(webhook::table.filter(webhook::id.eq(self.id))).execute(connection))
+    }
+
+    /// Stores a webhook from a backup, it gets a new id as its deliveries are not part of backups
+    fn restore<C: Connection<Backend=Pg>>(&self, connection: &C) -> BackendResult<()>
+    {
+        let webhook = NewWebhook {
+            package_id: self.package_id.clone(),
+            url: self.url.clone(),
+            secret: self.secret.clone(),
+            events: self.events.clone(),
+            created_by: self.created_by.clone(),
+        };
+        err_discard!(insert(&webhook).into(webhook::table).execute(connection))
+    }
+
+    pub fn id(&self) -> i64
+    {
+        self.id
+    }
+
+    pub fn package_id(&self) -> &str
+    {
+        &self.package_id
+    }
+
+    pub fn url(&self) -> &str
+    {
+        &self.url
+    }
+
+    pub fn events(&self) -> Vec<WebhookEvent>
+    {
+        WebhookEvent::all().into_iter().filter(|event| subscribes(&self.events, *event)).collect()
+    }
+
+    pub fn has_secret(&self) -> bool
+    {
+        self.secret.is_some()
+    }
+}
+
+#[insertable_into(webhook_delivery)]
+struct NewWebhookDelivery
+{
+    webhook_id: Option<i64>,
+    event: String,
+    package_id: String,
+    package_version: Option<String>,
+    url: String,
+    payload: String,
+    signature: Option<String>,
+    attempts: i32,
+    next_attempt: Option<NaiveDateTime>,
+    delivered: bool,
+    response_status: Option<i32>,
+    last_error: Option<String>,
+    created: NaiveDateTime,
+}
+
+/// A queued or finished webhook call. The payload is signed when queued, so the secret is not copied.
+#[derive(Queryable, Debug)]
+#[changeset_for(webhook_delivery, treat_none_as_null="true")]
+pub struct WebhookDelivery
+{
+    id: i64,
+    webhook_id: Option<i64>,
+    event: String,
+    package_id: String,
+    package_version: Option<String>,
+    url: String,
+    payload: String,
+    signature: Option<String>,
+    attempts: i32,
+    next_attempt: Option<NaiveDateTime>,
+    delivered: bool,
+    response_status: Option<i32>,
+    last_error: Option<String>,
+    created: NaiveDateTime,
+}
+
+impl WebhookDelivery
+{
+    /// Queues a delivery for every global and package webhook subscribed to `event`
+    pub fn enqueue<C: Connection<Backend=Pg>>(connection: &C,
+                   event: WebhookEvent,
+                   actor: &User,
+                   package_id: &str,
+                   package_version: Option<&str>,
+                   metadata: Json,
+                  ) -> BackendResult<()>
+    {
+        let now = UTC::now().naive_utc();
+        let payload = {
+            let mut object = BTreeMap::new();
+            object.insert(String::from("event"), event.as_str().to_json());
+            object.insert(String::from("package_id"), package_id.to_json());
+            object.insert(String::from("version"), package_version.map(String::from).to_json());
+            object.insert(String::from("actor"), actor.id.to_json());
+            object.insert(String::from("timestamp"), format!("{}Z", now.format("%Y-%m-%dT%H:%M:%S")).to_json());
+            object.insert(String::from("metadata"), metadata);
+            Json::Object(object).to_string()
+        };
+
+        let mut targets = Vec::new();
+        for config in CONFIG.backend.webhooks.iter() {
+            let package_matches = config.packages.is_empty() || config.packages.iter().any(|id| id.to_lowercase() == package_id.to_lowercase());
+            if package_matches && subscribes(&config.events.join(","), event) {
+                targets.push((None, config.url.clone(), config.secret.clone()));
+            }
+        }
+        for webhook in try!(Webhook::for_package(connection, package_id)) {
+            if subscribes(&webhook.events, event) {
+                targets.push((Some(webhook.id), webhook.url, webhook.secret));
+            }
+        }
+
+        for (webhook_id, url, secret) in targets {
+            let delivery = NewWebhookDelivery {
+                webhook_id: webhook_id,
+                event: String::from(event.as_str()),
+                package_id: String::from(package_id),
+                package_version: package_version.map(String::from),
+                url: url,
+                signature: secret.map(|secret| sign_webhook(&secret, &payload)),
+                payload: payload.clone(),
+                attempts: 0,
+                next_attempt: Some(now),
+                delivered: false,
+                response_status: None,
+                last_error: None,
+                created: now,
+            };
+            try!(insert(&delivery).into(webhook_delivery::table).execute(connection));
+        }
+        Ok(())
+    }
+
+    /// Deliveries whose next attempt is due, oldest first
+    pub fn due<C: Connection<Backend=Pg>>(connection: &C, limit: i64) -> BackendResult<Vec<Self>>
+    {
+        err!(webhook_delivery::table.filter(
+                webhook_delivery::next_attempt.le(UTC::now().naive_utc())
+            ).order(webhook_delivery::id.asc()).limit(limit).load(connection))
+    }
+
+    /// Newest deliveries of a package, `global` includes the ones of webhooks from the config
+    pub fn for_package<C: Connection<Backend=Pg>>(connection: &C, package_id: &str, global: bool, limit: i64) -> BackendResult<Vec<Self>>
+    {
+        let mut query = webhook_delivery::table.filter(webhook_delivery::package_id.eq(package_id)).into_boxed();
+        if !global {
+            query = query.filter(webhook_delivery::webhook_id.is_not_null());
+        }
+        err!(query.order(webhook_delivery::id.desc()).limit(limit).load(connection))
+    }
+
+    /// Records the outcome of an attempt, `retry_in` is None once no further attempts should be made
+    pub fn attempted<C: Connection<Backend=Pg>>(&mut self, connection: &C, response_status: Option<i32>, error: Option<String>, retry_in: Option<Duration>) -> BackendResult<()>
+    {
+        self.record_attempt(UTC::now().naive_utc(), response_status, error, retry_in);
+        err_discard!(update(webhook_delivery::table.filter(webhook_delivery::id.eq(self.id))).set(&*self).execute(connection))
+    }
+
+    fn record_attempt(&mut self, now: NaiveDateTime, response_status: Option<i32>, error: Option<String>, retry_in: Option<Duration>)
+    {
+        self.attempts += 1;
+        self.response_status = response_status;
+        self.delivered = error.is_none();
+        self.last_error = error;
+        self.next_attempt = if self.delivered { None } else { retry_in.map(|duration| now + duration) };
+    }
+
+    /// Delivery as `enqueue` creates it, without a database
+    #[cfg(test)]
+    pub fn queued(id: i64, event: WebhookEvent, url: &str, payload: &str, secret: Option<&str>) -> WebhookDelivery
+    {
+        let now = UTC::now().naive_utc();
+        WebhookDelivery {
+            id: id,
+            webhook_id: None,
+            event: String::from(event.as_str()),
+            package_id: String::from("test"),
+            package_version: Some(String::from("1.0.0")),
+            url: String::from(url),
+            payload: String::from(payload),
+            signature: secret.map(|secret| sign_webhook(secret, payload)),
+            attempts: 0,
+            next_attempt: Some(now),
+            delivered: false,
+            response_status: None,
+            last_error: None,
+            created: now,
+        }
+    }
+
+    pub fn id(&self) -> i64
+    {
+        self.id
+    }
+
+    /// Registration of a maintainer, None for webhooks of the config file
+    pub fn webhook_id(&self) -> Option<i64>
+    {
+        self.webhook_id
+    }
+
+    pub fn event(&self) -> &str
+    {
+        &self.event
+    }
+
+    pub fn package_version(&self) -> Option<&String>
+    {
+        self.package_version.as_ref()
+    }
+
+    pub fn url(&self) -> &str
+    {
+        &self.url
+    }
+
+    pub fn payload(&self) -> &str
+    {
+        &self.payload
+    }
+
+    pub fn signature(&self) -> Option<&String>
+    {
+        self.signature.as_ref()
+    }
+
+    pub fn attempts(&self) -> i32
+    {
+        self.attempts
+    }
+
+    pub fn next_attempt(&self) -> Option<&NaiveDateTime>
+    {
+        self.next_attempt.as_ref()
+    }
+
+    pub fn delivered(&self) -> bool
+    {
+        self.delivered
+    }
+
+    pub fn response_status(&self) -> Option<i32>
+    {
+        self.response_status
+    }
+
+    pub fn last_error(&self) -> Option<&String>
+    {
+        self.last_error.as_ref()
+    }
+
+    pub fn created(&self) -> &NaiveDateTime
+    {
+        &self.created
+    }
+}
+
+#[test]
+fn webhook_delivery_attempts() {
+    let now = UTC::now().naive_utc();
+    let mut delivery = WebhookDelivery::queued(1, WebhookEvent::Push, "http://localhost/", "{}", None);
+
+    delivery.record_attempt(now, Some(500), Some(String::from("Receiver answered with 500")), Some(Duration::seconds(30)));
+    assert_eq!(delivery.attempts(), 1);
+    assert!(!delivery.delivered);
+    assert_eq!(delivery.response_status, Some(500));
+    assert_eq!(delivery.next_attempt(), Some(&(now + Duration::seconds(30))));
+
+    delivery.record_attempt(now, None, Some(String::from("connection refused")), None);
+    assert_eq!(delivery.attempts(), 2);
+    assert_eq!(delivery.response_status, None);
+    assert_eq!(delivery.next_attempt(), None);
+
+    delivery.record_attempt(now, Some(204), None, Some(Duration::seconds(60)));
+    assert_eq!(delivery.attempts(), 3);
+    assert!(delivery.delivered);
+    assert_eq!(delivery.last_error, None);
+    assert_eq!(delivery.next_attempt(), None);
+}
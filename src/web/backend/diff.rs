@@ -0,0 +1,164 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use std::cmp;
+
+/// Unchanged lines shown around every change
+const CONTEXT: usize = 3;
+/// Bigger files are shown as completely replaced instead of computing their longest common subsequence
+const MAX_CELLS: usize = 4 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op
+{
+    Equal,
+    Delete,
+    Insert,
+}
+
+fn edit_script<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)>
+{
+    let prefix = old.iter().zip(new.iter()).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|&(a, b)| a == b).count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+    let (n, m) = (old_changed.len(), new_changed.len());
+
+    let mut ops: Vec<(Op, &'a str)> = old[..prefix].iter().map(|line| (Op::Equal, *line)).collect();
+
+    if n * m > MAX_CELLS {
+        ops.extend(old_changed.iter().map(|line| (Op::Delete, *line)));
+        ops.extend(new_changed.iter().map(|line| (Op::Insert, *line)));
+    } else {
+        //lcs[i * (m + 1) + j] is the length of the longest common subsequence of old_changed[i..] and new_changed[j..]
+        let mut lcs = vec![0usize; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if old_changed[i] == new_changed[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    cmp::max(lcs[(i + 1) * (m + 1) + j], lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if old_changed[i] == new_changed[j] {
+                ops.push((Op::Equal, old_changed[i]));
+                i += 1;
+                j += 1;
+            } else if lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1] {
+                ops.push((Op::Delete, old_changed[i]));
+                i += 1;
+            } else {
+                ops.push((Op::Insert, new_changed[j]));
+                j += 1;
+            }
+        }
+        ops.extend(old_changed[i..].iter().map(|line| (Op::Delete, *line)));
+        ops.extend(new_changed[j..].iter().map(|line| (Op::Insert, *line)));
+    }
+
+    ops.extend(old[old.len() - suffix..].iter().map(|line| (Op::Equal, *line)));
+    ops
+}
+
+fn range(start: usize, count: usize) -> String
+{
+    //empty ranges point at the line before them
+    if count == 0 {
+        format!("{},0", start)
+    } else {
+        format!("{},{}", start + 1, count)
+    }
+}
+
+/// Line based diff in unified format, `None` if both texts are equal
+pub fn unified(old_label: &str, new_label: &str, old: &str, new: &str) -> Option<String>
+{
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = edit_script(&old_lines, &new_lines);
+    if ops.iter().all(|&(op, _)| op == Op::Equal) {
+        return None;
+    }
+
+    //line numbers in the old and new text in front of every operation
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_position, mut new_position) = (0, 0);
+    for &(op, _) in ops.iter() {
+        positions.push((old_position, new_position));
+        match op {
+            Op::Equal => { old_position += 1; new_position += 1; },
+            Op::Delete => old_position += 1,
+            Op::Insert => new_position += 1,
+        }
+    }
+    positions.push((old_position, new_position));
+
+    let mut output = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let mut index = 0;
+    while let Some(first) = (index..ops.len()).find(|&k| ops[k].0 != Op::Equal) {
+        let start = cmp::max(index, first.saturating_sub(CONTEXT));
+
+        //hunks are merged, if their context would overlap
+        let mut end = first + 1;
+        for k in first + 1..ops.len() {
+            if ops[k].0 != Op::Equal {
+                end = k + 1;
+            } else if k - end >= 2 * CONTEXT {
+                break;
+            }
+        }
+        let stop = cmp::min(end + CONTEXT, ops.len());
+
+        let old_count = ops[start..stop].iter().filter(|&&(op, _)| op != Op::Insert).count();
+        let new_count = ops[start..stop].iter().filter(|&&(op, _)| op != Op::Delete).count();
+        output.push_str(&format!("@@ -{} +{} @@\n", range(positions[start].0, old_count), range(positions[start].1, new_count)));
+        for &(op, line) in ops[start..stop].iter() {
+            output.push(match op {
+                Op::Equal => ' ',
+                Op::Delete => '-',
+                Op::Insert => '+',
+            });
+            output.push_str(line);
+            output.push('\n');
+        }
+        index = stop;
+    }
+    Some(output)
+}
+
+#[test]
+fn unified_equal() {
+    assert_eq!(unified("a", "b", "one\ntwo\n", "one\ntwo"), None);
+}
+
+#[test]
+fn unified_hunks() {
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n";
+    let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n15\n16\n";
+    assert_eq!(unified("old", "new", old, new).unwrap(),
+               "--- old\n+++ new\n\
+                @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+                @@ -11,5 +11,5 @@\n 11\n 12\n 13\n-14\n 15\n+16\n");
+}
+
+#[test]
+fn unified_added() {
+    assert_eq!(unified("old", "new", "", "a\nb\n").unwrap(), "--- old\n+++ new\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+}
@@ -13,10 +13,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-mod version;
+pub mod version;
 #[allow(dead_code)]
 pub mod db;
 pub mod xml;
+pub mod oidc;
+pub mod retention;
+pub mod webhook;
+pub mod validation;
+pub mod scanner;
+pub mod scripts;
+pub mod contents;
+pub mod diff;
+pub mod assets;
+pub mod symbols;
 mod storage;
+mod signature;
 
 pub use self::storage::Storage;
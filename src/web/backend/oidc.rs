@@ -0,0 +1,564 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use hyper::Client;
+use hyper::header::{ContentType, Accept, qitem};
+use rustc_serialize::json::Json;
+use rustc_serialize::base64::{self, ToBase64, FromBase64, URL_SAFE};
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::{Mac, MacResult};
+use crypto::sha2::{Sha256, Sha384, Sha512};
+use rand::{self, Rng};
+use url::Url;
+use url::form_urlencoded;
+use chrono::*;
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+use super::signature::{self, WorkDir};
+use ::utils::CONFIG;
+use ::utils::config::OIDCConfig;
+use ::utils::error::{OIDCError, SignatureError};
+
+/// State of a login attempt between redirecting to the identity provider and its callback
+#[derive(Clone)]
+pub struct PendingLogin
+{
+    pub verifier: String,
+    pub nonce: String,
+    pub remember: bool,
+    pub expires: DateTime<UTC>,
+}
+
+/// User information extracted from a validated id token
+pub struct Identity
+{
+    pub username: String,
+    pub fullname: String,
+    pub mail: Option<String>,
+    pub groups: Vec<String>,
+}
+
+impl Identity
+{
+    pub fn is_admin(&self) -> bool
+    {
+        match CONFIG.auth.oidc.as_ref().and_then(|config| config.admin_groups.as_ref()) {
+            Some(groups) => self.groups.iter().any(|group| groups.contains(group)),
+            None => false,
+        }
+    }
+
+    pub fn is_allowed(&self) -> bool
+    {
+        match CONFIG.auth.oidc.as_ref().and_then(|config| config.allowed_groups.as_ref()) {
+            Some(groups) => self.groups.iter().any(|group| groups.contains(group)),
+            None => true,
+        }
+    }
+}
+
+struct ProviderMetadata
+{
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+fn config() -> Result<&'static OIDCConfig, OIDCError>
+{
+    CONFIG.auth.oidc.as_ref().ok_or(OIDCError::NotConfigured)
+}
+
+fn issuer(config: &OIDCConfig) -> &str
+{
+    config.issuer.trim_right_matches('/')
+}
+
+fn random_string(n: usize) -> String
+{
+    rand::thread_rng().gen_ascii_chars().take(n).collect()
+}
+
+fn fetch_json(response: &mut Read) -> Result<Json, OIDCError>
+{
+    let mut body = String::new();
+    try!(response.read_to_string(&mut body));
+    Json::from_str(&body).map_err(|err| OIDCError::InvalidResponse(format!("{}", err).into()))
+}
+
+fn discover(config: &OIDCConfig) -> Result<ProviderMetadata, OIDCError>
+{
+    let client = Client::new();
+    let mut response = try!(client.get(&*format!("{}/.well-known/openid-configuration", issuer(config)))
+                                  .header(Accept(vec![qitem("application/json".parse().unwrap())]))
+                                  .send());
+    let json = try!(fetch_json(&mut response));
+
+    Ok(ProviderMetadata {
+        authorization_endpoint: try!(json.find("authorization_endpoint").and_then(|x| x.as_string()).ok_or("authorization_endpoint missing")).to_string(),
+        token_endpoint: try!(json.find("token_endpoint").and_then(|x| x.as_string()).ok_or("token_endpoint missing")).to_string(),
+        jwks_uri: try!(json.find("jwks_uri").and_then(|x| x.as_string()).ok_or("jwks_uri missing")).to_string(),
+    })
+}
+
+/// Starts an authorization code flow with PKCE.
+/// Returns the url to redirect the user to, the state parameter and the information needed for the callback.
+pub fn authorization_request(remember: bool) -> Result<(String, String, PendingLogin), OIDCError>
+{
+    let config = try!(config());
+    let metadata = try!(discover(config));
+
+    let pending = PendingLogin {
+        verifier: random_string(64),
+        nonce: random_string(32),
+        remember: remember,
+        expires: UTC::now() + Duration::minutes(10),
+    };
+    let state = random_string(32);
+
+    let challenge = {
+        let mut hasher = Sha256::new();
+        hasher.input(pending.verifier.as_bytes());
+        let mut digest = [0u8; 32];
+        hasher.result(&mut digest);
+        digest.to_base64(URL_SAFE)
+    };
+
+    let mut url = try!(Url::parse(&metadata.authorization_endpoint).map_err(|_| OIDCError::InvalidResponse("authorization_endpoint is no valid url".into())));
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &config.redirect_url)
+        .append_pair("scope", config.scopes.as_ref().map(String::as_ref).unwrap_or("openid profile email"))
+        .append_pair("state", &state)
+        .append_pair("nonce", &pending.nonce)
+        .append_pair("code_challenge", &challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    Ok((url.into_string(), state, pending))
+}
+
+/// Exchanges the authorization code and validates the returned id token
+pub fn exchange(code: &str, pending: &PendingLogin) -> Result<Identity, OIDCError>
+{
+    exchange_code(try!(config()), code, pending)
+}
+
+fn exchange_code(config: &OIDCConfig, code: &str, pending: &PendingLogin) -> Result<Identity, OIDCError>
+{
+    let metadata = try!(discover(config));
+
+    let body = {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        serializer.append_pair("grant_type", "authorization_code")
+                  .append_pair("code", code)
+                  .append_pair("redirect_uri", &config.redirect_url)
+                  .append_pair("client_id", &config.client_id)
+                  .append_pair("code_verifier", &pending.verifier);
+        if let Some(ref secret) = config.client_secret {
+            serializer.append_pair("client_secret", secret);
+        }
+        serializer.finish()
+    };
+
+    let client = Client::new();
+    let mut response = try!(client.post(&*metadata.token_endpoint)
+                                  .header(ContentType("application/x-www-form-urlencoded".parse().unwrap()))
+                                  .header(Accept(vec![qitem("application/json".parse().unwrap())]))
+                                  .body(&*body)
+                                  .send());
+    let json = try!(fetch_json(&mut response));
+
+    if let Some(error) = json.find("error").and_then(|x| x.as_string()) {
+        return Err(OIDCError::InvalidResponse(format!("token endpoint returned \"{}\"", error).into()));
+    }
+    let id_token = try!(json.find("id_token").and_then(|x| x.as_string()).ok_or("id_token missing"));
+
+    try!(verify_signature(config, &metadata, id_token));
+    let claims = try!(decode_part(id_token, 1));
+    validate_claims(config, &claims, pending)
+}
+
+fn decode_part(id_token: &str, index: usize) -> Result<Json, OIDCError>
+{
+    let part = try!(id_token.split('.').nth(index).ok_or(OIDCError::InvalidToken("malformed jwt".into())));
+    let decoded = try!(part.from_base64().map_err(|_| OIDCError::InvalidToken("jwt part is no valid base64".into())));
+    let text = try!(String::from_utf8(decoded).map_err(|_| OIDCError::InvalidToken("jwt part is no valid utf8".into())));
+    Json::from_str(&text).map_err(|_| OIDCError::InvalidToken("jwt part is no valid json".into()))
+}
+
+fn hmac_matches<D: Digest>(digest: D, key: &[u8], input: &[u8], signature: &[u8]) -> bool
+{
+    let mut hmac = Hmac::new(digest, key);
+    hmac.input(input);
+    //MacResult compares in constant time
+    hmac.result() == MacResult::new(signature)
+}
+
+/// Checks the JWS signature of the id token.
+/// HS* tokens are signed with the client secret, RS* tokens with a key published at the provider's `jwks_uri`.
+fn verify_signature(config: &OIDCConfig, metadata: &ProviderMetadata, id_token: &str) -> Result<(), OIDCError>
+{
+    let parts: Vec<&str> = id_token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(OIDCError::InvalidToken("malformed jwt".into()));
+    }
+    let header = try!(decode_part(id_token, 0));
+    let input = format!("{}.{}", parts[0], parts[1]);
+    let signature = try!(parts[2].from_base64().map_err(|_| OIDCError::InvalidToken("signature is no valid base64".into())));
+
+    let alg = header.find("alg").and_then(|x| x.as_string()).unwrap_or("none");
+    let valid = match alg {
+        "HS256" | "HS384" | "HS512" => {
+            let secret = try!(config.client_secret.as_ref().ok_or(OIDCError::InvalidToken("token is signed with the client secret, but none is configured".into())));
+            match alg {
+                "HS256" => hmac_matches(Sha256::new(), secret.as_bytes(), input.as_bytes(), &signature),
+                "HS384" => hmac_matches(Sha384::new(), secret.as_bytes(), input.as_bytes(), &signature),
+                _ => hmac_matches(Sha512::new(), secret.as_bytes(), input.as_bytes(), &signature),
+            }
+        },
+        "RS256" | "RS384" | "RS512" => {
+            let keys = try!(fetch_keys(metadata));
+            let (n, e) = try!(select_key(&keys, header.find("kid").and_then(|x| x.as_string())));
+            try!(verify_rsa(&format!("-sha{}", &alg[2..]), &rsa_public_key_pem(&n, &e), input.as_bytes(), &signature))
+        },
+        _ => return Err(OIDCError::InvalidToken(format!("unsupported signature algorithm \"{}\"", alg).into())),
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(OIDCError::InvalidToken("signature does not match".into()))
+    }
+}
+
+fn fetch_keys(metadata: &ProviderMetadata) -> Result<Json, OIDCError>
+{
+    let client = Client::new();
+    let mut response = try!(client.get(&*metadata.jwks_uri)
+                                  .header(Accept(vec![qitem("application/json".parse().unwrap())]))
+                                  .send());
+    fetch_json(&mut response)
+}
+
+/// Modulus and exponent of the RSA signing key with the given id, or of the only one if the token names none
+fn select_key(keys: &Json, kid: Option<&str>) -> Result<(Vec<u8>, Vec<u8>), OIDCError>
+{
+    let candidates: Vec<&Json> = match keys.find("keys") {
+        Some(&Json::Array(ref keys)) => keys.iter().filter(|key| {
+            key.find("kty").and_then(|x| x.as_string()) == Some("RSA") &&
+            key.find("use").and_then(|x| x.as_string()).unwrap_or("sig") == "sig" &&
+            (kid.is_none() || key.find("kid").and_then(|x| x.as_string()) == kid)
+        }).collect(),
+        _ => return Err(OIDCError::InvalidResponse("jwks contains no keys".into())),
+    };
+    if candidates.len() != 1 {
+        return Err(OIDCError::InvalidToken("no unique signing key found for the token".into()));
+    }
+    let component = |name: &str| -> Result<Vec<u8>, OIDCError> {
+        candidates[0].find(name).and_then(|x| x.as_string()).and_then(|x| x.from_base64().ok())
+            .ok_or(OIDCError::InvalidResponse(format!("jwk has no valid \"{}\"", name).into()))
+    };
+    Ok((try!(component("n")), try!(component("e"))))
+}
+
+/// DER element with the given tag
+fn der(tag: u8, content: &[u8]) -> Vec<u8>
+{
+    let mut element = vec![tag];
+    if content.len() < 0x80 {
+        element.push(content.len() as u8);
+    } else {
+        let length: Vec<u8> = (0..4).rev().map(|i| (content.len() >> (i * 8)) as u8).skip_while(|x| *x == 0).collect();
+        element.push(0x80 | length.len() as u8);
+        element.extend(length);
+    }
+    element.extend(content);
+    element
+}
+
+fn der_integer(value: &[u8]) -> Vec<u8>
+{
+    let mut content: Vec<u8> = value.iter().cloned().skip_while(|x| *x == 0).collect();
+    if content.first().map(|x| x & 0x80 != 0).unwrap_or(true) {
+        content.insert(0, 0);
+    }
+    der(0x02, &content)
+}
+
+/// SubjectPublicKeyInfo of an RSA key in the PEM format openssl reads
+fn rsa_public_key_pem(n: &[u8], e: &[u8]) -> String
+{
+    //AlgorithmIdentifier rsaEncryption with NULL parameters
+    let algorithm = [0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01, 0x05, 0x00];
+    let mut rsa_key = der_integer(n);
+    rsa_key.extend(der_integer(e));
+    let mut bit_string = vec![0];
+    bit_string.extend(der(0x30, &rsa_key));
+    let mut info = algorithm.to_vec();
+    info.extend(der(0x03, &bit_string));
+
+    let encoded = der(0x30, &info).to_base64(base64::Config {
+        char_set: base64::CharacterSet::Standard,
+        newline: base64::Newline::LF,
+        pad: true,
+        line_length: Some(64),
+    });
+    format!("-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n", encoded)
+}
+
+fn verify_rsa(digest: &str, pem: &str, input: &[u8], signature: &[u8]) -> Result<bool, OIDCError>
+{
+    let dir = try!(WorkDir::create("oidc"));
+    let key_path = dir.0.join("key.pem");
+    let input_path = dir.0.join("input");
+    let signature_path = dir.0.join("signature");
+    try!(try!(File::create(&key_path)).write_all(pem.as_bytes()));
+    try!(try!(File::create(&input_path)).write_all(input));
+    try!(try!(File::create(&signature_path)).write_all(signature));
+
+    match signature::openssl(&["dgst", digest, "-verify", &key_path.to_string_lossy(),
+                               "-signature", &signature_path.to_string_lossy(), &input_path.to_string_lossy()]) {
+        Ok(_) => Ok(true),
+        Err(SignatureError::OpenSSLError(err)) => Err(err.into()),
+        //openssl fails with "Verification failure"
+        Err(_) => Ok(false),
+    }
+}
+
+fn validate_claims(config: &OIDCConfig, claims: &Json, pending: &PendingLogin) -> Result<Identity, OIDCError>
+{
+    match claims.find("iss").and_then(|x| x.as_string()) {
+        Some(iss) if iss.trim_right_matches('/') == issuer(config) => {},
+        _ => return Err(OIDCError::InvalidToken("issuer does not match".into())),
+    };
+    let audience_matches = match claims.find("aud") {
+        Some(&Json::String(ref aud)) => *aud == config.client_id,
+        Some(&Json::Array(ref auds)) => auds.iter().any(|aud| aud.as_string() == Some(&*config.client_id)),
+        _ => false,
+    };
+    if !audience_matches {
+        return Err(OIDCError::InvalidToken("audience does not match".into()));
+    }
+    match claims.find("exp").and_then(|x| x.as_i64()) {
+        Some(exp) if exp > UTC::now().timestamp() => {},
+        _ => return Err(OIDCError::InvalidToken("token expired".into())),
+    };
+    match claims.find("nonce").and_then(|x| x.as_string()) {
+        Some(nonce) if nonce == pending.nonce => {},
+        _ => return Err(OIDCError::InvalidToken("nonce does not match".into())),
+    };
+
+    let claim = |name: &Option<String>, default: &str| -> Option<String> {
+        claims.find(name.as_ref().map(String::as_ref).unwrap_or(default)).and_then(|x| x.as_string()).map(String::from)
+    };
+
+    let username = try!(claim(&config.username_claim, "preferred_username").ok_or(OIDCError::InvalidToken("username claim missing".into())));
+    let fullname = claim(&config.fullname_claim, "name").unwrap_or(username.clone());
+    let mail = claim(&config.mail_claim, "email");
+    let groups = match claims.find(config.groups_claim.as_ref().map(String::as_ref).unwrap_or("groups")) {
+        Some(&Json::Array(ref groups)) => groups.iter().filter_map(|x| x.as_string()).map(String::from).collect(),
+        Some(&Json::String(ref group)) => vec![group.clone()],
+        _ => Vec::new(),
+    };
+
+    Ok(Identity {
+        username: username,
+        fullname: fullname,
+        mail: mail,
+        groups: groups,
+    })
+}
+
+#[cfg(test)]
+fn test_config(issuer: &str) -> OIDCConfig
+{
+    OIDCConfig {
+        issuer: String::from(issuer),
+        client_id: String::from("hazel"),
+        client_secret: Some(String::from("client secret")),
+        redirect_url: String::from("https://hazel.example.org/oidc/callback"),
+        display_name: None,
+        scopes: None,
+        username_claim: None,
+        fullname_claim: None,
+        mail_claim: None,
+        groups_claim: None,
+        admin_groups: None,
+        allowed_groups: None,
+    }
+}
+
+#[cfg(test)]
+fn test_pending() -> PendingLogin
+{
+    PendingLogin {
+        verifier: String::from("verifier"),
+        nonce: String::from("nonce"),
+        remember: false,
+        expires: UTC::now() + Duration::minutes(10),
+    }
+}
+
+/// HS256 token signed with the secret of `test_config`
+#[cfg(test)]
+fn test_token(claims: &str) -> String
+{
+    let input = format!("{}.{}", r#"{"alg":"HS256","typ":"JWT"}"#.as_bytes().to_base64(URL_SAFE), claims.as_bytes().to_base64(URL_SAFE));
+    let mut hmac = Hmac::new(Sha256::new(), b"client secret");
+    hmac.input(input.as_bytes());
+    format!("{}.{}", input, hmac.result().code().to_base64(URL_SAFE))
+}
+
+#[cfg(test)]
+fn test_claims(issuer: &str) -> String
+{
+    format!(r#"{{"iss":"{}","aud":"hazel","exp":{},"nonce":"nonce","preferred_username":"jdoe","name":"Jane Doe","groups":["staff"]}}"#,
+            issuer, UTC::now().timestamp() + 300)
+}
+
+/// Answers the discovery and token requests like an identity provider would, returns the requests it received.
+/// The token response is created from the issuer url, which is only known once the provider listens.
+#[cfg(test)]
+fn fake_provider<F: Fn(&str) -> String + Send + 'static>(token_response: F) -> (String, ::std::thread::JoinHandle<Vec<String>>)
+{
+    use std::net::TcpListener;
+    use std::io::{BufRead, BufReader};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let issuer = format!("http://{}", listener.local_addr().unwrap());
+    let provider_issuer = issuer.clone();
+    let handle = ::std::thread::spawn(move || {
+        let issuer = provider_issuer;
+        let mut requests = Vec::new();
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.to_lowercase().starts_with("content-length:") {
+                    length = line[15..].trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                request.push_str(&line);
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8(body).unwrap());
+
+            let response = if request.starts_with("GET /.well-known/openid-configuration ") {
+                format!(r#"{{"issuer":"{0}","authorization_endpoint":"{0}/authorize","token_endpoint":"{0}/token","jwks_uri":"{0}/jwks"}}"#, issuer)
+            } else {
+                token_response(&issuer)
+            };
+            write!(reader.get_mut(), "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   response.len(), response).unwrap();
+            requests.push(request);
+        }
+        requests
+    });
+    (issuer, handle)
+}
+
+#[test]
+fn oidc_exchange() {
+    let (issuer, provider) = fake_provider(|issuer| format!(r#"{{"id_token":"{}","token_type":"Bearer"}}"#, test_token(&test_claims(issuer))));
+    let identity = exchange_code(&test_config(&issuer), "the code", &test_pending()).unwrap();
+    assert_eq!(identity.username, "jdoe");
+    assert_eq!(identity.fullname, "Jane Doe");
+    assert_eq!(identity.mail, None);
+    assert_eq!(identity.groups, vec![String::from("staff")]);
+
+    let requests = provider.join().unwrap();
+    assert!(requests[0].starts_with("GET /.well-known/openid-configuration "));
+    assert!(requests[1].starts_with("POST /token "));
+    assert!(requests[1].contains("grant_type=authorization_code&code=the+code&"));
+    assert!(requests[1].contains("&code_verifier=verifier"));
+}
+
+#[test]
+fn oidc_exchange_rejects_forged_token() {
+    let (issuer, provider) = fake_provider(|issuer| {
+        let token = test_token(&test_claims(issuer));
+        let forged = test_claims(issuer).replace("jdoe", "admin").as_bytes().to_base64(URL_SAFE);
+        let parts: Vec<&str> = token.split('.').collect();
+        format!(r#"{{"id_token":"{}.{}.{}"}}"#, parts[0], forged, parts[2])
+    });
+    assert!(exchange_code(&test_config(&issuer), "code", &test_pending()).is_err());
+    provider.join().unwrap();
+
+    let (issuer, provider) = fake_provider(|_| String::from(r#"{"error":"invalid_grant"}"#));
+    assert!(exchange_code(&test_config(&issuer), "code", &test_pending()).is_err());
+    provider.join().unwrap();
+}
+
+#[test]
+fn oidc_signature() {
+    let config = test_config("https://idp.example.org");
+    let metadata = ProviderMetadata {
+        authorization_endpoint: String::from("https://idp.example.org/authorize"),
+        token_endpoint: String::from("https://idp.example.org/token"),
+        jwks_uri: String::from("https://idp.example.org/jwks"),
+    };
+    let token = test_token(&test_claims(&config.issuer));
+    assert!(verify_signature(&config, &metadata, &token).is_ok());
+
+    let unsigned = format!("{}.{}.", r#"{"alg":"none"}"#.as_bytes().to_base64(URL_SAFE), test_claims(&config.issuer).as_bytes().to_base64(URL_SAFE));
+    assert!(verify_signature(&config, &metadata, &unsigned).is_err());
+
+    let mut other_secret = test_config(&config.issuer);
+    other_secret.client_secret = Some(String::from("another secret"));
+    assert!(verify_signature(&other_secret, &metadata, &token).is_err());
+    other_secret.client_secret = None;
+    assert!(verify_signature(&other_secret, &metadata, &token).is_err());
+}
+
+#[test]
+fn oidc_claims() {
+    let config = test_config("https://idp.example.org/");
+    let pending = test_pending();
+    let claims = |replace: &str, with: &str| Json::from_str(&test_claims("https://idp.example.org").replace(replace, with)).unwrap();
+
+    assert!(validate_claims(&config, &claims("jdoe", "jdoe"), &pending).is_ok());
+    assert!(validate_claims(&config, &claims(r#""aud":"hazel""#, r#""aud":["other","hazel"]"#), &pending).is_ok());
+    assert!(validate_claims(&config, &claims("idp.example.org", "evil.example.org"), &pending).is_err());
+    assert!(validate_claims(&config, &claims(r#""aud":"hazel""#, r#""aud":"other""#), &pending).is_err());
+    assert!(validate_claims(&config, &claims(r#""nonce":"nonce""#, r#""nonce":"replayed""#), &pending).is_err());
+    assert!(validate_claims(&config, &claims(r#""exp":"#, r#""exp":1,"old":"#), &pending).is_err());
+    assert!(validate_claims(&config, &claims("preferred_username", "nickname"), &pending).is_err());
+}
+
+#[test]
+fn jwk_to_pem() {
+    let n = "3zq2SaIpxEwQysiekzI2mweId-tGzVTclHkmYLjewtGzdaiIILYZX7l44sUxpEgklJyIhtLm0fb3ZwU6SDTlFw".from_base64().unwrap();
+    let e = "AQAB".from_base64().unwrap();
+    assert_eq!(rsa_public_key_pem(&n, &e), "-----BEGIN PUBLIC KEY-----\n\
+                                            MFwwDQYJKoZIhvcNAQEBBQADSwAwSAJBAN86tkmiKcRMEMrInpMyNpsHiHfrRs1U\n\
+                                            3JR5JmC43sLRs3WoiCC2GV+5eOLFMaRIJJSciIbS5tH292cFOkg05RcCAwEAAQ==\n\
+                                            -----END PUBLIC KEY-----\n");
+
+    let keys = Json::from_str(r#"{"keys":[{"kty":"EC","kid":"a"},{"kty":"RSA","kid":"b","use":"sig","n":"AQAB","e":"AQAB"},{"kty":"RSA","kid":"c","n":"AQAB","e":"AQAB"}]}"#).unwrap();
+    assert!(select_key(&keys, Some("b")).is_ok());
+    assert!(select_key(&keys, Some("a")).is_err());
+    assert!(select_key(&keys, None).is_err());
+}
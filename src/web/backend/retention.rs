@@ -0,0 +1,221 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::{UTC, Duration, NaiveDateTime};
+use diesel::Connection;
+use diesel::pg::{Pg, PgConnection};
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+use rustc_serialize::json::{Json, ToJson};
+
+use std::cmp;
+use std::net::{IpAddr, Ipv4Addr};
+use std::thread;
+use std::time;
+
+use super::db::{Package, PackageVersion, User, AuditEvent, AuditAction, WebhookDelivery, WebhookEvent};
+use super::Storage;
+use ::utils::CONFIG;
+use ::utils::config::RetentionConfig;
+#[cfg(test)]
+use ::utils::config::PackageRetentionConfig;
+use ::utils::error::BackendResult;
+
+pub struct RetentionCandidate
+{
+    pub version: PackageVersion,
+    pub reason: String,
+}
+
+/// (keep_latest, prerelease_max_age_days) for a package, rules for the package replace the global ones
+fn rules(config: &RetentionConfig, id: &str) -> (Option<u32>, Option<u64>)
+{
+    match config.packages.iter().find(|rule| rule.id.to_lowercase() == id.to_lowercase()) {
+        Some(rule) => (rule.keep_latest, rule.prerelease_max_age_days),
+        None => (config.keep_latest, config.prerelease_max_age_days),
+    }
+}
+
+/// Versions of the package `id` the rules select, newest first. The latest stable version is never selected.
+fn select(config: &RetentionConfig, now: NaiveDateTime, id: &str, mut versions: Vec<PackageVersion>) -> Vec<RetentionCandidate>
+{
+    let (keep_latest, max_age) = rules(config, id);
+    versions.sort_by(|a, b| b.version().cmp(&a.version()));
+    let latest_stable = versions.iter().position(|version| !version.version().is_prerelease());
+
+    let mut result = Vec::new();
+    for (index, version) in versions.into_iter().enumerate() {
+        if Some(index) == latest_stable {
+            continue;
+        }
+        let reason = match (keep_latest, max_age) {
+            (Some(keep), _) if index >= keep as usize => format!("not among the latest {} versions", keep),
+            (_, Some(days)) if version.version().is_prerelease() && now - *version.creation_date() > Duration::days(days as i64) => {
+                format!("prerelease older than {} days", days)
+            },
+            _ => continue,
+        };
+        result.push(RetentionCandidate {
+            version: version,
+            reason: reason,
+        });
+    }
+    result
+}
+
+/// Versions the rules would delete. The latest stable version of a package and versions
+/// other packages strictly depend on are never included.
+pub fn candidates<C: Connection<Backend=Pg>>(connection: &C, config: &RetentionConfig) -> BackendResult<Vec<RetentionCandidate>>
+{
+    let now = UTC::now().naive_utc();
+    let mut result = Vec::new();
+
+    for package in try!(Package::all(connection)) {
+        for candidate in select(config, now, package.id(), try!(package.versions(connection))) {
+            if !try!(candidate.version.blocking_dependencies(connection)).is_empty() {
+                debug!(target: "hazel::retention", "Keeping {} {}, other packages depend on it", candidate.version.id(), candidate.version.version());
+                continue;
+            }
+            result.push(candidate);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Deletes the candidates, or only logs them with `dry_run`. Returns the number of affected versions.
+pub fn apply<C: Connection<Backend=Pg>>(connection: &C, storage: &Storage, config: &RetentionConfig, dry_run: bool) -> BackendResult<usize>
+{
+    let candidates = try!(candidates(connection, config));
+    if candidates.is_empty() {
+        return Ok(0);
+    }
+    //deletions are recorded in the name of the superuser
+    let actor = try!(User::get(connection, &String::from("admin")));
+
+    let mut affected = 0;
+    for candidate in candidates.iter() {
+        let version = &candidate.version;
+        if dry_run {
+            info!(target: "hazel::retention", "Would delete {} {} ({})", version.id(), version.version(), candidate.reason);
+            affected += 1;
+            continue;
+        }
+
+        let mut metadata = version.to_json();
+        match version.delete(connection, storage) {
+            Ok(()) => {
+                info!(target: "hazel::retention", "Deleted {} {} ({})", version.id(), version.version(), candidate.reason);
+                affected += 1;
+                if let Json::Object(ref mut object) = metadata {
+                    object.insert(String::from("retention_reason"), candidate.reason.to_json());
+                }
+                if let Err(err) = WebhookDelivery::enqueue(connection, WebhookEvent::Delete, &actor, version.id(), Some(&*format!("{}", version.version())), metadata.clone()) {
+                    error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                }
+                AuditEvent::record(connection, &actor, AuditAction::Retention, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                                   Some(version.id()), Some(&*format!("{}", version.version())), Some(metadata), None);
+            },
+            Err(err) => warn!(target: "hazel::retention", "Unable to delete {} {}: {}", version.id(), version.version(), err),
+        }
+    }
+
+    Ok(affected)
+}
+
+/// Evaluates the configured rules every `interval_mins` on a background thread
+pub fn schedule(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage)
+{
+    let config = match CONFIG.backend.retention {
+        Some(ref config) => config,
+        None => return,
+    };
+
+    thread::spawn(move || {
+        loop {
+            match pool.get() {
+                Ok(connection) => match apply(&*connection, &storage, config, config.dry_run) {
+                    Ok(0) => {},
+                    Ok(count) if config.dry_run => info!(target: "hazel::retention", "{} versions would be deleted", count),
+                    Ok(count) => info!(target: "hazel::retention", "Deleted {} versions", count),
+                    Err(err) => error!(target: "hazel::retention", "{}", err),
+                },
+                Err(err) => error!("{:?}", err),
+            }
+            thread::sleep(time::Duration::from_secs(cmp::max(config.interval_mins, 1) * 60));
+        }
+    });
+}
+
+#[cfg(test)]
+fn retention_config(keep_latest: Option<u32>, prerelease_max_age_days: Option<u64>) -> RetentionConfig
+{
+    RetentionConfig {
+        interval_mins: 60,
+        dry_run: false,
+        keep_latest: keep_latest,
+        prerelease_max_age_days: prerelease_max_age_days,
+        packages: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+fn selected(config: &RetentionConfig, now: NaiveDateTime, id: &str, versions: &[(&str, i64)]) -> Vec<String>
+{
+    let versions = versions.iter().map(|&(version, age_days)| PackageVersion::uploaded(id, version, now - Duration::days(age_days))).collect();
+    select(config, now, id, versions).iter().map(|candidate| format!("{}", candidate.version.version())).collect()
+}
+
+#[test]
+fn retention_keep_latest() {
+    let now = UTC::now().naive_utc();
+    let config = retention_config(Some(2), None);
+    assert_eq!(selected(&config, now, "test", &[("1.0.0", 3), ("1.2.0", 1), ("1.1.0", 2), ("0.9.0", 4)]), vec!["1.0.0", "0.9.0"]);
+    assert!(selected(&config, now, "test", &[("1.0.0", 3), ("1.1.0", 2)]).is_empty());
+    assert!(selected(&retention_config(None, None), now, "test", &[("1.0.0", 3), ("1.1.0", 2), ("1.2.0", 1)]).is_empty());
+}
+
+#[test]
+fn retention_prerelease_age() {
+    let now = UTC::now().naive_utc();
+    let config = retention_config(None, Some(30));
+    assert_eq!(selected(&config, now, "test", &[("1.0.0", 60), ("1.1.0-beta", 45), ("1.1.0-rc", 10), ("0.9.0", 90)]), vec!["1.1.0-beta"]);
+    assert_eq!(selected(&retention_config(Some(5), Some(30)), now, "test", &[("1.0.0", 60), ("2.0.0-alpha", 31)]), vec!["2.0.0-alpha"]);
+}
+
+#[test]
+fn retention_latest_stable() {
+    let now = UTC::now().naive_utc();
+    let config = retention_config(Some(1), Some(30));
+    //prereleases newer than the latest stable version do not push it out
+    assert_eq!(selected(&config, now, "test", &[("1.0.0", 100), ("0.9.0", 120), ("2.0.0-beta.2", 5), ("2.0.0-beta.1", 40)]), vec!["2.0.0-beta.1", "0.9.0"]);
+    assert!(selected(&retention_config(Some(0), None), now, "test", &[("1.0.0", 100)]).is_empty());
+    //without a stable version only the rules apply
+    assert_eq!(selected(&config, now, "test", &[("1.0.0-beta", 10), ("1.0.0-alpha", 50)]), vec!["1.0.0-alpha"]);
+}
+
+#[test]
+fn retention_package_rules() {
+    let now = UTC::now().naive_utc();
+    let mut config = retention_config(Some(1), Some(30));
+    config.packages.push(PackageRetentionConfig {
+        id: String::from("My-CI-Package"),
+        keep_latest: Some(3),
+        prerelease_max_age_days: None,
+    });
+    let versions = [("1.0.0", 100), ("1.1.0", 90), ("1.2.0", 80), ("1.3.0", 70), ("1.4.0-beta", 60)];
+    assert_eq!(selected(&config, now, "my-ci-package", &versions), vec!["1.1.0", "1.0.0"]);
+    assert_eq!(selected(&config, now, "other", &versions), vec!["1.4.0-beta", "1.2.0", "1.1.0", "1.0.0"]);
+}
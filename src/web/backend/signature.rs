@@ -0,0 +1,398 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crypto::digest::Digest;
+use crypto::sha2::{Sha256, Sha384, Sha512};
+use rustc_serialize::base64::FromBase64;
+use uuid::Uuid;
+
+use std::ascii::AsciiExt;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use ::utils::CONFIG;
+use ::utils::error::SignatureError;
+
+const SIGNATURE_FILE: &'static str = ".signature.p7s";
+
+//DER encoded commitment type oids (id-cti-ets-proofOfOrigin / proofOfReceipt)
+const PROOF_OF_ORIGIN: &'static [u8] = &[0x06, 0x0b, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x06, 0x01];
+const PROOF_OF_RECEIPT: &'static [u8] = &[0x06, 0x0b, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x06, 0x02];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureType
+{
+    Author,
+    Repository,
+}
+
+impl SignatureType
+{
+    pub fn as_str(&self) -> &'static str
+    {
+        match *self {
+            SignatureType::Author => "Author",
+            SignatureType::Repository => "Repository",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PackageSignature
+{
+    pub signature_type: SignatureType,
+    pub signer: String,
+    pub fingerprint: String,
+}
+
+/// Upper case hex without separators, the form fingerprints are stored and compared in
+fn normalize_fingerprint(fingerprint: &str) -> String
+{
+    fingerprint.chars().filter(|c| c.is_digit(16)).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+fn le16(data: &[u8], offset: usize) -> usize
+{
+    data[offset] as usize | (data[offset + 1] as usize) << 8
+}
+
+fn le32(data: &[u8], offset: usize) -> usize
+{
+    le16(data, offset) | le16(data, offset + 2) << 16
+}
+
+fn put_le16(data: &mut [u8], offset: usize, value: usize)
+{
+    data[offset] = value as u8;
+    data[offset + 1] = (value >> 8) as u8;
+}
+
+fn put_le32(data: &mut [u8], offset: usize, value: usize)
+{
+    put_le16(data, offset, value & 0xffff);
+    put_le16(data, offset + 2, value >> 16);
+}
+
+struct CentralEntry
+{
+    name: String,
+    start: usize,
+    end: usize,
+    local_offset: usize,
+}
+
+/// Splits the archive into the signature file contents and the archive bytes as if
+/// the signature entry was never added, which is what the signature commits to.
+/// Returns None for unsigned packages.
+fn split_signature(package: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>, SignatureError>
+{
+    if package.len() < 22 {
+        return Err("Package is no zip archive".into());
+    }
+    let eocd = match (0..package.len() - 21).rev().find(|&i| le32(package, i) == 0x06054b50) {
+        Some(eocd) => eocd,
+        None => return Err("Package is no zip archive".into()),
+    };
+    let entry_count = le16(package, eocd + 10);
+    let cd_size = le32(package, eocd + 12);
+    let cd_offset = le32(package, eocd + 16);
+    if cd_offset + cd_size > eocd {
+        return Err("Broken zip central directory".into());
+    }
+
+    let mut entries = Vec::new();
+    let mut position = cd_offset;
+    for _ in 0..entry_count {
+        if position + 46 > eocd || le32(package, position) != 0x02014b50 {
+            return Err("Broken zip central directory".into());
+        }
+        let name_len = le16(package, position + 28);
+        let end = position + 46 + name_len + le16(package, position + 30) + le16(package, position + 32);
+        if end > eocd {
+            return Err("Broken zip central directory".into());
+        }
+        entries.push(CentralEntry {
+            name: String::from_utf8_lossy(&package[position + 46..position + 46 + name_len]).into_owned(),
+            start: position,
+            end: end,
+            local_offset: le32(package, position + 42),
+        });
+        position = end;
+    }
+
+    let signature = match entries.iter().position(|entry| entry.name == SIGNATURE_FILE) {
+        Some(index) => entries.remove(index),
+        None => return Ok(None),
+    };
+
+    //the local entry ends where the next one (or the central directory) begins, this covers data descriptors.
+    //offsets come from the central directory, anything outside of the entry data is rejected before it is read
+    let local_start = signature.local_offset;
+    let local_end = entries.iter().map(|entry| entry.local_offset).filter(|&offset| offset > local_start).min().unwrap_or(cd_offset);
+    if local_end > cd_offset || local_start + 30 > local_end || le32(package, local_start) != 0x04034b50 {
+        return Err("Broken zip entry for the signature file".into());
+    }
+    if le16(package, local_start + 8) != 0 {
+        return Err("Signature file has to be stored uncompressed".into());
+    }
+    let data_start = local_start + 30 + le16(package, local_start + 26) + le16(package, local_start + 28);
+    let data_end = data_start + le32(package, signature.start + 20);
+    if data_end > local_end {
+        return Err("Broken zip entry for the signature file".into());
+    }
+    let disk_entries = le16(package, eocd + 8);
+    if disk_entries == 0 {
+        return Err("Broken zip central directory".into());
+    }
+    let content = package[data_start..data_end].to_vec();
+    let removed = local_end - local_start;
+
+    let mut unsigned = Vec::with_capacity(package.len());
+    unsigned.extend_from_slice(&package[..local_start]);
+    unsigned.extend_from_slice(&package[local_end..cd_offset]);
+    for entry in entries.iter() {
+        let mut header = package[entry.start..entry.end].to_vec();
+        if entry.local_offset > local_start {
+            put_le32(&mut header, 42, entry.local_offset - removed);
+        }
+        unsigned.extend_from_slice(&header);
+    }
+    let mut end_record = package[eocd..].to_vec();
+    put_le16(&mut end_record, 8, disk_entries - 1);
+    put_le16(&mut end_record, 10, entry_count - 1);
+    put_le32(&mut end_record, 12, cd_size - (signature.end - signature.start));
+    put_le32(&mut end_record, 16, cd_offset - removed);
+    unsigned.extend_from_slice(&end_record);
+
+    Ok(Some((content, unsigned)))
+}
+
+/// Checks the signed content ("Version:1" followed by "<oid>-Hash:<base64>") against the archive
+fn verify_content(content: &str, unsigned: &[u8]) -> Result<(), SignatureError>
+{
+    for line in content.lines() {
+        let (oid, value) = match line.find("-Hash:") {
+            Some(index) => (&line[..index], line[index + 6..].trim()),
+            None => continue,
+        };
+        let expected = match value.from_base64() {
+            Ok(expected) => expected,
+            Err(_) => return Err("Package hash is not base64 encoded".into()),
+        };
+        let mut digest: Box<Digest> = match oid.trim() {
+            "2.16.840.1.101.3.4.2.1" => Box::new(Sha256::new()),
+            "2.16.840.1.101.3.4.2.2" => Box::new(Sha384::new()),
+            "2.16.840.1.101.3.4.2.3" => Box::new(Sha512::new()),
+            oid => return Err(format!("Unsupported hash algorithm {}", oid).into()),
+        };
+        digest.input(unsigned);
+        let mut actual = vec![0u8; digest.output_bytes()];
+        digest.result(&mut actual);
+        return if actual == expected {
+            Ok(())
+        } else {
+            Err("Package contents do not match the signature".into())
+        }
+    }
+    Err("Signature does not contain a package hash".into())
+}
+
+/// Scratch directory for the openssl invocations, removed on drop
+pub struct WorkDir(pub PathBuf);
+
+impl WorkDir
+{
+    pub fn create(purpose: &str) -> io::Result<WorkDir>
+    {
+        let dir = WorkDir(env::temp_dir().join(format!("hazel-{}-{}", purpose, Uuid::new_v4())));
+        try!(fs::create_dir(&dir.0));
+        Ok(dir)
+    }
+}
+
+impl Drop for WorkDir
+{
+    fn drop(&mut self)
+    {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs the configured openssl binary and returns its output
+pub fn openssl(args: &[&str]) -> Result<String, SignatureError>
+{
+    let binary = CONFIG.backend.signatures.as_ref().and_then(|config| config.openssl.clone()).unwrap_or(String::from("openssl"));
+    let output = try!(Command::new(binary).args(args).output());
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!("openssl {}: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()).into())
+    }
+}
+
+/// Verifies the signature of a nupkg, if there is one.
+/// The certificate chain is not validated, instead the signing certificate has to be in the configured set of trusted fingerprints.
+/// Without `[backend.signatures]` nothing is verified and every package is recorded as unsigned.
+pub fn verify(package: &[u8]) -> Result<Option<PackageSignature>, SignatureError>
+{
+    if CONFIG.backend.signatures.is_none() {
+        return Ok(None);
+    }
+
+    let (p7s, unsigned) = match try!(split_signature(package)) {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+
+    let signature_type = if p7s.windows(PROOF_OF_ORIGIN.len()).any(|window| window == PROOF_OF_ORIGIN) {
+        SignatureType::Author
+    } else if p7s.windows(PROOF_OF_RECEIPT.len()).any(|window| window == PROOF_OF_RECEIPT) {
+        SignatureType::Repository
+    } else {
+        return Err("Signature has no known commitment type".into());
+    };
+
+    let dir = try!(WorkDir::create("signature"));
+    let p7s_path = dir.0.join("signature.p7s");
+    let signer_path = dir.0.join("signer.pem");
+    let content_path = dir.0.join("content");
+    try!(try!(File::create(&p7s_path)).write_all(&p7s));
+
+    try!(openssl(&["cms", "-verify", "-binary", "-noverify", "-inform", "DER",
+                   "-in", &p7s_path.to_string_lossy(),
+                   "-signer", &signer_path.to_string_lossy(),
+                   "-out", &content_path.to_string_lossy()]));
+
+    let mut content = String::new();
+    try!(try!(File::open(&content_path)).read_to_string(&mut content));
+    try!(verify_content(&content, &unsigned));
+
+    let certificate = try!(openssl(&["x509", "-noout", "-subject", "-fingerprint", "-sha256", "-nameopt", "RFC2253",
+                                     "-in", &signer_path.to_string_lossy()]));
+    let mut signer = None;
+    let mut fingerprint = None;
+    for line in certificate.lines() {
+        if let Some(index) = line.find('=') {
+            if line.to_lowercase().starts_with("subject") {
+                signer = Some(String::from(line[index + 1..].trim()));
+            } else if line.to_lowercase().contains("fingerprint") {
+                fingerprint = Some(normalize_fingerprint(&line[index + 1..]));
+            }
+        }
+    }
+
+    let signature = match (signer, fingerprint) {
+        (Some(signer), Some(fingerprint)) => PackageSignature {
+            signature_type: signature_type,
+            signer: signer,
+            fingerprint: fingerprint,
+        },
+        _ => return Err("Unable to read the signing certificate".into()),
+    };
+
+    if let Some(ref config) = CONFIG.backend.signatures {
+        if !config.trusted_fingerprints.iter().any(|trusted| normalize_fingerprint(trusted) == signature.fingerprint) {
+            return Err(SignatureError::Untrusted(signature.fingerprint));
+        }
+    }
+
+    Ok(Some(signature))
+}
+
+/// Whether packages with the given id may only be uploaded signed
+pub fn required(id: &str) -> bool
+{
+    match CONFIG.backend.signatures {
+        Some(ref config) => config.required_prefixes.iter().any(|prefix| id.to_lowercase().starts_with(&prefix.to_lowercase())),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+fn stored_zip(entries: &[(&str, &[u8])]) -> Vec<u8>
+{
+    let mut zip = Vec::new();
+    let mut central = Vec::new();
+    for &(name, data) in entries {
+        let mut local = vec![0u8; 30];
+        put_le32(&mut local, 0, 0x04034b50);
+        put_le32(&mut local, 18, data.len());
+        put_le32(&mut local, 22, data.len());
+        put_le16(&mut local, 26, name.len());
+        let mut header = vec![0u8; 46];
+        put_le32(&mut header, 0, 0x02014b50);
+        put_le32(&mut header, 20, data.len());
+        put_le32(&mut header, 24, data.len());
+        put_le16(&mut header, 28, name.len());
+        put_le32(&mut header, 42, zip.len());
+        header.extend_from_slice(name.as_bytes());
+        central.extend_from_slice(&header);
+        zip.extend_from_slice(&local);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(data);
+    }
+    let mut end_record = vec![0u8; 22];
+    put_le32(&mut end_record, 0, 0x06054b50);
+    put_le16(&mut end_record, 8, entries.len());
+    put_le16(&mut end_record, 10, entries.len());
+    put_le32(&mut end_record, 12, central.len());
+    put_le32(&mut end_record, 16, zip.len());
+    zip.extend_from_slice(&central);
+    zip.extend_from_slice(&end_record);
+    zip
+}
+
+#[test]
+fn split_removes_signature_entry()
+{
+    let signed = stored_zip(&[("a.txt", b"first"), (SIGNATURE_FILE, b"p7s"), ("b.txt", b"second")]);
+    let (content, unsigned) = split_signature(&signed).unwrap().unwrap();
+    assert_eq!(content, b"p7s".to_vec());
+    assert_eq!(unsigned, stored_zip(&[("a.txt", b"first"), ("b.txt", b"second")]));
+    assert!(split_signature(&unsigned).unwrap().is_none());
+}
+
+#[test]
+fn split_rejects_crafted_offsets()
+{
+    let signed = stored_zip(&[(SIGNATURE_FILE, b"p7s"), ("a.txt", b"first")]);
+    let cd_offset = le32(&signed, signed.len() - 6);
+    let signature_header = cd_offset;
+    let next_header = cd_offset + 46 + SIGNATURE_FILE.len();
+
+    //signature entry pointing past the archive
+    let mut crafted = signed.clone();
+    put_le32(&mut crafted, signature_header + 42, 0xfffffff0);
+    assert!(split_signature(&crafted).is_err());
+
+    //next entry pointing behind the central directory
+    let mut crafted = signed.clone();
+    put_le32(&mut crafted, next_header + 42, cd_offset + 100);
+    assert!(split_signature(&crafted).is_err());
+
+    //signature size reaching into the next entry
+    let mut crafted = signed.clone();
+    put_le32(&mut crafted, signature_header + 20, 1000);
+    assert!(split_signature(&crafted).is_err());
+
+    //no entries on this disk
+    let mut crafted = signed.clone();
+    let eocd = crafted.len() - 22;
+    put_le16(&mut crafted, eocd + 8, 0);
+    assert!(split_signature(&crafted).is_err());
+}
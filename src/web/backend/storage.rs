@@ -14,15 +14,19 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::db::PackageVersion;
-use semver::Version;
 
 use fs2::FileExt;
 
 use std::fs::{self, File};
 use std::path::PathBuf;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::sync::Mutex;
 
+use uuid::Uuid;
+
+const PACKAGE_EXTENSION: &'static str = ".nuget";
+const SYMBOLS_EXTENSION: &'static str = ".snupkg";
+
 pub struct Storage {
     path: Mutex<PathBuf>,
     open_lock: Mutex<()>,
@@ -37,18 +41,25 @@ impl Storage {
         }
     }
 
-    fn get_file_path(&self, package: &PackageVersion) -> PathBuf {
+    fn get_file_path(&self, package: &PackageVersion, extension: &str) -> PathBuf {
         let mut path = {
             self.path.lock().unwrap().clone()
         };
         path.push(package.id());
-        path.set_file_name(package.id().to_string() + "_" + &Version::from(package.version()).to_string() + ".nuget");
+        path.set_file_name(package.id().to_string() + "_" + &package.version().to_string() + extension);
         path
     }
 
-    pub fn store<R: Read>(&self, package: &PackageVersion, mut data: R) -> io::Result<()> {
-        let path = self.get_file_path(package);
+    pub fn store<R: Read>(&self, package: &PackageVersion, data: R) -> io::Result<()> {
+        self.store_at(self.get_file_path(package, PACKAGE_EXTENSION), data)
+    }
+
+    /// Symbol packages (.snupkg) are stored next to their package and deleted with it
+    pub fn store_symbols<R: Read>(&self, package: &PackageVersion, data: R) -> io::Result<()> {
+        self.store_at(self.get_file_path(package, SYMBOLS_EXTENSION), data)
+    }
 
+    fn store_at<R: Read>(&self, path: PathBuf, mut data: R) -> io::Result<()> {
         let mut file = {
             let _ = self.open_lock.lock().unwrap();
             match File::open(path.clone()) {
@@ -69,9 +80,29 @@ impl Storage {
         Ok(())
     }
 
+    /// Moves the package and symbol package of a version stored under a different version string
+    pub fn rename_version(&self, package: &PackageVersion, old_version: &str) -> io::Result<()> {
+        for extension in [PACKAGE_EXTENSION, SYMBOLS_EXTENSION].iter() {
+            let target = self.get_file_path(package, extension);
+            let mut source = target.clone();
+            source.set_file_name(package.id().to_string() + "_" + old_version + extension);
+            if source.exists() {
+                let _lock = self.open_lock.lock().unwrap();
+                try!(fs::rename(source, target));
+            }
+        }
+        Ok(())
+    }
+
     pub fn get(&self, package: &PackageVersion) -> io::Result<File> {
-        let path = self.get_file_path(package);
+        self.get_at(self.get_file_path(package, PACKAGE_EXTENSION))
+    }
+
+    pub fn get_symbols(&self, package: &PackageVersion) -> io::Result<File> {
+        self.get_at(self.get_file_path(package, SYMBOLS_EXTENSION))
+    }
 
+    fn get_at(&self, path: PathBuf) -> io::Result<File> {
         let file = {
             let _ = self.open_lock.lock().unwrap();
             let file = try!(File::open(path));
@@ -84,7 +115,7 @@ impl Storage {
     }
 
     pub fn rewrite(&self, package: &PackageVersion, file: File) -> io::Result<File> {
-        let path = self.get_file_path(package);
+        let path = self.get_file_path(package, PACKAGE_EXTENSION);
 
         let new_file = {
             let _ = self.open_lock.lock().unwrap();
@@ -99,8 +130,14 @@ impl Storage {
     }
 
     pub fn delete(&self, package: &PackageVersion) {
-        let path = self.get_file_path(package);
+        self.delete_at(self.get_file_path(package, PACKAGE_EXTENSION));
+        let symbols = self.get_file_path(package, SYMBOLS_EXTENSION);
+        if symbols.exists() {
+            self.delete_at(symbols);
+        }
+    }
 
+    fn delete_at(&self, path: PathBuf) {
         let _ = self.open_lock.lock().unwrap();
 
         {
@@ -123,4 +160,14 @@ impl Storage {
 
         // drop open mutex
     }
+
+    /// Creates and removes a scratch file, to make sure packages can be stored
+    pub fn check_writable(&self) -> io::Result<()> {
+        let path = {
+            self.path.lock().unwrap().join(format!(".hazel-check-{}", Uuid::new_v4().simple()))
+        };
+        let result = File::create(&path).and_then(|mut file| file.write_all(b"hazel"));
+        let _ = fs::remove_file(&path);
+        result
+    }
 }
@@ -0,0 +1,324 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use url::percent_encoding::percent_decode;
+use zip::ZipArchive;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use super::contents::MAX_READ_SIZE;
+use ::utils::error::ValidationProblem;
+
+/// Bigger PDBs are rejected
+pub const MAX_PDB_SIZE: u64 = 64 * 1024 * 1024;
+
+const CODEVIEW_DEBUG_TYPE: u32 = 2;
+const DEBUG_DIRECTORY_INDEX: usize = 6;
+const DEBUG_ENTRY_SIZE: usize = 28;
+const SECTION_HEADER_SIZE: usize = 40;
+
+macro_rules! some {
+    ($option:expr) => (
+        match $option {
+            Some(x) => x,
+            None => return None,
+        }
+    )
+}
+
+/// PDB of a symbol package, that belongs to a binary of the main package
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol
+{
+    /// name as stored in the archive
+    pub path: String,
+    /// lower case file name, debuggers ask for it
+    pub file_name: String,
+    /// guid of the debug id, lower case hex without dashes
+    pub signature: String,
+    pub size: u64,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16>
+{
+    if data.len() < 2 || offset > data.len() - 2 {
+        None
+    } else {
+        Some(data[offset] as u16 | (data[offset + 1] as u16) << 8)
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32>
+{
+    if data.len() < 4 || offset > data.len() - 4 {
+        None
+    } else {
+        Some(data[offset] as u32 | (data[offset + 1] as u32) << 8 | (data[offset + 2] as u32) << 16 | (data[offset + 3] as u32) << 24)
+    }
+}
+
+/// Formats a guid like symbol servers expect it, the first three fields are little endian
+fn guid(data: &[u8], offset: usize) -> Option<String>
+{
+    if data.len() < 16 || offset > data.len() - 16 {
+        return None;
+    }
+    Some(format!("{:08x}{:04x}{:04x}{}", some!(u32_at(data, offset)), some!(u16_at(data, offset + 4)), some!(u16_at(data, offset + 6)),
+        data[offset + 8..offset + 16].iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join("")))
+}
+
+/// Guid of the id in the `#Pdb` stream, None if the data is no portable PDB
+pub fn pdb_signature(pdb: &[u8]) -> Option<String>
+{
+    if pdb.len() < 4 || &pdb[..4] != b"BSJB" {
+        return None;
+    }
+    let version_length = some!(u32_at(pdb, 12)) as usize;
+    let streams = some!(u16_at(pdb, 16 + version_length + 2));
+    let mut offset = 16 + version_length + 4;
+    for _ in 0..streams {
+        let stream_offset = some!(u32_at(pdb, offset)) as usize;
+        let stream_size = some!(u32_at(pdb, offset + 4));
+        offset += 8;
+        if offset >= pdb.len() {
+            return None;
+        }
+        let name_length = some!(pdb[offset..].iter().position(|byte| *byte == 0));
+        let name = &pdb[offset..offset + name_length];
+        //names are null terminated and padded to four bytes
+        offset += (name_length + 4) & !3;
+        if name == b"#Pdb" {
+            return if stream_size < 20 { None } else { guid(pdb, stream_offset) };
+        }
+    }
+    None
+}
+
+fn debug_signatures(pe: &[u8]) -> Option<Vec<String>>
+{
+    if some!(u16_at(pe, 0)) != 0x5a4d {
+        return None;
+    }
+    let header = some!(u32_at(pe, 0x3c)) as usize;
+    if some!(u32_at(pe, header)) != 0x4550 {
+        return None;
+    }
+    let sections = some!(u16_at(pe, header + 6)) as usize;
+    let optional_header = header + 24;
+    let section_table = optional_header + some!(u16_at(pe, header + 20)) as usize;
+    let directories = match some!(u16_at(pe, optional_header)) {
+        0x10b => optional_header + 96,
+        0x20b => optional_header + 112,
+        _ => return None,
+    };
+    if (some!(u32_at(pe, directories - 4)) as usize) <= DEBUG_DIRECTORY_INDEX {
+        return None;
+    }
+    let debug_rva = some!(u32_at(pe, directories + DEBUG_DIRECTORY_INDEX * 8)) as usize;
+    let debug_size = some!(u32_at(pe, directories + DEBUG_DIRECTORY_INDEX * 8 + 4)) as usize;
+
+    let mut debug_directory = None;
+    for section in 0..sections {
+        let section = section_table + section * SECTION_HEADER_SIZE;
+        let virtual_size = some!(u32_at(pe, section + 8)) as usize;
+        let virtual_address = some!(u32_at(pe, section + 12)) as usize;
+        let raw_size = some!(u32_at(pe, section + 16)) as usize;
+        let raw_pointer = some!(u32_at(pe, section + 20)) as usize;
+        let size = if virtual_size > raw_size { virtual_size } else { raw_size };
+        if debug_rva >= virtual_address && debug_rva < virtual_address + size {
+            debug_directory = Some(raw_pointer + debug_rva - virtual_address);
+            break;
+        }
+    }
+    let debug_directory = some!(debug_directory);
+
+    let mut signatures = Vec::new();
+    for entry in 0..debug_size / DEBUG_ENTRY_SIZE {
+        let entry = debug_directory + entry * DEBUG_ENTRY_SIZE;
+        if some!(u32_at(pe, entry + 12)) != CODEVIEW_DEBUG_TYPE {
+            continue;
+        }
+        let data = some!(u32_at(pe, entry + 24)) as usize;
+        //"RSDS" followed by guid, age and pdb path
+        if u32_at(pe, data) == Some(0x53445352) {
+            signatures.extend(guid(pe, data + 4));
+        }
+    }
+    Some(signatures)
+}
+
+/// Guids of the PDBs a .dll or .exe was built with, taken from its CodeView debug entries
+pub fn binary_signatures(pe: &[u8]) -> Vec<String>
+{
+    debug_signatures(pe).unwrap_or(Vec::new())
+}
+
+/// Extracts the guid of a symbol server key, `<guid><age>` with the age in hex (`ffffffff` for portable PDBs)
+pub fn parse_signature(signature: &str) -> Option<String>
+{
+    let signature = signature.to_lowercase();
+    if signature.len() < 32 || signature.len() > 40 || !signature.chars().all(|c| c.is_digit(16)) {
+        return None;
+    }
+    Some(String::from(&signature[..32]))
+}
+
+fn decoded(name: &str) -> String
+{
+    String::from(percent_decode(name.as_bytes()).decode_utf8_lossy()).to_lowercase()
+}
+
+/// Matches every PDB of the symbol package with the .dll or .exe of the same path and name in the main package.
+/// Only portable PDBs are accepted, their ids have to match the debug entries of the binaries.
+pub fn check<R: Read + Seek, S: Read + Seek>(symbols: &mut ZipArchive<R>, package: &mut ZipArchive<S>) -> (Vec<Symbol>, Vec<ValidationProblem>)
+{
+    let mut problems = Vec::new();
+    let mut found = Vec::new();
+
+    let mut binaries = BTreeMap::new();
+    for i in 0..package.len() {
+        if let Ok(file) = package.by_index(i) {
+            let name = decoded(file.name());
+            if name.ends_with(".dll") || name.ends_with(".exe") {
+                binaries.insert(String::from(&name[..name.len() - 4]), (i, String::from(file.name())));
+            }
+        }
+    }
+
+    for i in 0..symbols.len() {
+        let (path, pdb) = {
+            let file = match symbols.by_index(i) {
+                Ok(file) => file,
+                Err(err) => {
+                    problems.push(ValidationProblem::new("archive", format!("Entry {} is unreadable: {}", i, err)));
+                    continue;
+                }
+            };
+            let path = String::from(file.name());
+            if !decoded(&path).ends_with(".pdb") {
+                continue;
+            }
+            if file.size() > MAX_PDB_SIZE {
+                problems.push(ValidationProblem::new(path, format!("May not be larger than {} MiB", MAX_PDB_SIZE / 1024 / 1024)));
+                continue;
+            }
+            let mut pdb = Vec::new();
+            if let Err(err) = file.take(MAX_PDB_SIZE).read_to_end(&mut pdb) {
+                problems.push(ValidationProblem::new(path, format!("Unreadable: {}", err)));
+                continue;
+            }
+            (path, pdb)
+        };
+
+        let signature = match pdb_signature(&pdb) {
+            Some(signature) => signature,
+            None => {
+                problems.push(ValidationProblem::new(path, "Only portable PDBs are supported"));
+                continue;
+            }
+        };
+        let name = decoded(&path);
+        let (index, binary_path) = match binaries.get(&name[..name.len() - 4]) {
+            Some(&(index, ref binary_path)) => (index, binary_path.clone()),
+            None => {
+                problems.push(ValidationProblem::new(path, "The package does not contain a .dll or .exe of the same name next to it"));
+                continue;
+            }
+        };
+        let mut binary = Vec::new();
+        //the declared size of an entry may be forged, so the read is capped as well
+        let read = match package.by_index(index) {
+            Ok(file) => file.take(MAX_READ_SIZE + 1).read_to_end(&mut binary).map_err(|err| format!("{}", err)),
+            Err(err) => Err(format!("{}", err)),
+        };
+        if let Err(err) = read {
+            problems.push(ValidationProblem::new(binary_path, format!("Unreadable: {}", err)));
+            continue;
+        }
+        if binary.len() as u64 > MAX_READ_SIZE {
+            problems.push(ValidationProblem::new(binary_path, format!("Binaries larger than {} MiB can not be checked against their symbols", MAX_READ_SIZE / 1024 / 1024)));
+            continue;
+        }
+        if !binary_signatures(&binary).contains(&signature) {
+            problems.push(ValidationProblem::new(path, format!("Was not built together with \"{}\" of the package", binary_path)));
+            continue;
+        }
+
+        found.push(Symbol {
+            file_name: String::from(name.rsplit('/').next().unwrap_or("")),
+            path: path,
+            signature: signature,
+            size: pdb.len() as u64,
+        });
+    }
+
+    if found.is_empty() && problems.is_empty() {
+        problems.push(ValidationProblem::new("archive", "The symbol package does not contain any .pdb files"));
+    }
+    (found, problems)
+}
+
+#[test]
+fn portable_pdb() {
+    let mut pdb = Vec::new();
+    pdb.extend(b"BSJB\x01\x00\x01\x00\x00\x00\x00\x00\x0c\x00\x00\x00PDB v1.0\x00\x00\x00\x00");
+    //flags, one stream at offset 48 with 32 bytes named "#Pdb"
+    pdb.extend(b"\x00\x00\x01\x00\x30\x00\x00\x00\x20\x00\x00\x00#Pdb\x00\x00\x00\x00");
+    pdb.extend(&[0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, 0x9a, 0xbc, 0xde, 0xf0, 0x12, 0x34, 0x56, 0x78]);
+    pdb.extend(&[0u8; 16]);
+
+    assert_eq!(pdb_signature(&pdb), Some(String::from("12345678123456789abcdef012345678")));
+    assert_eq!(pdb_signature(b"Microsoft C/C++ MSF 7.00\r\n"), None);
+    assert_eq!(pdb_signature(&pdb[..40]), None);
+}
+
+#[test]
+fn pe_codeview() {
+    let mut pe = vec![0u8; 0x300];
+    {
+        let mut put = |offset: usize, bytes: &[u8]| for (i, byte) in bytes.iter().enumerate() { pe[offset + i] = *byte; };
+        put(0, b"MZ");
+        put(0x3c, &[0x80, 0, 0, 0]);
+        put(0x80, b"PE\x00\x00");
+        //one section, 224 bytes of PE32 optional header
+        put(0x86, &[1, 0]);
+        put(0x94, &[0xe0, 0]);
+        put(0x98, &[0x0b, 0x01]);
+        put(0x98 + 92, &[16, 0, 0, 0]);
+        //debug directory at rva 0x1000 with one entry
+        put(0x98 + 96 + 48, &[0x00, 0x10, 0, 0, 28, 0, 0, 0]);
+        //section mapping rva 0x1000 to the file offset 0x200
+        put(0x178 + 8, &[0x00, 0x01, 0, 0, 0x00, 0x10, 0, 0, 0x00, 0x01, 0, 0, 0x00, 0x02, 0, 0]);
+        //codeview entry pointing at 0x240
+        put(0x200 + 12, &[2, 0, 0, 0]);
+        put(0x200 + 24, &[0x40, 0x02, 0, 0]);
+        put(0x240, b"RSDS");
+        put(0x244, &[0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, 0x9a, 0xbc, 0xde, 0xf0, 0x12, 0x34, 0x56, 0x78]);
+        put(0x254, b"\x01\x00\x00\x00Example.pdb\x00");
+    }
+
+    assert_eq!(binary_signatures(&pe), vec![String::from("12345678123456789abcdef012345678")]);
+    assert!(binary_signatures(&pe[..0x100]).is_empty());
+    assert!(binary_signatures(b"not a binary").is_empty());
+}
+
+#[test]
+fn symbol_server_keys() {
+    assert_eq!(parse_signature("12345678123456789ABCDEF012345678FFFFFFFF"), Some(String::from("12345678123456789abcdef012345678")));
+    assert_eq!(parse_signature("12345678123456789abcdef0123456781"), Some(String::from("12345678123456789abcdef012345678")));
+    assert_eq!(parse_signature("12345678123456789abcdef0123456"), None);
+    assert_eq!(parse_signature("12345678-1234-5678-9abc-def012345678"), None);
+}
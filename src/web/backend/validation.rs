@@ -0,0 +1,342 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use regex::Regex;
+use treexml::{Document, Element};
+use url::Url;
+use zip::ZipArchive;
+
+use std::io::{Read, Seek};
+
+use super::assets::{self, AssetKind, MAX_ASSET_SIZE};
+use super::version::{NugetVersion, VersionRange};
+use ::utils::error::ValidationProblem;
+
+lazy_static! {
+    //same rule as the nuget gallery
+    static ref ID_REGEX: Regex = Regex::new(r"^\w+([_.-]\w+)*$").unwrap();
+}
+
+const MAX_ID_LENGTH: usize = 100;
+const CONTENT_TYPES: &'static str = "[Content_Types].xml";
+const URL_TAGS: &'static [&'static str] = &["projectUrl", "licenseUrl", "iconUrl", "projectSourceUrl", "packageSourceUrl",
+                                             "docsUrl", "mailingListUrl", "bugTrackerUrl", "reportAbuseUrl"];
+
+/// The nuspec describing the package, nuspecs of nested packages in subdirectories are ignored
+pub fn is_root_nuspec(name: &str) -> bool
+{
+    !name.contains('/') && name.to_lowercase().ends_with(".nuspec")
+}
+
+/// Entries must stay inside the directory the package is extracted to
+fn escapes_package(name: &str) -> bool
+{
+    let name = name.replace('\\', "/");
+    name.starts_with('/') ||
+        name.split('/').next().map(|first| first.contains(':')).unwrap_or(false) ||
+        name.split('/').any(|segment| segment == "..")
+}
+
+/// Checks the archive layout and parses its nuspec.
+/// Returns the nuspec, if there is exactly one valid one, and all problems found on the way.
+pub fn validate_archive<R: Read + Seek>(zip: &mut ZipArchive<R>) -> (Option<Element>, Vec<ValidationProblem>)
+{
+    let mut problems = Vec::new();
+    let mut nuspecs = Vec::new();
+    let mut content_types = false;
+
+    for i in 0..zip.len() {
+        let name = match zip.by_index(i) {
+            Ok(file) => String::from(file.name()),
+            Err(err) => {
+                problems.push(ValidationProblem::new("archive", format!("Entry {} is unreadable: {}", i, err)));
+                continue;
+            }
+        };
+        if escapes_package(&name) {
+            problems.push(ValidationProblem::new(name.clone(), "Entries may not use absolute paths or \"..\""));
+        }
+        if name == CONTENT_TYPES {
+            content_types = true;
+        }
+        if is_root_nuspec(&name) {
+            nuspecs.push(i);
+        }
+    }
+
+    if !content_types {
+        problems.push(ValidationProblem::new(CONTENT_TYPES, "Missing, please build the package with \"choco pack\" or \"nuget pack\""));
+    }
+    if nuspecs.len() != 1 {
+        problems.push(ValidationProblem::new("nuspec", format!("The package root has to contain exactly one .nuspec file, found {}", nuspecs.len())));
+        return (None, problems);
+    }
+
+    let nuspec = match read_nuspec(zip, nuspecs[0]) {
+        Ok(nuspec) => nuspec,
+        Err(problem) => {
+            problems.push(problem);
+            return (None, problems);
+        },
+    };
+    problems.extend(validate_nuspec(&nuspec));
+    if let Some(metadata) = nuspec.find_child(|tag| tag.name == "metadata") {
+        problems.extend(validate_assets(zip, metadata));
+    }
+    (Some(nuspec), problems)
+}
+
+fn read_nuspec<R: Read + Seek>(zip: &mut ZipArchive<R>, index: usize) -> Result<Element, ValidationProblem>
+{
+    match zip.by_index(index).map_err(|err| format!("{}", err))
+             .and_then(|file| Document::parse(file).map_err(|err| format!("{}", err))) {
+        Ok(Document { root: Some(root), .. }) => Ok(root),
+        Ok(_) => Err(ValidationProblem::new("nuspec", "The .nuspec file is empty")),
+        Err(err) => Err(ValidationProblem::new("nuspec", format!("The .nuspec file is no valid xml: {}", err))),
+    }
+}
+
+/// Checks the structure of a symbol package (.snupkg), its PDBs are checked against the main package by `symbols::check`
+pub fn validate_symbol_archive<R: Read + Seek>(zip: &mut ZipArchive<R>) -> (Option<Element>, Vec<ValidationProblem>)
+{
+    let mut problems = Vec::new();
+    let mut nuspecs = Vec::new();
+
+    for i in 0..zip.len() {
+        let name = match zip.by_index(i) {
+            Ok(file) => String::from(file.name()),
+            Err(err) => {
+                problems.push(ValidationProblem::new("archive", format!("Entry {} is unreadable: {}", i, err)));
+                continue;
+            }
+        };
+        if escapes_package(&name) {
+            problems.push(ValidationProblem::new(name.clone(), "Entries may not use absolute paths or \"..\""));
+        }
+        if is_root_nuspec(&name) {
+            nuspecs.push(i);
+        }
+    }
+
+    if nuspecs.len() != 1 {
+        problems.push(ValidationProblem::new("nuspec", format!("The package root has to contain exactly one .nuspec file, found {}", nuspecs.len())));
+        return (None, problems);
+    }
+    let nuspec = match read_nuspec(zip, nuspecs[0]) {
+        Ok(nuspec) => nuspec,
+        Err(problem) => {
+            problems.push(problem);
+            return (None, problems);
+        },
+    };
+
+    match nuspec.find_child(|tag| tag.name == "metadata") {
+        Some(metadata) => {
+            let text = |name: &str| metadata.find_child(|tag| tag.name == name).and_then(|tag| tag.text.clone());
+            if text("id").map(|id| id.trim().is_empty()).unwrap_or(true) {
+                problems.push(ValidationProblem::new("id", "Required"));
+            }
+            match text("version") {
+                Some(version) => if NugetVersion::parse(&version).is_err() {
+                    problems.push(ValidationProblem::new("version", format!("\"{}\" is no valid version", version)));
+                },
+                None => problems.push(ValidationProblem::new("version", "Required")),
+            }
+            let symbols_package = metadata.find_child(|tag| tag.name == "packageTypes")
+                .map(|types| types.children.iter().any(|package_type| package_type.attributes.get("name").map(|name| &**name) == Some("SymbolsPackage")))
+                .unwrap_or(false);
+            if !symbols_package {
+                problems.push(ValidationProblem::new("packageTypes", "Symbol packages have the package type \"SymbolsPackage\", please build them with \"dotnet pack\" or \"nuget pack -Symbols -SymbolPackageFormat snupkg\""));
+            }
+        },
+        None => problems.push(ValidationProblem::new("metadata", "The .nuspec file does not contain a \"metadata\" tag")),
+    }
+    (Some(nuspec), problems)
+}
+
+/// Files referenced by `icon`, `readme` and `license type="file"` have to be part of the package
+fn validate_assets<R: Read + Seek>(zip: &mut ZipArchive<R>, metadata: &Element) -> Vec<ValidationProblem>
+{
+    let mut problems = Vec::new();
+    for kind in AssetKind::all() {
+        let path = match assets::referenced_file(metadata, kind) {
+            Some(path) => path,
+            None => continue,
+        };
+        if kind.media_type(&path).is_none() {
+            problems.push(ValidationProblem::new(kind.as_str(), "Only png, jpg and gif icons are supported"));
+        }
+        match assets::find_entry(zip, &path).map(|index| zip.by_index(index).map(|file| file.size())) {
+            Some(Ok(size)) if size > MAX_ASSET_SIZE => problems.push(ValidationProblem::new(kind.as_str(), format!("\"{}\" may not be larger than {} KiB", path, MAX_ASSET_SIZE / 1024))),
+            Some(Ok(_)) => {},
+            Some(Err(err)) => problems.push(ValidationProblem::new(kind.as_str(), format!("\"{}\" is unreadable: {}", path, err))),
+            None => problems.push(ValidationProblem::new(kind.as_str(), format!("\"{}\" is not part of the package", path))),
+        }
+    }
+    problems
+}
+
+/// Checks the metadata against the rules of nuget
+pub fn validate_nuspec(nuspec: &Element) -> Vec<ValidationProblem>
+{
+    let mut problems = Vec::new();
+    let metadata = match nuspec.find_child(|tag| tag.name == "metadata") {
+        Some(metadata) => metadata,
+        None => {
+            problems.push(ValidationProblem::new("metadata", "The .nuspec file does not contain a \"metadata\" tag"));
+            return problems;
+        }
+    };
+    let text = |name: &str| metadata.find_child(|tag| tag.name == name)
+                                    .and_then(|tag| tag.text.clone())
+                                    .map(|text| String::from(text.trim()))
+                                    .and_then(|text| if text.is_empty() { None } else { Some(text) });
+
+    match text("id") {
+        Some(id) => {
+            if id.len() > MAX_ID_LENGTH {
+                problems.push(ValidationProblem::new("id", format!("May not be longer than {} characters", MAX_ID_LENGTH)));
+            }
+            if !ID_REGEX.is_match(&id) {
+                problems.push(ValidationProblem::new("id", "May only contain letters, digits and \"_\", \".\" or \"-\" between them"));
+            }
+        },
+        None => problems.push(ValidationProblem::new("id", "Required")),
+    }
+
+    match text("version") {
+        Some(version) => if NugetVersion::parse(&version).is_err() {
+            problems.push(ValidationProblem::new("version", format!("\"{}\" is no valid version", version)));
+        },
+        None => problems.push(ValidationProblem::new("version", "Required")),
+    }
+
+    if text("description").is_none() {
+        problems.push(ValidationProblem::new("description", "Required"));
+    }
+    if text("authors").is_none() {
+        problems.push(ValidationProblem::new("authors", "Required, separate multiple authors by \",\""));
+    }
+
+    for name in URL_TAGS.iter() {
+        if let Some(url) = text(*name) {
+            match Url::parse(&url) {
+                Ok(ref parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => {},
+                _ => problems.push(ValidationProblem::new(*name, format!("\"{}\" is no absolute http or https url", url))),
+            }
+        }
+    }
+
+    if let Some(dependencies) = metadata.find_child(|tag| tag.name == "dependencies") {
+        let groups = dependencies.children.iter().filter(|tag| tag.name == "group").flat_map(|group| group.children.iter());
+        for dependency in dependencies.children.iter().filter(|tag| tag.name == "dependency").chain(groups) {
+            let id = match dependency.attributes.get("id") {
+                Some(id) if !id.trim().is_empty() => id,
+                _ => {
+                    problems.push(ValidationProblem::new("dependencies", "Every dependency needs an \"id\" attribute"));
+                    continue;
+                }
+            };
+            if let Some(range) = dependency.attributes.get("version") {
+                if VersionRange::convert(range).is_err() {
+                    problems.push(ValidationProblem::new("dependencies", format!("\"{}\" of \"{}\" is no valid version range", range, id)));
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+fn nuspec_problems(id: &str, extra: &str) -> Vec<String>
+{
+    let xml = format!("<package><metadata><id>{}</id><version>1.0.0</version><description>Test</description><authors>Contoso</authors>{}</metadata></package>", id, extra);
+    let nuspec = Document::parse(xml.as_bytes()).unwrap().root.unwrap();
+    validate_nuspec(&nuspec).into_iter().map(|problem| problem.location).collect()
+}
+
+#[cfg(test)]
+fn archive_problems(entries: &[&str]) -> (bool, Vec<String>)
+{
+    use std::io::{Cursor, Write};
+    use zip::{ZipWriter, CompressionMethod};
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for name in entries.iter() {
+        writer.start_file(*name, CompressionMethod::Stored).unwrap();
+        if is_root_nuspec(name) {
+            write!(writer, "<package><metadata><id>Contoso.Core</id><version>1.0.0</version><description>Test</description><authors>Contoso</authors></metadata></package>").unwrap();
+        } else {
+            writer.write_all(b"Write-Host 'test'").unwrap();
+        }
+    }
+    let mut zip = ZipArchive::new(writer.finish().unwrap()).unwrap();
+    let (nuspec, problems) = validate_archive(&mut zip);
+    (nuspec.is_some(), problems.into_iter().map(|problem| problem.location).collect())
+}
+
+#[test]
+fn nuspec_id() {
+    use std::iter;
+
+    assert!(nuspec_problems("Contoso.Core", "").is_empty());
+    assert!(nuspec_problems("Contoso_Core.2-beta", "").is_empty());
+    for id in &["Contoso..Core", ".Contoso", "Contoso-", "Contoso Core", "Contoso/Core"] {
+        assert_eq!(nuspec_problems(id, ""), vec!["id"]);
+    }
+    assert_eq!(nuspec_problems("", ""), vec!["id"]);
+    assert!(nuspec_problems(&iter::repeat("a").take(100).collect::<String>(), "").is_empty());
+    assert_eq!(nuspec_problems(&iter::repeat("a").take(101).collect::<String>(), ""), vec!["id"]);
+}
+
+#[test]
+fn nuspec_required_fields() {
+    let problems = |xml: &str| -> Vec<String> {
+        validate_nuspec(&Document::parse(xml.as_bytes()).unwrap().root.unwrap()).into_iter().map(|problem| problem.location).collect()
+    };
+    assert_eq!(problems("<package><metadata/></package>"), vec!["id", "version", "description", "authors"]);
+    assert_eq!(problems("<package/>"), vec!["metadata"]);
+    assert_eq!(problems("<package><metadata><id>Contoso</id><version>one</version><description>Test</description><authors>Contoso</authors></metadata></package>"), vec!["version"]);
+}
+
+#[test]
+fn nuspec_urls() {
+    assert!(nuspec_problems("Contoso", "<projectUrl>https://contoso.example.org</projectUrl><licenseUrl>http://contoso.example.org/license</licenseUrl>").is_empty());
+    assert_eq!(nuspec_problems("Contoso", "<iconUrl>contoso.example.org/icon.png</iconUrl>"), vec!["iconUrl"]);
+    assert_eq!(nuspec_problems("Contoso", "<docsUrl>javascript:alert(1)</docsUrl>"), vec!["docsUrl"]);
+    assert_eq!(nuspec_problems("Contoso", "<projectSourceUrl>ftp://contoso.example.org</projectSourceUrl><bugTrackerUrl>https://</bugTrackerUrl>"),
+               vec!["projectSourceUrl", "bugTrackerUrl"]);
+}
+
+#[test]
+fn archive_paths() {
+    for name in &["tools/chocolateyInstall.ps1", "lib/net45/Contoso.dll", "tools/a..b/c.txt", "[Content_Types].xml"] {
+        assert!(!escapes_package(name), "{}", name);
+    }
+    for name in &["../evil.ps1", "tools/../../evil.ps1", "tools\\..\\..\\evil.ps1", "/etc/passwd", "\\evil.ps1", "C:/Windows/evil.dll", "C:\\Windows\\evil.dll"] {
+        assert!(escapes_package(name), "{}", name);
+    }
+}
+
+#[test]
+fn archive_layout() {
+    assert_eq!(archive_problems(&["[Content_Types].xml", "Contoso.Core.nuspec", "tools/chocolateyInstall.ps1"]), (true, Vec::new()));
+    assert_eq!(archive_problems(&["[Content_Types].xml", "Contoso.Core.nuspec", "tools/nested/Other.nuspec"]), (true, Vec::new()));
+    assert_eq!(archive_problems(&["Contoso.Core.nuspec", "tools/chocolateyInstall.ps1"]), (true, vec![String::from(CONTENT_TYPES)]));
+    assert_eq!(archive_problems(&["[Content_Types].xml", "Contoso.Core.nuspec", "Other.nuspec"]), (false, vec![String::from("nuspec")]));
+    assert_eq!(archive_problems(&["[Content_Types].xml", "tools/chocolateyInstall.ps1"]), (false, vec![String::from("nuspec")]));
+    assert_eq!(archive_problems(&["[Content_Types].xml", "Contoso.Core.nuspec", "../evil.ps1"]), (true, vec![String::from("../evil.ps1")]));
+}
@@ -13,117 +13,295 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use semver::{ReqParseError, VersionReq, Predicate, PredBuilder, Op, Identifier, WildcardVersion};
+use semver::{VersionReq, Op, Identifier, WildcardVersion};
 
 use regex::Regex;
 
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
 use ::utils::error::NugetVersionError;
 
 lazy_static! {
-    static ref VERSION_REG_REGEX: Regex = Regex::new(r"^(\[|\()?\s*((\d+)(?:\.(\d+))?(?:\.(\d+))?(?:-(\w+))?)?\s*(,?)\s*((\d+)(?:\.(\d+))?(?:\.(\d+))?(?:-(\w+))?)?\s*(\]|\))?$").unwrap();
+    static ref VERSION_REGEX: Regex = Regex::new(r"^(\d+)(?:\.(\d+))?(?:\.(\d+))?(?:\.(\d+))?(?:-([0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*))?(?:\+[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?$").unwrap();
+    static ref VERSION_REG_REGEX: Regex = Regex::new(r"^(\[|\()?\s*(\d+(?:\.\d+){0,3}(?:-[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?(?:\+[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?)?\s*(,?)\s*(\d+(?:\.\d+){0,3}(?:-[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?(?:\+[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?)?\s*(\]|\))?$").unwrap();
 }
 
-pub trait NugetToSemver
+/// NuGet version with up to four numeric parts and a dotted prerelease label.
+/// Missing parts are zero, build metadata is accepted but dropped, so it plays no role in equality or ordering.
+#[derive(Clone, Debug)]
+pub struct NugetVersion
 {
-    fn convert(nuget_requirement: &str) -> Result<VersionReq, ReqParseError>;
-    fn to_nuget(&self) -> Result<Option<String>, NugetVersionError>;
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub revision: u64,
+    pub pre: Vec<Identifier>,
 }
 
-impl NugetToSemver for VersionReq
+impl NugetVersion
 {
-    fn convert(nuget_requirement: &str) -> Result<VersionReq, ReqParseError>
+    pub fn parse(version: &str) -> Result<NugetVersion, NugetVersionError>
     {
-        let caps = match VERSION_REG_REGEX.captures_iter(nuget_requirement).next() {
-            None => return Err(ReqParseError::InvalidVersionRequirement),
-            Some(x) => x,
+        let caps = match VERSION_REGEX.captures(version.trim()) {
+            Some(caps) => caps,
+            None => return Err(NugetVersionError::InvalidVersion),
+        };
+        let part = |index| match caps.at(index) {
+            Some(number) => number.parse::<u64>().map_err(|_| NugetVersionError::InvalidVersion),
+            None => Ok(0),
         };
 
-        let range_start = caps.at(1);
-        let ver1 = caps.at(2);
-        let ver1_major = caps.at(3);
-        let ver1_minor = caps.at(4);
-        let ver1_patch = caps.at(5);
-        let ver1_pre = caps.at(6);
-        let comma = caps.at(7);
-        let ver2 = caps.at(8);
-        let ver2_major = caps.at(9);
-        let ver2_minor = caps.at(10);
-        let ver2_patch = caps.at(11);
-        let ver2_pre = caps.at(12);
-        let range_stop = caps.at(13);
-
-        let mut builder_min = PredBuilder::new();
-        let mut builder_max = PredBuilder::new();
-
-        // ignore some invalid ones, that are not easily parsed by the regex
-        if range_start.is_some() != range_stop.is_some() {
-            return Err(ReqParseError::InvalidVersionRequirement);
-        }
-        if comma.is_some() && ver1.is_some() && ver2.is_none() && range_stop.is_some() && range_stop.unwrap() == "]" {
-            return Err(ReqParseError::InvalidVersionRequirement);
+        Ok(NugetVersion {
+            major: try!(part(1)),
+            minor: try!(part(2)),
+            patch: try!(part(3)),
+            revision: try!(part(4)),
+            pre: caps.at(5).map(|pre| pre.split('.').map(|label| match label.parse::<u64>() {
+                Ok(number) => Identifier::Numeric(number),
+                Err(_) => Identifier::AlphaNumeric(String::from(label)),
+            }).collect()).unwrap_or(Vec::new()),
+        })
+    }
+
+    pub fn is_prerelease(&self) -> bool
+    {
+        !self.pre.is_empty()
+    }
+
+    fn same_release(&self, other: &NugetVersion) -> bool
+    {
+        self.major == other.major && self.minor == other.minor && self.patch == other.patch && self.revision == other.revision
+    }
+}
+
+impl FromStr for NugetVersion
+{
+    type Err = NugetVersionError;
+
+    fn from_str(version: &str) -> Result<NugetVersion, NugetVersionError>
+    {
+        NugetVersion::parse(version)
+    }
+}
+
+/// Normalized form: at least three parts, the revision only if it is not zero
+impl fmt::Display for NugetVersion
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        try!(write!(f, "{}.{}.{}", self.major, self.minor, self.patch));
+        if self.revision != 0 {
+            try!(write!(f, ".{}", self.revision));
         }
-        if comma.is_some() && ver1.is_none() && ver2.is_some() && range_start.is_some() && range_start.unwrap() == "[" {
-            return Err(ReqParseError::InvalidVersionRequirement);
+        if self.is_prerelease() {
+            try!(write!(f, "-{}", self.pre.iter().map(|label| format!("{}", label)).collect::<Vec<_>>().join(".")));
         }
+        Ok(())
+    }
+}
 
-        match range_start {
-            None => try!(builder_min.set_op(Op::GtEq)),
-            Some("[") => {
-                match comma {
-                    Some(_) => try!(builder_min.set_op(Op::GtEq)),
-                    None => match range_stop {
-                        Some("]") => try!(builder_max.set_op(Op::Ex)),
-                        Some(")") => return Err(ReqParseError::InvalidVersionRequirement),
-                        _ => unreachable!(),
-                    }
+/// Prerelease labels compare numerically if both are numbers and case insensitive otherwise
+fn compare_pre(a: &[Identifier], b: &[Identifier]) -> Ordering
+{
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => {
+            for (x, y) in a.iter().zip(b.iter()) {
+                let ordering = match (x, y) {
+                    (&Identifier::Numeric(ref x), &Identifier::Numeric(ref y)) => x.cmp(y),
+                    (&Identifier::Numeric(_), &Identifier::AlphaNumeric(_)) => Ordering::Less,
+                    (&Identifier::AlphaNumeric(_), &Identifier::Numeric(_)) => Ordering::Greater,
+                    (&Identifier::AlphaNumeric(ref x), &Identifier::AlphaNumeric(ref y)) => x.to_lowercase().cmp(&y.to_lowercase()),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
                 }
             }
-            Some("(") => try!(builder_min.set_op(Op::Gt)),
-            _ => unreachable!(),
+            a.len().cmp(&b.len())
+        },
+    }
+}
+
+impl Ord for NugetVersion
+{
+    fn cmp(&self, other: &NugetVersion) -> Ordering
+    {
+        match (self.major, self.minor, self.patch, self.revision).cmp(&(other.major, other.minor, other.patch, other.revision)) {
+            Ordering::Equal => compare_pre(&self.pre, &other.pre),
+            x => x,
+        }
+    }
+}
+impl PartialOrd for NugetVersion
+{
+    fn partial_cmp(&self, other: &NugetVersion) -> Option<Ordering>
+    {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for NugetVersion
+{
+    fn eq(&self, other: &NugetVersion) -> bool
+    {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for NugetVersion {}
+
+/// NuGet version range, every bound is a version and whether it is inclusive
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionRange
+{
+    pub min: Option<(NugetVersion, bool)>,
+    pub max: Option<(NugetVersion, bool)>,
+}
+
+impl VersionRange
+{
+    pub fn any() -> VersionRange
+    {
+        VersionRange {
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Parses NuGet range notation, "1.0" means at least 1.0, "[1.0]" exactly 1.0,
+    /// "(1.0,2.0]" more than 1.0 up to 2.0 inclusive, either side may be left out.
+    pub fn convert(nuget_requirement: &str) -> Result<VersionRange, NugetVersionError>
+    {
+        let nuget_requirement = nuget_requirement.trim();
+        if nuget_requirement.is_empty() || nuget_requirement == "*" {
+            return Ok(VersionRange::any());
+        }
+
+        let caps = match VERSION_REG_REGEX.captures(nuget_requirement) {
+            None => return Err(NugetVersionError::InvalidRange),
+            Some(x) => x,
+        };
+
+        let range_start = caps.at(1);
+        let ver1 = match caps.at(2) {
+            Some(version) => Some(try!(NugetVersion::parse(version))),
+            None => None,
         };
-        let pred_min = match ver1 {
+        let comma = caps.at(3).map(|comma| !comma.is_empty()).unwrap_or(false);
+        let ver2 = match caps.at(4) {
+            Some(version) => Some(try!(NugetVersion::parse(version))),
             None => None,
-            Some(_) => {
-                builder_min.major = ver1_major.and_then(|x| x.parse::<u64>().ok());
-                builder_min.minor = ver1_minor.and_then(|x| x.parse::<u64>().ok());
-                builder_min.patch = ver1_patch.and_then(|x| x.parse::<u64>().ok());
-                builder_min.has_pre = ver1_pre.is_some();
-                if ver1_pre.is_some() {
-                    builder_min.pre.push(match ver1_pre.unwrap().parse().ok() {
-                        Some(n) => Identifier::Numeric(n),
-                        None => Identifier::AlphaNumeric(String::from(ver1_pre.unwrap())),
-                    })
+        };
+        let range_stop = caps.at(5);
+
+        let range = match (range_start, range_stop, comma) {
+            (None, None, false) => VersionRange {
+                min: ver1.map(|version| (version, true)),
+                max: None,
+            },
+            (Some("["), Some("]"), false) => VersionRange {
+                min: ver1.clone().map(|version| (version, true)),
+                max: ver1.map(|version| (version, true)),
+            },
+            (Some(start), Some(stop), true) => {
+                // "[,1.0]" and "(1.0,]" have an inclusive bound without a version
+                if (ver1.is_none() && start == "[") || (ver2.is_none() && stop == "]") {
+                    return Err(NugetVersionError::InvalidRange);
                 }
-                Some(try!(builder_min.build()))
-            }
+                VersionRange {
+                    min: ver1.map(|version| (version, start == "[")),
+                    max: ver2.map(|version| (version, stop == "]")),
+                }
+            },
+            _ => return Err(NugetVersionError::InvalidRange),
         };
 
-        match range_stop {
-            None | Some("]") => try!(builder_max.set_op(Op::LtEq)),
-            Some(")") => try!(builder_max.set_op(Op::Lt)),
-            _ => unreachable!(),
+        match (&range.min, &range.max) {
+            (&None, &None) => Err(NugetVersionError::InvalidRange),
+            (&Some((ref min, min_inclusive)), &Some((ref max, max_inclusive))) if min > max || (min == max && !(min_inclusive && max_inclusive)) => Err(NugetVersionError::InvalidRange),
+            _ => Ok(range),
+        }
+    }
+
+    /// Prereleases only match, if one of the bounds is a prerelease of the same version
+    pub fn matches(&self, version: &NugetVersion) -> bool
+    {
+        if version.is_prerelease() && !self.min.iter().chain(self.max.iter())
+            .any(|&(ref bound, _)| bound.is_prerelease() && bound.same_release(version)) {
+            return false;
+        }
+
+        let above_min = match self.min {
+            Some((ref min, true)) => version >= min,
+            Some((ref min, false)) => version > min,
+            None => true,
         };
-        let pred_max = match ver2 {
-            None => None,
-            Some(_) => {
-                builder_max.major = ver2_major.and_then(|x| x.parse::<u64>().ok());
-                builder_max.minor = ver2_minor.and_then(|x| x.parse::<u64>().ok());
-                builder_max.patch = ver2_patch.and_then(|x| x.parse::<u64>().ok());
-                builder_max.has_pre = ver2_pre.is_some();
-                if ver2_pre.is_some() {
-                    builder_max.pre.push(match ver2_pre.unwrap().parse().ok() {
-                        Some(n) => Identifier::Numeric(n),
-                        None => Identifier::AlphaNumeric(String::from(ver2_pre.unwrap())),
-                    })
-                }
-                Some(try!(builder_max.build()))
-            }
+        let below_max = match self.max {
+            Some((ref max, true)) => version <= max,
+            Some((ref max, false)) => version < max,
+            None => true,
         };
+        above_min && below_max
+    }
+
+    /// None if the range does not restrict the version at all
+    pub fn to_nuget(&self) -> Option<String>
+    {
+        match (&self.min, &self.max) {
+            (&None, &None) => None,
+            _ => Some(format!("{}", self)),
+        }
+    }
+}
+
+/// Older hazel versions stored ranges in semver notation, those are converted on the fly
+impl FromStr for VersionRange
+{
+    type Err = NugetVersionError;
+
+    fn from_str(range: &str) -> Result<VersionRange, NugetVersionError>
+    {
+        match VersionRange::convert(range) {
+            Ok(range) => Ok(range),
+            Err(err) => match VersionReq::from_str(range) {
+                Ok(req) => match try!(req.to_nuget()) {
+                    Some(range) => VersionRange::convert(&range),
+                    None => Ok(VersionRange::any()),
+                },
+                Err(_) => Err(err),
+            },
+        }
+    }
+}
 
-        let predicates: Vec<Predicate> = pred_min.iter().chain(pred_max.iter()).cloned().collect();
-        Ok(VersionReq::new(&predicates))
+/// Normalized NuGet notation, "*" for any version
+impl fmt::Display for VersionRange
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match (&self.min, &self.max) {
+            (&None, &None) => write!(f, "*"),
+            (&Some((ref min, true)), &None) => write!(f, "{}", min),
+            (&Some((ref min, true)), &Some((ref max, true))) if min == max => write!(f, "[{}]", min),
+            (min, max) => write!(f, "{}{}, {}{}",
+                match *min { Some((_, true)) => "[", _ => "(" },
+                min.as_ref().map(|&(ref version, _)| format!("{}", version)).unwrap_or(String::new()),
+                max.as_ref().map(|&(ref version, _)| format!("{}", version)).unwrap_or(String::new()),
+                match *max { Some((_, true)) => "]", _ => ")" }),
+        }
     }
+}
+
+/// Converts ranges stored in semver notation by older hazel versions
+trait SemverToNuget
+{
+    fn to_nuget(&self) -> Result<Option<String>, NugetVersionError>;
+}
 
+impl SemverToNuget for VersionReq
+{
     fn to_nuget(&self) -> Result<Option<String>, NugetVersionError>
     {
         let predicates = self.predicates();
@@ -191,90 +369,142 @@ impl NugetToSemver for VersionReq
 
 #[test]
 fn solo() {
-    use semver::Version;
-
-    let req = VersionReq::convert("1.0").unwrap();
-    assert!(!req.matches(&Version::parse("0.9.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.1.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.4").unwrap()));
-    assert!(!req.matches(&Version::parse("3.0.0-alpha1").unwrap()));
+    let req = VersionRange::convert("1.0").unwrap();
+    assert!(!req.matches(&NugetVersion::parse("0.9.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.1.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.4").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("3.0.0-alpha1").unwrap()));
 }
 
 #[test]
 fn pre() {
-    use semver::Version;
-
-    let req = VersionReq::convert("1.0.0-alpha1").unwrap();
-    assert!(!req.matches(&Version::parse("0.9.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.1.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0-prealpha0").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0-alpha1").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0-alpha2").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0-beta1").unwrap()));
+    let req = VersionRange::convert("1.0.0-alpha1").unwrap();
+    assert!(!req.matches(&NugetVersion::parse("0.9.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.1.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-prealpha0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-alpha1").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-alpha2").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-beta1").unwrap()));
 }
 
 #[test]
 fn solo_two() {
-    use semver::Version;
-
-    let req = VersionReq::convert("[1.0,)").unwrap();
-    assert!(!req.matches(&Version::parse("0.9.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.1.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.4").unwrap()));
-    assert!(!req.matches(&Version::parse("3.0.0-alpha1").unwrap()));
+    let req = VersionRange::convert("[1.0,)").unwrap();
+    assert!(!req.matches(&NugetVersion::parse("0.9.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.1.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.4").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("3.0.0-alpha1").unwrap()));
 }
 
 #[test]
 fn solo_three() {
-    use semver::Version;
-
-    let req = VersionReq::convert("(,1.0]").unwrap();
-    assert!(req.matches(&Version::parse("0.9.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.0.0").unwrap()));
-    assert!(!req.matches(&Version::parse("1.1.0").unwrap()));
+    let req = VersionRange::convert("(,1.0]").unwrap();
+    assert!(req.matches(&NugetVersion::parse("0.9.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.1.0").unwrap()));
 }
 
 #[test]
 fn solo_four() {
-    use semver::Version;
-
-    let req = VersionReq::convert("(,1.0)").unwrap();
-    assert!(req.matches(&Version::parse("0.9.0").unwrap()));
-    assert!(!req.matches(&Version::parse("1.0.0").unwrap()));
-    assert!(!req.matches(&Version::parse("1.1.0").unwrap()));
+    let req = VersionRange::convert("(,1.0)").unwrap();
+    assert!(req.matches(&NugetVersion::parse("0.9.0").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.1.0").unwrap()));
 }
 
 #[test]
 fn minimum() {
-    use semver::Version;
-
-    let req = VersionReq::convert("(1.0,)").unwrap();
-    assert!(!req.matches(&Version::parse("0.9.0").unwrap()));
-    assert!(!req.matches(&Version::parse("1.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.1.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.4").unwrap()));
-    assert!(!req.matches(&Version::parse("3.0.0-alpha1").unwrap()));
+    let req = VersionRange::convert("(1.0,)").unwrap();
+    assert!(!req.matches(&NugetVersion::parse("0.9.0").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.1.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.4").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("3.0.0-alpha1").unwrap()));
 }
 
 #[test]
 fn full() {
-    use semver::Version;
-
-    let req = VersionReq::convert("(1.0.0,3.0.1]").unwrap();
-    assert!(!req.matches(&Version::parse("0.9.0").unwrap()));
-    assert!(!req.matches(&Version::parse("1.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("1.1.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.0").unwrap()));
-    assert!(req.matches(&Version::parse("2.0.4").unwrap()));
-    assert!(!req.matches(&Version::parse("3.0.0-alpha1").unwrap()));
-    assert!(req.matches(&Version::parse("3.0.1").unwrap()));
-    assert!(!req.matches(&Version::parse("3.0.2").unwrap()));
-    assert!(!req.matches(&Version::parse("3.1.0").unwrap()));
-    assert!(!req.matches(&Version::parse("4.0.0").unwrap()));
+    let req = VersionRange::convert("(1.0.0,3.0.1]").unwrap();
+    assert!(!req.matches(&NugetVersion::parse("0.9.0").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.1.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.0").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("2.0.4").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("3.0.0-alpha1").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("3.0.1").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("3.0.2").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("3.1.0").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("4.0.0").unwrap()));
+}
+
+#[test]
+fn four_part() {
+    let req = VersionRange::convert("[1.2.3.4,1.2.3.10)").unwrap();
+    assert!(!req.matches(&NugetVersion::parse("1.2.3").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.2.3.3").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.2.3.4").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.2.3.9").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.2.3.10").unwrap()));
+    assert!(!req.matches(&NugetVersion::parse("1.2.4").unwrap()));
+    assert!(NugetVersion::parse("2016.10.18.1").unwrap() > NugetVersion::parse("2016.10.18").unwrap());
+    assert!(NugetVersion::parse("1.2.3.1").unwrap() < NugetVersion::parse("1.2.4-alpha").unwrap());
+}
+
+#[test]
+fn dotted_pre() {
+    let req = VersionRange::convert("[1.0.0-beta.2,1.0.0]").unwrap();
+    assert!(!req.matches(&NugetVersion::parse("1.0.0-beta.1").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-beta.2").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-beta.10").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-BETA.11").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0-rc.1").unwrap()));
+    assert!(req.matches(&NugetVersion::parse("1.0.0").unwrap()));
+    assert!(NugetVersion::parse("1.0.0-beta").unwrap() < NugetVersion::parse("1.0.0-beta.1").unwrap());
+    assert!(NugetVersion::parse("1.0.0-1").unwrap() < NugetVersion::parse("1.0.0-alpha").unwrap());
+}
+
+#[test]
+fn build_ignored() {
+    assert_eq!(NugetVersion::parse("1.0.0+abc").unwrap(), NugetVersion::parse("1.0.0+def").unwrap());
+    assert_eq!(format!("{}", NugetVersion::parse("1.0.0-rc.1+build.5").unwrap()), "1.0.0-rc.1");
+    let req = VersionRange::convert("[1.0.0+abc]").unwrap();
+    assert!(req.matches(&NugetVersion::parse("1.0.0+def").unwrap()));
+}
+
+#[test]
+fn normalized() {
+    assert_eq!(format!("{}", NugetVersion::parse("1").unwrap()), "1.0.0");
+    assert_eq!(format!("{}", NugetVersion::parse("1.2").unwrap()), "1.2.0");
+    assert_eq!(format!("{}", NugetVersion::parse("1.2.3.0").unwrap()), "1.2.3");
+    assert_eq!(format!("{}", NugetVersion::parse("01.02.03.04").unwrap()), "1.2.3.4");
+    assert!(NugetVersion::parse("1.2.3.4.5").is_err());
+    assert!(NugetVersion::parse("1.0.0-beta..1").is_err());
+    assert_eq!(format!("{}", VersionRange::convert("1.0").unwrap()), "1.0.0");
+    assert_eq!(format!("{}", VersionRange::convert("[1.0]").unwrap()), "[1.0.0]");
+    assert_eq!(format!("{}", VersionRange::convert("(,2.0.0.1]").unwrap()), "(, 2.0.0.1]");
+    assert_eq!(format!("{}", VersionRange::convert("").unwrap()), "*");
+}
+
+#[test]
+fn invalid_ranges() {
+    assert!(VersionRange::convert("[1.0)").is_err());
+    assert!(VersionRange::convert("(1.0)").is_err());
+    assert!(VersionRange::convert("[,1.0]").is_err());
+    assert!(VersionRange::convert("(1.0,]").is_err());
+    assert!(VersionRange::convert("(,)").is_err());
+    assert!(VersionRange::convert("[2.0,1.0]").is_err());
+    assert!(VersionRange::convert("1.0,2.0").is_err());
+}
+
+#[test]
+fn legacy_semver() {
+    assert_eq!(VersionRange::from_str(">= 1.0.0").unwrap(), VersionRange::convert("1.0.0").unwrap());
+    assert_eq!(VersionRange::from_str("> 1.0.0, <= 2.0.0").unwrap(), VersionRange::convert("(1.0.0,2.0.0]").unwrap());
+    assert_eq!(VersionRange::from_str("*").unwrap(), VersionRange::any());
 }
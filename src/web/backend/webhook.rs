@@ -0,0 +1,271 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::Duration;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha2::Sha256;
+use diesel::pg::PgConnection;
+use hyper::Client;
+use hyper::client::RedirectPolicy;
+use hyper::header::ContentType;
+use r2d2::Pool;
+use r2d2_diesel::ConnectionManager;
+use rustc_serialize::hex::ToHex;
+use url::{Url, Host};
+
+use std::cmp;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::thread;
+use std::time;
+
+use super::db::WebhookDelivery;
+use ::utils::CONFIG;
+#[cfg(test)]
+use super::db::WebhookEvent;
+
+header! { (XHazelEvent, "X-Hazel-Event") => [String] }
+header! { (XHazelDelivery, "X-Hazel-Delivery") => [String] }
+header! { (XHazelSignature, "X-Hazel-Signature") => [String] }
+
+const MAX_ATTEMPTS: i32 = 8;
+const BATCH_SIZE: i64 = 20;
+
+/// Value of the X-Hazel-Signature header, receivers compare it with the hmac of the raw body
+pub fn sign(secret: &str, body: &str) -> String
+{
+    let mut hmac = Hmac::new(Sha256::new(), secret.as_bytes());
+    hmac.input(body.as_bytes());
+    format!("sha256={}", hmac.result().code().to_hex())
+}
+
+/// 30 seconds after the first failure, doubled after every further one, at most an hour
+fn backoff(failed_attempts: i32) -> Duration
+{
+    Duration::seconds(cmp::min(30i64 << cmp::min(failed_attempts, 10), 3600))
+}
+
+/// Loopback, link-local, private, shared and multicast addresses
+fn is_internal(address: &IpAddr) -> bool
+{
+    match *address {
+        IpAddr::V4(ref address) => {
+            let octets = address.octets();
+            octets[0] == 0 || octets[0] == 10 || octets[0] == 127 || octets[0] >= 224 ||
+                (octets[0] == 100 && octets[1] & 0xc0 == 64) ||
+                (octets[0] == 169 && octets[1] == 254) ||
+                (octets[0] == 172 && octets[1] & 0xf0 == 16) ||
+                (octets[0] == 192 && octets[1] == 168)
+        },
+        IpAddr::V6(ref address) => match address.to_ipv4() {
+            //mapped and compatible addresses, including :: and ::1
+            Some(mapped) => is_internal(&IpAddr::V4(mapped)),
+            None => {
+                let first = address.segments()[0];
+                first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80 || first & 0xff00 == 0xff00
+            },
+        },
+    }
+}
+
+/// Webhooks of maintainers may not reach the server itself or internal networks,
+/// unless their host is one of the `allowed_hosts` of the administrator
+pub fn check_target(url: &str, allowed_hosts: &[String]) -> Result<(), String>
+{
+    let url = try!(Url::parse(url).map_err(|err| format!("{}", err)));
+    let host = match url.host_str() {
+        Some(host) => host.to_lowercase(),
+        None => return Err(String::from("The url has no host")),
+    };
+    if allowed_hosts.iter().any(|allowed| allowed.to_lowercase() == host) {
+        return Ok(());
+    }
+    let addresses = match url.host() {
+        Some(Host::Ipv4(address)) => vec![IpAddr::V4(address)],
+        Some(Host::Ipv6(address)) => vec![IpAddr::V6(address)],
+        Some(Host::Domain(domain)) => match (domain, url.port_or_known_default().unwrap_or(80)).to_socket_addrs() {
+            Ok(addresses) => addresses.map(|address| address.ip()).collect(),
+            Err(err) => return Err(format!("Unable to resolve {}: {}", domain, err)),
+        },
+        None => Vec::new(),
+    };
+    if addresses.iter().any(is_internal) {
+        Err(format!("{} is an internal address, an administrator has to allow it", host))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the response status and an error, if the receiver did not accept the delivery.
+/// Targets of maintainers are checked again on every attempt, their dns records may have changed.
+fn deliver(client: &Client, delivery: &WebhookDelivery, allowed_hosts: &[String]) -> (Option<i32>, Option<String>)
+{
+    if delivery.webhook_id().is_some() {
+        if let Err(err) = check_target(delivery.url(), allowed_hosts) {
+            return (None, Some(err));
+        }
+    }
+
+    let mut request = client.post(delivery.url())
+                            .header(ContentType::json())
+                            .header(XHazelEvent(String::from(delivery.event())))
+                            .header(XHazelDelivery(format!("{}", delivery.id())))
+                            .body(delivery.payload());
+    if let Some(signature) = delivery.signature() {
+        request = request.header(XHazelSignature(signature.clone()));
+    }
+
+    match request.send() {
+        Ok(response) => {
+            let status = response.status.to_u16() as i32;
+            if response.status.is_success() {
+                (Some(status), None)
+            } else {
+                (Some(status), Some(format!("Receiver answered with {}", response.status)))
+            }
+        },
+        Err(err) => (None, Some(format!("{}", err))),
+    }
+}
+
+/// Sends due deliveries on a background thread, failed ones are retried with backoff
+pub fn schedule(pool: Pool<ConnectionManager<PgConnection>>)
+{
+    thread::spawn(move || {
+        let mut client = Client::new();
+        //a redirect could lead to a target the url was not checked for
+        client.set_redirect_policy(RedirectPolicy::FollowNone);
+        client.set_read_timeout(Some(time::Duration::from_secs(10)));
+        client.set_write_timeout(Some(time::Duration::from_secs(10)));
+
+        loop {
+            match pool.get() {
+                Ok(connection) => match WebhookDelivery::due(&*connection, BATCH_SIZE) {
+                    Ok(deliveries) => for mut delivery in deliveries {
+                        let (status, error) = deliver(&client, &delivery, &CONFIG.backend.webhook_allowed_hosts);
+                        let failed_attempts = delivery.attempts();
+                        let retry_in = if failed_attempts + 1 >= MAX_ATTEMPTS { None } else { Some(backoff(failed_attempts)) };
+                        match error {
+                            Some(ref error) => warn!(target: "hazel::webhook", "Delivery {} to {} failed: {}", delivery.id(), delivery.url(), error),
+                            None => debug!(target: "hazel::webhook", "Delivered {} to {}", delivery.id(), delivery.url()),
+                        }
+                        if let Err(err) = delivery.attempted(&*connection, status, error, retry_in) {
+                            error!(target: "hazel::webhook", "{:?}", err);
+                        }
+                    },
+                    Err(err) => error!(target: "hazel::webhook", "{:?}", err),
+                },
+                Err(err) => error!("{:?}", err),
+            }
+            thread::sleep(time::Duration::from_secs(5));
+        }
+    });
+}
+
+/// Accepts a single request and answers with `status`, returns the request as received
+#[cfg(test)]
+fn fake_receiver(status: &'static str) -> (String, thread::JoinHandle<String>)
+{
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.to_lowercase().starts_with("content-length:") {
+                length = line[15..].trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+        write!(reader.get_mut(), "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+        request
+    });
+    (url, handle)
+}
+
+#[test]
+fn webhook_signature() {
+    assert_eq!(sign("shared secret", r#"{"event":"push","package_id":"test"}"#),
+               "sha256=12cf1c5a7182d42f64373fcef0908e9921dab8d1129b08699819e39f3a1389e1");
+}
+
+#[test]
+fn webhook_deliver() {
+    let payload = r#"{"event":"push","package_id":"test"}"#;
+    let (url, receiver) = fake_receiver("200 OK");
+    let delivery = WebhookDelivery::queued(7, WebhookEvent::Push, &url, payload, Some("shared secret"));
+    assert_eq!(deliver(&Client::new(), &delivery, &[]), (Some(200), None));
+
+    let request = receiver.join().unwrap();
+    assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+    assert!(request.contains("Content-Type: application/json\r\n"));
+    assert!(request.contains("X-Hazel-Event: push\r\n"));
+    assert!(request.contains("X-Hazel-Delivery: 7\r\n"));
+    assert!(request.contains("X-Hazel-Signature: sha256=12cf1c5a7182d42f64373fcef0908e9921dab8d1129b08699819e39f3a1389e1\r\n"));
+    assert!(request.ends_with(&format!("\r\n\r\n{}", payload)));
+}
+
+#[test]
+fn webhook_deliver_failures() {
+    let (url, receiver) = fake_receiver("500 Internal Server Error");
+    let delivery = WebhookDelivery::queued(8, WebhookEvent::Delete, &url, "{}", None);
+    let (status, error) = deliver(&Client::new(), &delivery, &[]);
+    assert_eq!(status, Some(500));
+    assert!(error.is_some());
+    assert!(!receiver.join().unwrap().contains("X-Hazel-Signature"));
+
+    //nothing listens on the port any more
+    let (status, error) = deliver(&Client::new(), &delivery, &[]);
+    assert_eq!(status, None);
+    assert!(error.is_some());
+}
+
+#[test]
+fn webhook_backoff() {
+    assert_eq!(backoff(0), Duration::seconds(30));
+    assert_eq!(backoff(1), Duration::seconds(60));
+    assert_eq!(backoff(6), Duration::seconds(1920));
+    assert_eq!(backoff(7), Duration::seconds(3600));
+    assert_eq!(backoff(40), Duration::seconds(3600));
+}
+
+#[test]
+fn webhook_targets() {
+    for url in &["http://127.0.0.1:8080/", "http://169.254.169.254/latest/meta-data/", "http://10.1.2.3/", "http://172.16.0.1/",
+                 "http://192.168.1.1/", "http://100.64.0.1/", "http://0.0.0.0/", "http://[::1]/", "http://[::]/", "http://[fe80::1]/",
+                 "http://[fd00::1]/", "http://[::ffff:169.254.169.254]/", "http://localhost:8000/"] {
+        assert!(check_target(url, &[]).is_err(), "{}", url);
+    }
+    for url in &["https://93.184.216.34/hook", "http://172.32.0.1/", "http://[2606:2800:220:1::]/"] {
+        assert!(check_target(url, &[]).is_ok(), "{}", url);
+    }
+    let allowed = vec![String::from("LOCALHOST"), String::from("10.1.2.3")];
+    assert!(check_target("http://localhost:8000/", &allowed).is_ok());
+    assert!(check_target("http://10.1.2.3/", &allowed).is_ok());
+    assert!(check_target("http://10.1.2.4/", &allowed).is_err());
+}
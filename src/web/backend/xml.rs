@@ -18,8 +18,8 @@ use treexml::Element;
 use diesel::prelude::*;
 use diesel::pg::Pg;
 
-use super::db::{Package, PackageVersion,Dependency};
-use super::version::NugetToSemver;
+use super::db::{Package, PackageVersion, PackageAsset, Dependency, Deprecation, Advisory};
+use super::assets::AssetKind;
 use ::utils::error::{BackendResult, XmlError};
 
 pub trait FromNugetXml
@@ -37,6 +37,39 @@ pub trait ToNugetFeedXml
     fn xml_entry<C: Connection<Backend=Pg>>(&self, base_url: &str, connection: &C) -> BackendResult<Element>;
 }
 
+const NUSPEC_NAMESPACE: &'static str = "http://schemas.microsoft.com/packaging/2010/07/nuspec.xsd";
+
+/// Metadata that cannot be edited and is stored in a lossy way (e.g. dependency groups),
+/// so the uploaded values are kept
+const PRESERVED_METADATA: &'static [&'static str] = &["id", "version", "authors", "tags", "dependencies"];
+
+/// Applies the metadata of `description` (see `ToNugetXml`) to an uploaded nuspec,
+/// keeping everything hazel does not know about, like `files` or `frameworkAssemblies`.
+pub fn merge_nuspec(nuspec: &mut Element, description: &Element)
+{
+    if !nuspec.attributes.contains_key("xmlns") {
+        nuspec.attributes.insert(String::from("xmlns"), String::from(NUSPEC_NAMESPACE));
+    }
+    let updated = match description.find_child(|tag| tag.name == "metadata") {
+        Some(metadata) => metadata,
+        None => return,
+    };
+    if nuspec.find_child(|tag| tag.name == "metadata").is_none() {
+        nuspec.children.insert(0, Element::new("metadata"));
+    }
+    let metadata = nuspec.find_child_mut(|tag| tag.name == "metadata").unwrap();
+
+    for tag in updated.children.iter().filter(|tag| !PRESERVED_METADATA.contains(&&*tag.name)) {
+        match tag.text {
+            Some(ref text) if !text.is_empty() => match metadata.children.iter().position(|child| child.name == tag.name) {
+                Some(index) => metadata.children[index].text = Some(text.clone()),
+                None => metadata.children.push(tag.clone()),
+            },
+            _ => metadata.children.retain(|child| child.name != tag.name),
+        }
+    }
+}
+
 impl FromNugetXml for PackageVersion
 {
     fn set_from_xml(&mut self, package: &Element) -> Result<(), XmlError>
@@ -84,7 +117,7 @@ impl ToNugetXml for PackageVersion
         metadata.children.push(id);
 
         let mut version = Element::new("version");
-        version.text = Some(format!("{}", self.version()));
+        version.text = Some(String::from(self.original_version()));
         metadata.children.push(version);
 
         let mut title = Element::new("title");
@@ -178,7 +211,7 @@ impl ToNugetXml for Dependency
         let mut root = Element::new("dependency");
         root.attributes.insert(String::from("id"), String::from(try!(self.requirement(connection)).id()));
 
-        match try!(self.version_req().to_nuget()) {
+        match self.version_req().to_nuget() {
             Some(x) => { root.attributes.insert(String::from("version"), x); },
             None => {},
         }
@@ -275,7 +308,7 @@ impl ToNugetFeedXml for PackageVersion
             {
                 dependencies_string.push_str(&format!("{}:{}:|",
                     try!(dependency.requirement(connection)).id(),
-                    match try!(dependency.version_req().to_nuget()) {
+                    match dependency.version_req().to_nuget() {
                         Some(x) => { x },
                         None => { String::new() },
                     }
@@ -306,8 +339,12 @@ impl ToNugetFeedXml for PackageVersion
             }
             properties.children.push(report_abuse_url);
 
+            //stored icons are served by hazel, clients may not reach the original url
             let mut icon_url = Element::new("d:IconUrl");
-            icon_url.text = self.icon_url.clone();
+            icon_url.text = match try!(PackageAsset::get(connection, self, AssetKind::Icon)) {
+                Some(ref icon) if icon.content().is_some() => Some(format!("{}/packages/{}/{}/icon", base_url, self.id(), self.version())),
+                _ => self.icon_url.clone(),
+            };
             if icon_url.text.is_none() {
                 icon_url.attributes.insert(String::from("m:null"), String::from("true"));
             }
@@ -317,8 +354,8 @@ impl ToNugetFeedXml for PackageVersion
             is_latest_version.attributes.insert(String::from("m:type"), String::from("Edm.Boolean"));
             is_latest_version.text = Some(String::from(
                 match versions.iter()
-                    .filter(|pkgver| !pkgver.version().is_prerelease())
-                    .max_by_key(|pkgver| pkgver.version()).unwrap() == self {
+                    .filter(|pkgver| !pkgver.version().is_prerelease() && !pkgver.is_quarantined())
+                    .max_by_key(|pkgver| pkgver.version()) == Some(self) {
                         true => "true",
                         false => "false",
                 }
@@ -329,7 +366,8 @@ impl ToNugetFeedXml for PackageVersion
             is_absolute_latest_version.attributes.insert(String::from("m:type"), String::from("Edm.Boolean"));
             is_absolute_latest_version.text = Some(String::from(
                 match versions.iter()
-                    .max_by_key(|pkgver| pkgver.version()).unwrap() == self {
+                    .filter(|pkgver| !pkgver.is_quarantined())
+                    .max_by_key(|pkgver| pkgver.version()) == Some(self) {
                         true => "true",
                         false => "false",
                 }
@@ -428,9 +466,108 @@ impl ToNugetFeedXml for PackageVersion
             }
             properties.children.push(bug_tracker_url);
 
+            let mut is_signed = Element::new("d:IsSigned");
+            is_signed.attributes.insert(String::from("m:type"), String::from("Edm.Boolean"));
+            is_signed.text = Some(format!("{}", self.is_signed()));
+            properties.children.push(is_signed);
+
+            let mut signature_type = Element::new("d:SignatureType");
+            signature_type.text = self.signature_type().cloned();
+            if signature_type.text.is_none() {
+                signature_type.attributes.insert(String::from("m:null"), String::from("true"));
+            }
+            properties.children.push(signature_type);
+
+            let deprecation = try!(Deprecation::get(connection, self));
+
+            let mut is_deprecated = Element::new("d:IsDeprecated");
+            is_deprecated.attributes.insert(String::from("m:type"), String::from("Edm.Boolean"));
+            is_deprecated.text = Some(format!("{}", deprecation.is_some()));
+            properties.children.push(is_deprecated);
+
+            let mut deprecation_reasons = Element::new("d:DeprecationReasons");
+            deprecation_reasons.text = deprecation.as_ref().map(|deprecation| deprecation.reasons().iter().map(|reason| reason.as_str()).collect::<Vec<_>>().join(","));
+            if deprecation_reasons.text.is_none() {
+                deprecation_reasons.attributes.insert(String::from("m:null"), String::from("true"));
+            }
+            properties.children.push(deprecation_reasons);
+
+            let mut deprecation_message = Element::new("d:DeprecationMessage");
+            deprecation_message.text = deprecation.as_ref().and_then(|deprecation| deprecation.message().cloned());
+            if deprecation_message.text.is_none() {
+                deprecation_message.attributes.insert(String::from("m:null"), String::from("true"));
+            }
+            properties.children.push(deprecation_message);
+
+            let mut alternate_package_id = Element::new("d:AlternatePackageId");
+            alternate_package_id.text = deprecation.as_ref().and_then(|deprecation| deprecation.alternate_id().cloned());
+            if alternate_package_id.text.is_none() {
+                alternate_package_id.attributes.insert(String::from("m:null"), String::from("true"));
+            }
+            properties.children.push(alternate_package_id);
+
+            let mut alternate_package_range = Element::new("d:AlternatePackageVersionRange");
+            alternate_package_range.text = deprecation.as_ref().and_then(|deprecation| deprecation.alternate_range()).map(|range| format!("{}", range));
+            if alternate_package_range.text.is_none() {
+                alternate_package_range.attributes.insert(String::from("m:null"), String::from("true"));
+            }
+            properties.children.push(alternate_package_range);
+
+            //"<severity>:<advisory url>|..." with NuGet's severities from 0 (low) to 3 (critical)
+            let advisories = try!(Advisory::for_version(connection, self));
+
+            let mut vulnerability_severity = Element::new("d:VulnerabilitySeverity");
+            vulnerability_severity.attributes.insert(String::from("m:type"), String::from("Edm.Int32"));
+            vulnerability_severity.text = advisories.iter().map(|advisory| advisory.severity().level()).max().map(|level| format!("{}", level));
+            if vulnerability_severity.text.is_none() {
+                vulnerability_severity.attributes.insert(String::from("m:null"), String::from("true"));
+            }
+            properties.children.push(vulnerability_severity);
+
+            let mut vulnerabilities = Element::new("d:Vulnerabilities");
+            if advisories.is_empty() {
+                vulnerabilities.attributes.insert(String::from("m:null"), String::from("true"));
+            } else {
+                vulnerabilities.text = Some(advisories.iter().map(|advisory| format!("{}:{}", advisory.severity().level(), advisory.url())).collect::<Vec<_>>().join("|"));
+            }
+            properties.children.push(vulnerabilities);
+
             root.children.push(properties);
         }
 
         Ok(root)
     }
 }
+
+#[test]
+fn merge_nuspec_metadata() {
+    use treexml::Document;
+
+    let parse = |xml: &str| Document::parse(xml.as_bytes()).unwrap().root.unwrap();
+    let render = |nuspec: &Element| format!("{}", Document { root: Some(nuspec.clone()), .. Document::default() });
+
+    let mut nuspec = parse(r#"<package><metadata><id>Contoso.Core</id><version>1.0</version><title>Old</title><summary>Removed</summary><dependencies><group targetFramework="net45"/></dependencies></metadata><files><file src="tools\**"/></files></package>"#);
+    let description = parse(r#"<package><metadata><id>contoso.core</id><version>1.0.0</version><title>New</title><summary></summary><projectUrl>https://contoso.example.org</projectUrl><dependencies/></metadata></package>"#);
+    merge_nuspec(&mut nuspec, &description);
+
+    assert_eq!(nuspec.attributes.get("xmlns").map(|xmlns| &**xmlns), Some(NUSPEC_NAMESPACE));
+    let metadata = nuspec.find_child(|tag| tag.name == "metadata").unwrap();
+    let text = |name: &str| metadata.find_child(|tag| tag.name == name).and_then(|tag| tag.text.clone());
+    assert_eq!(text("id"), Some(String::from("Contoso.Core")));
+    assert_eq!(text("version"), Some(String::from("1.0")));
+    assert_eq!(text("title"), Some(String::from("New")));
+    assert_eq!(text("summary"), None);
+    assert!(metadata.find_child(|tag| tag.name == "summary").is_none());
+    assert_eq!(text("projectUrl"), Some(String::from("https://contoso.example.org")));
+    assert_eq!(metadata.find_child(|tag| tag.name == "dependencies").unwrap().children.len(), 1);
+    assert!(nuspec.find_child(|tag| tag.name == "files").is_some());
+
+    //merging again changes nothing, so the package is not rewritten
+    let merged = render(&nuspec);
+    merge_nuspec(&mut nuspec, &description);
+    assert_eq!(render(&nuspec), merged);
+
+    let mut empty = parse("<package/>");
+    merge_nuspec(&mut empty, &description);
+    assert_eq!(empty.find_child(|tag| tag.name == "metadata").unwrap().children.len(), 2);
+}
@@ -0,0 +1,162 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Handler, IronResult, Request, Response, Protocol};
+use iron::typemap::Key;
+use hyper;
+use hyper::net::{NetworkListener, NetworkStream};
+use hyper::server::{Server, Listening};
+use hyper::server::request::Request as HttpRequest;
+use hyper::server::response::Response as HttpResponse;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, Shutdown};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+//unix sockets have no peer address, requests appear to come from localhost
+fn localhost() -> SocketAddr
+{
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0)
+}
+
+/// Marks requests received on the unix socket, their peer is always a trusted reverse proxy
+pub struct UnixSocketPeer;
+impl Key for UnixSocketPeer { type Value = (); }
+
+/// Lets several listeners serve the same middleware chain
+pub struct SharedHandler<H: Handler>(pub Arc<H>);
+
+impl<H: Handler> Clone for SharedHandler<H>
+{
+    fn clone(&self) -> Self
+    {
+        SharedHandler(self.0.clone())
+    }
+}
+
+impl<H: Handler> Handler for SharedHandler<H>
+{
+    fn handle(&self, req: &mut Request) -> IronResult<Response>
+    {
+        self.0.handle(req)
+    }
+}
+
+impl<H: Handler> hyper::server::Handler for SharedHandler<H>
+{
+    fn handle<'a, 'k>(&'a self, http_req: HttpRequest<'a, 'k>, http_res: HttpResponse<'a>)
+    {
+        match Request::from_http(http_req, localhost(), &Protocol::Http) {
+            Ok(mut req) => {
+                req.extensions.insert::<UnixSocketPeer>(());
+                let response = match self.0.handle(&mut req) {
+                    Ok(response) => response,
+                    Err(err) => {
+                        error!("Error handling request on unix socket: {}", err.error);
+                        err.response
+                    },
+                };
+                let _ = response.write_back(http_res);
+            },
+            Err(err) => error!("Error creating request on unix socket: {}", err),
+        }
+    }
+}
+
+pub struct UnixHttpStream(UnixStream);
+
+impl Clone for UnixHttpStream
+{
+    fn clone(&self) -> Self
+    {
+        UnixHttpStream(self.0.try_clone().unwrap())
+    }
+}
+
+impl Read for UnixHttpStream
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        self.0.read(buf)
+    }
+}
+
+impl Write for UnixHttpStream
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+    {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()>
+    {
+        self.0.flush()
+    }
+}
+
+impl NetworkStream for UnixHttpStream
+{
+    fn peer_addr(&mut self) -> io::Result<SocketAddr>
+    {
+        Ok(localhost())
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>
+    {
+        self.0.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()>
+    {
+        self.0.set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()>
+    {
+        self.0.shutdown(how)
+    }
+}
+
+#[derive(Clone)]
+pub struct UnixHttpListener(Arc<UnixListener>);
+
+impl NetworkListener for UnixHttpListener
+{
+    type Stream = UnixHttpStream;
+
+    fn accept(&mut self) -> hyper::Result<UnixHttpStream>
+    {
+        Ok(UnixHttpStream(try!(self.0.accept()).0))
+    }
+
+    fn local_addr(&mut self) -> io::Result<SocketAddr>
+    {
+        Ok(localhost())
+    }
+}
+
+/// Serves the handler on a unix domain socket, replacing a stale socket file
+pub fn listen<H: Handler>(path: &Path, handler: SharedHandler<H>) -> hyper::Result<Listening>
+{
+    if path.exists() {
+        try!(fs::remove_file(path));
+    }
+    let listener = UnixHttpListener(Arc::new(try!(UnixListener::bind(path))));
+    Server::new(listener).handle(handler)
+}
@@ -16,3 +16,4 @@
 pub mod server;
 pub mod backend;
 pub mod views;
+pub mod listener;
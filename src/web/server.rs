@@ -30,20 +30,28 @@ use diesel::pg::PgConnection;
 use r2d2_diesel::ConnectionManager;
 
 use super::backend::Storage;
-use utils::middleware::Logger;
+use super::listener::{self, SharedHandler};
+use super::backend::oidc::PendingLogin;
+use utils::middleware::AccessLogger;
 use utils::middleware::PathNormalizer;
-use utils::middleware::{SessionManager, SessionInfo};
+use utils::middleware::ProxyHeaders;
+use utils::middleware::{SessionManager, SessionInfo, PendingSecondFactor};
+use utils::throttle::Throttle;
+use utils::metrics::{Metrics, MetricsRecorder};
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use ::utils::CONFIG;
+use ::utils::config::ListenerConfig;
 
 use super::views::api::index::index;
 use super::views::api::metadata::metadata;
 use super::views::api::packages::packages;
 use super::views::api::download::download;
 use super::views::api::upload::upload;
+use super::views::api::upload::symbol_upload;
 use super::views::api::delete::delete;
 use super::views::api::package::package;
 use super::views::api::search::search;
@@ -51,6 +59,7 @@ use super::views::api::packagesbyid::packagesbyid;
 use super::views::api::updates::updates;
 use super::views::api::complete_ids::complete_ids;
 use super::views::api::complete_ver::complete_ver;
+use super::views::api::diff::diff;
 
 use super::views::interface::index::index as interface_index;
 use super::views::interface::user::user as interface_user;
@@ -66,6 +75,32 @@ use super::views::interface::pkgver_update::pkgver_update as interface_pkgver_up
 use super::views::interface::transfer::transfer as interface_transfer;
 use super::views::interface::mail_confirmation::mail_confirmation as interface_mail_confirmation;
 use super::views::interface::mail_resend::mail_resend as interface_mail_resend;
+use super::views::interface::oidc::oidc_login as interface_oidc_login;
+use super::views::interface::oidc::oidc_callback as interface_oidc_callback;
+use super::views::interface::audit::audit as interface_audit;
+use super::views::interface::totp::totp_login as interface_totp_login;
+use super::views::interface::totp::totp_enrol_page as interface_totp_enrol_page;
+use super::views::interface::totp::totp_enrol as interface_totp_enrol;
+use super::views::interface::totp::totp_disable as interface_totp_disable;
+use super::views::interface::audit::audit_export as interface_audit_export;
+use super::views::interface::storage::storage as interface_storage;
+use super::views::interface::webhooks::webhooks as interface_webhooks;
+use super::views::interface::webhooks::webhook_create as interface_webhook_create;
+use super::views::interface::webhooks::webhook_delete as interface_webhook_delete;
+use super::views::interface::contents::contents as interface_contents;
+use super::views::interface::contents::content_download as interface_content_download;
+use super::views::interface::diff::diff as interface_diff;
+use super::views::interface::assets::icon as interface_icon;
+use super::views::interface::assets::readme as interface_readme;
+use super::views::interface::assets::license as interface_license;
+use super::views::interface::symbols::symbol as interface_symbol;
+use super::views::interface::deprecation::deprecate as interface_deprecate;
+use super::views::interface::deprecation::undeprecate as interface_undeprecate;
+use super::views::interface::deprecation::advisory_create as interface_advisory_create;
+use super::views::interface::deprecation::advisory_delete as interface_advisory_delete;
+use super::views::interface::metrics::metrics as interface_metrics;
+use super::views::interface::health::healthz as interface_healthz;
+use super::views::interface::health::readyz as interface_readyz;
 
 #[derive(Copy, Clone)]
 pub struct ConnectionPoolKey;
@@ -79,7 +114,23 @@ impl Key for StorageKey { type Value = Storage; }
 pub struct SessionStoreKey;
 impl Key for SessionStoreKey { type Value = HashMap<String, SessionInfo>; }
 
-pub fn start(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage) -> Listening {
+#[derive(Copy, Clone)]
+pub struct TwoFactorStoreKey;
+impl Key for TwoFactorStoreKey { type Value = HashMap<String, PendingSecondFactor>; }
+
+#[derive(Copy, Clone)]
+pub struct ThrottleKey;
+impl Key for ThrottleKey { type Value = Throttle; }
+
+#[derive(Copy, Clone)]
+pub struct MetricsKey;
+impl Key for MetricsKey { type Value = Metrics; }
+
+#[derive(Copy, Clone)]
+pub struct OIDCStateStoreKey;
+impl Key for OIDCStateStoreKey { type Value = HashMap<String, PendingLogin>; }
+
+pub fn start(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage) -> Vec<Listening> {
     let mut mount = Mount::new();
 
     // home
@@ -116,20 +167,52 @@ pub fn start(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage) -> L
         interface.get("/user", interface_user);
         interface.get("/user/edit", interface_user);
         interface.post("/user/edit", interface_user_update);
+        interface.get("/user/totp", interface_totp_enrol_page);
+        interface.post("/user/totp", interface_totp_enrol);
+        interface.post("/user/totp/disable", interface_totp_disable);
         interface.post("/register", interface_register);
         interface.post("/login", interface_login);
+        interface.post("/login/totp", interface_totp_login);
         interface.get("/logout", interface_logout);
         interface.post("/apikey/reset", interface_apikey);
         interface.post("/apikey/revoke", interface_apikey);
         interface.get("/packages/:id", interface_package_newestver);
         interface.get("/packages/:id/:version", interface_package);
         interface.get("/packages/:id/:version/edit", interface_package);
+        interface.get("/packages/:id/:version/contents", interface_contents);
+        interface.get("/packages/:id/:version/contents/download", interface_content_download);
+        interface.get("/packages/:id/:version/diff/:base", interface_diff);
+        interface.get("/packages/:id/:version/icon", interface_icon);
+        interface.get("/packages/:id/:version/readme", interface_readme);
+        interface.get("/packages/:id/:version/license", interface_license);
+        interface.get("/symbols/:file/:signature/:name", interface_symbol);
         interface.post("/packages/:id/edit", interface_pkg_update);
         interface.post("/packages/:id/:version/edit", interface_pkgver_update);
+        interface.post("/packages/:id/:version/deprecate", interface_deprecate);
+        interface.post("/packages/:id/:version/undeprecate", interface_undeprecate);
+        interface.post("/packages/:id/:version/advisories", interface_advisory_create);
+        interface.post("/packages/:id/:version/advisories/delete", interface_advisory_delete);
         interface.get("/packages/transfer/:id/:new_maintainer", interface_transfer);
-        if CONFIG.auth.mail.is_some() {
-            interface.post("/mail_confirmation/resend", interface_mail_resend);
-            interface.get("/mail_confirmation/:key", interface_mail_confirmation);
+        interface.get("/packages/:id/webhooks", interface_webhooks);
+        interface.post("/packages/:id/webhooks", interface_webhook_create);
+        interface.post("/packages/:id/webhooks/:webhook/delete", interface_webhook_delete);
+        interface.get("/admin/audit", interface_audit);
+        interface.get("/admin/audit/export", interface_audit_export);
+        interface.get("/admin/storage", interface_storage);
+        interface.get("/healthz", interface_healthz);
+        interface.get("/readyz", interface_readyz);
+        //mail may be configured by a reload, the handlers fail without it
+        interface.post("/mail_confirmation/resend", interface_mail_resend);
+        interface.get("/mail_confirmation/:key", interface_mail_confirmation);
+        if CONFIG.auth.oidc.is_some() {
+            interface.get("/oidc/login", interface_oidc_login);
+            interface.get("/oidc/callback", interface_oidc_callback);
+        }
+        if CONFIG.server.metrics {
+            if CONFIG.server.metrics_token.is_none() {
+                warn!("Metrics are enabled without a metrics_token, /metrics is readable by anyone");
+            }
+            interface.get("/metrics", interface_metrics);
         }
 
         mount.mount("/css/", Static::new(PathBuf::from(CONFIG.web.resources.clone()).join("css")));
@@ -186,6 +269,8 @@ pub fn start(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage) -> L
         // add/delete package
         feed.post("package", upload);
         feed.put("package", upload);
+        feed.post("symbolpackage", symbol_upload);
+        feed.put("symbolpackage", symbol_upload);
         feed.delete("package/:id/:version", delete);
         feed.delete("package/:id", delete);
 
@@ -201,6 +286,9 @@ pub fn start(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage) -> L
         feed.get("package-ids", complete_ids);
         feed.get("package-versions/:id", complete_ver);
 
+        // changes between two versions
+        feed.get("package-diff/:id/:base/:version", diff);
+
         //Package(Id=':id',Version=':version')
         feed.get("*", package); //Router does not handle this correctly
 
@@ -209,14 +297,41 @@ pub fn start(pool: Pool<ConnectionManager<PgConnection>>, storage: Storage) -> L
 
     let mut chain = Chain::new(mount);
     chain.link_around(SessionManager);
+    chain.link_around(AccessLogger);
+    chain.link_around(MetricsRecorder);
     chain.link_before(PathNormalizer);
-    chain.link_before(Logger);
+    chain.link_before(ProxyHeaders);
+    chain.link_after(ProxyHeaders);
     chain.link(Read::<ConnectionPoolKey>::both(pool));
     chain.link(Read::<StorageKey>::both(storage));
     chain.link(Write::<SessionStoreKey>::both(HashMap::new()));
+    chain.link(Write::<TwoFactorStoreKey>::both(HashMap::new()));
+    chain.link(Write::<OIDCStateStoreKey>::both(HashMap::new()));
+    chain.link(Write::<ThrottleKey>::both(Throttle::new()));
+    chain.link(Write::<MetricsKey>::both(Metrics::new()));
+
+    let handler = SharedHandler(Arc::new(chain));
+
+    let mut listeners = CONFIG.server.listeners.clone();
+    if listeners.is_empty() && CONFIG.server.unix_socket.is_none() {
+        listeners.push(ListenerConfig {
+            address: String::from("0.0.0.0"),
+            port: CONFIG.server.port,
+            https: CONFIG.server.https.clone(),
+        });
+    }
 
-    match CONFIG.server.https.clone() {
-        Some(config) => Iron::new(chain).https(("0.0.0.0", CONFIG.server.port), PathBuf::from(config.certificate), PathBuf::from(config.key)),
-        None => Iron::new(chain).http(("0.0.0.0", CONFIG.server.port)),
-    }.unwrap()
+    let mut listening = Vec::new();
+    for config in listeners {
+        let iron = Iron::new(handler.clone());
+        let address = (&*config.address, config.port);
+        listening.push(match config.https {
+            Some(https) => iron.https(address, PathBuf::from(https.certificate), PathBuf::from(https.key)),
+            None => iron.http(address),
+        }.expect(&format!("Unable to listen on {} port {}", config.address, config.port)));
+    }
+    if let Some(ref path) = CONFIG.server.unix_socket {
+        listening.push(listener::listen(Path::new(path), handler.clone()).expect(&format!("Unable to listen on {}", path)));
+    }
+    listening
 }
@@ -56,7 +56,9 @@ pub fn complete_ids(req: &mut Request) -> IronResult<Response> {
         Ok(packages) => packages.into_iter().filter(|pkg| {
                             pkg.id().starts_with(partial_id)
                         }).map(|pkg| pkg.versions(&*connection).unwrap().into_iter().filter(|pkgver| {
-                            if !include_prerelease {
+                            if pkgver.is_quarantined() {
+                                false
+                            } else if !include_prerelease {
                                 !pkgver.version().is_prerelease()
                             } else { true }
                         }).take(1).next())
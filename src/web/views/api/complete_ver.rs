@@ -63,7 +63,7 @@ pub fn complete_ver(req: &mut Request) -> IronResult<Response> {
     };
 
     let mut answer = String::from("[");
-    for (i, pkg) in packages.into_iter().filter(|pkgver| if !include_prerelease {
+    for (i, pkg) in packages.into_iter().filter(|pkgver| !pkgver.is_quarantined()).filter(|pkgver| if !include_prerelease {
         !pkgver.version().is_prerelease()
     } else { true }).take(30).enumerate() {
         if i != 0 {
@@ -15,12 +15,14 @@
 
 use iron::{Request, Response, IronResult};
 use iron::status;
-use persistent::Read;
+use persistent::{Read, Write};
 use router::Router;
-use semver::Version;
+use ::web::backend::version::NugetVersion;
+use rustc_serialize::json::ToJson;
 use ::utils::error::BackendError;
-use ::web::server::{ConnectionPoolKey, StorageKey};
-use ::web::backend::db::{Package, PackageVersion, User};
+use ::web::server::{ConnectionPoolKey, StorageKey, ThrottleKey, MetricsKey};
+use ::utils::throttle::{ThrottleTarget, too_many_requests};
+use ::web::backend::db::{Package, PackageVersion, User, AuditEvent, AuditAction, WebhookDelivery, WebhookEvent};
 
 header! { (XNugetApiKey, "X-NuGet-ApiKey") => [String] }
 
@@ -43,11 +45,17 @@ pub fn delete(req: &mut Request) -> IronResult<Response> {
         }
     };
 
+    let address = req.remote_addr.ip();
+    let throttle_mutex = req.extensions.get::<Write<ThrottleKey>>().unwrap().clone();
+    if let Some(retry_after) = throttle_mutex.lock().unwrap().check(&ThrottleTarget::ApiKeyAddress(address)) {
+        return Ok(too_many_requests(retry_after));
+    }
+
     match User::get_by_apikey(&*connection, &apikey) {
         Ok(user) => {
             match req.extensions.get::<Router>().unwrap().find("version") {
                 Some(version) => {
-                    let pkgver = match PackageVersion::get(&*connection, id, &match Version::parse(version) {
+                    let pkgver = match PackageVersion::get(&*connection, id, &match NugetVersion::parse(version) {
                         Ok(ver) => ver,
                         Err(_) => return Ok(Response::with((status::UnprocessableEntity, "Version value invalid"))),
                     }) {
@@ -76,7 +84,13 @@ pub fn delete(req: &mut Request) -> IronResult<Response> {
 
                     if user.is_admin() || maintainer == user {
                         match pkgver.delete(&*connection, storage) {
-                            Ok(()) => Ok(Response::with(status::Ok)),
+                            Ok(()) => {
+                                AuditEvent::record(&*connection, &user, AuditAction::Delete, address, Some(pkgver.id()), Some(&*format!("{}", pkgver.version())), Some(pkgver.to_json()), None);
+                                if let Err(err) = WebhookDelivery::enqueue(&*connection, WebhookEvent::Delete, &user, pkgver.id(), Some(&*format!("{}", pkgver.version())), pkgver.to_json()) {
+                                    error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                                }
+                                Ok(Response::with(status::Ok))
+                            },
                             Err(err) => {
                                 error!("{:?}", err);
                                 Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
@@ -110,7 +124,12 @@ pub fn delete(req: &mut Request) -> IronResult<Response> {
                             Ok(versions) => {
                                 for version in versions {
                                     match version.delete(&*connection, storage) {
-                                        Ok(()) => {},
+                                        Ok(()) => {
+                                            AuditEvent::record(&*connection, &user, AuditAction::Delete, address, Some(version.id()), Some(&*format!("{}", version.version())), Some(version.to_json()), None);
+                                            if let Err(err) = WebhookDelivery::enqueue(&*connection, WebhookEvent::Delete, &user, version.id(), Some(&*format!("{}", version.version())), version.to_json()) {
+                                                error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                                            }
+                                        },
                                         Err(err) => {
                                             error!("{:?}", err);
                                             return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
@@ -131,6 +150,13 @@ pub fn delete(req: &mut Request) -> IronResult<Response> {
             }
         }
         //TODO better match
-        Err(_) => Ok(Response::with((status::InternalServerError, "No User with matching API-Key found"))),
+        Err(_) => {
+            info!(target: "hazel::security", "Invalid API-Key used from {}", address);
+            req.extensions.get::<Write<MetricsKey>>().unwrap().lock().unwrap().failed_login("apikey");
+            match throttle_mutex.lock().unwrap().failure(ThrottleTarget::ApiKeyAddress(address)) {
+                Some(retry_after) => Ok(too_many_requests(retry_after)),
+                None => Ok(Response::with((status::InternalServerError, "No User with matching API-Key found"))),
+            }
+        },
     }
 }
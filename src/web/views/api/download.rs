@@ -17,7 +17,7 @@ use iron::{Request, Response, IronResult};
 use iron::status;
 use persistent::Read;
 use router::Router;
-use semver::Version;
+use ::web::backend::version::NugetVersion;
 use ::utils::error::BackendError;
 use ::web::server::{ConnectionPoolKey, StorageKey};
 use ::web::backend::db::PackageVersion;
@@ -37,7 +37,7 @@ pub fn download(req: &mut Request) -> IronResult<Response> {
         }
     };
 
-    let pkgver = match PackageVersion::get(&*connection, id, &match Version::parse(version) {
+    let pkgver = match PackageVersion::get(&*connection, id, &match NugetVersion::parse(version) {
         Ok(ver) => ver,
         Err(_) => return Ok(Response::with((status::UnprocessableEntity, "Version value invalid"))),
     }) {
@@ -50,6 +50,10 @@ pub fn download(req: &mut Request) -> IronResult<Response> {
         }
     };
 
+    if pkgver.is_quarantined() {
+        return Ok(Response::with((status::Forbidden, "This version is quarantined until it passes the malware scan")));
+    }
+
     match pkgver.download(storage) {
         Ok(file) => Ok(Response::with((status::Ok, file))),
         Err(err) => {
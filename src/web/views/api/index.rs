@@ -16,16 +16,10 @@
 use iron::{Request, Response, IronResult};
 use iron::status;
 use iron::mime::Mime;
+use ::utils::middleware::base_url;
 
 pub fn index(req: &mut Request) -> IronResult<Response> {
-    let base_url = {
-        let url = &req.url;
-        if (&*url.scheme == "http" && url.port == 80) || (&*url.scheme == "https" && url.port == 443) {
-            format!("{}://{}", url.scheme, url.host)
-        } else {
-            format!("{}://{}:{}", url.scheme, url.host, url.port)
-        }
-    };
+    let base_url = base_url(req);
     Ok(Response::with((status::Ok, format!(
 "<service xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:app=\"http://www.w3.org/2007/app\" xmlns=\"http://www.w3.org/2007/app\" xml:base=\"{}/api/v2/\">
 <workspace>
@@ -61,6 +61,15 @@ pub fn metadata(_: &mut Request) -> IronResult<Response> {
                 <Property Name=\"DocsUrl\" Type=\"Edm.String\" Nullable=\"true\"/>
                 <Property Name=\"MailingListUrl\" Type=\"Edm.String\" Nullable=\"true\"/>
                 <Property Name=\"BugTrackerUrl\" Type=\"Edm.String\" Nullable=\"true\"/>
+                <Property Name=\"IsSigned\" Type=\"Edm.Boolean\" Nullable=\"false\"/>
+                <Property Name=\"SignatureType\" Type=\"Edm.String\" Nullable=\"true\"/>
+                <Property Name=\"IsDeprecated\" Type=\"Edm.Boolean\" Nullable=\"false\"/>
+                <Property Name=\"DeprecationReasons\" Type=\"Edm.String\" Nullable=\"true\"/>
+                <Property Name=\"DeprecationMessage\" Type=\"Edm.String\" Nullable=\"true\"/>
+                <Property Name=\"AlternatePackageId\" Type=\"Edm.String\" Nullable=\"true\"/>
+                <Property Name=\"AlternatePackageVersionRange\" Type=\"Edm.String\" Nullable=\"true\"/>
+                <Property Name=\"VulnerabilitySeverity\" Type=\"Edm.Int32\" Nullable=\"true\"/>
+                <Property Name=\"Vulnerabilities\" Type=\"Edm.String\" Nullable=\"true\"/>
             </EntityType>
             <EntityContainer Name=\"FeedContext_x0060_1\" m:IsDefaultEntityContainer=\"true\">
                 <EntitySet Name=\"Packages\" EntityType=\"NuGetGallery.V2FeedPackage\"/>
@@ -25,3 +25,4 @@ pub mod packagesbyid;
 pub mod updates;
 pub mod complete_ids;
 pub mod complete_ver;
+pub mod diff;
@@ -17,13 +17,14 @@ use iron::{Request, Response, IronResult};
 use iron::status;
 use iron::mime::Mime;
 use persistent::Read;
-use semver::Version;
+use ::web::backend::version::NugetVersion;
 use regex::Regex;
 use treexml::Document;
 use ::web::server::ConnectionPoolKey;
 
 use ::web::backend::db::PackageVersion;
 use ::web::backend::xml::ToNugetFeedXml;
+use ::utils::middleware::base_url;
 
 lazy_static! {
     static ref PKG_DESC: Regex = Regex::with_size_limit(8192, r#"^Packages\(Id='(?P<id>.*)'\s*,Version='(?P<version>.*)'\)$"#).unwrap();
@@ -47,14 +48,7 @@ pub fn package(req: &mut Request) -> IronResult<Response> {
         None => return Ok(Response::with(status::NotFound)),
     };
 
-    let base_url = {
-        let url = &req.url;
-        if (&*url.scheme == "http" && url.port == 80) || (&*url.scheme == "https" && url.port == 443) {
-            format!("{}://{}", url.scheme, url.host)
-        } else {
-            format!("{}://{}:{}", url.scheme, url.host, url.port)
-        }
-    };
+    let base_url = base_url(req);
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
 
     let connection = match connection_pool.get() {
@@ -65,10 +59,11 @@ pub fn package(req: &mut Request) -> IronResult<Response> {
         }
     };
 
-    let package = match PackageVersion::get(&*connection, id, &match Version::parse(version) {
+    let package = match PackageVersion::get(&*connection, id, &match NugetVersion::parse(version) {
         Ok(ver) => ver,
         Err(_) => return Ok(Response::with((status::NotFound, "Version value invalid"))),
     }) {
+        Ok(ref package) if package.is_quarantined() => return Ok(Response::with((status::NotFound, "Package not found"))),
         Ok(package) => package,
         Err(_) => {
             return Ok(Response::with((status::NotFound, "Package not found")));
@@ -22,16 +22,10 @@ use treexml::{Document, Element};
 use ::web::server::ConnectionPoolKey;
 use ::web::backend::db::PackageVersion;
 use ::web::backend::xml::ToNugetFeedXml;
+use ::utils::middleware::base_url;
 
 pub fn packages(req: &mut Request) -> IronResult<Response> {
-    let base_url = {
-        let url = &req.url;
-        if (&*url.scheme == "http" && url.port == 80) || (&*url.scheme == "https" && url.port == 443) {
-            format!("{}://{}", url.scheme, url.host)
-        } else {
-            format!("{}://{}:{}", url.scheme, url.host, url.port)
-        }
-    };
+    let base_url = base_url(req);
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
 
     let connection = match connection_pool.get() {
@@ -44,7 +38,7 @@ pub fn packages(req: &mut Request) -> IronResult<Response> {
 
     //TODO limit packages when we reach a high count and add helper for quicker updated retrieval
     let packages = match PackageVersion::all(&*connection) {
-        Ok(packages) => packages,
+        Ok(packages) => packages.into_iter().filter(|pkgver| !pkgver.is_quarantined()).collect::<Vec<_>>(),
         Err(err) => {
             error!("{:?}", err);
             return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
@@ -24,6 +24,7 @@ use treexml::{Document, Element};
 use ::web::server::ConnectionPoolKey;
 use ::web::backend::db::Package;
 use ::web::backend::xml::ToNugetFeedXml;
+use ::utils::middleware::base_url;
 
 pub fn packagesbyid(req: &mut Request) -> IronResult<Response> {
 
@@ -35,14 +36,7 @@ pub fn packagesbyid(req: &mut Request) -> IronResult<Response> {
         _ => return Ok(Response::with((status::BadRequest, "id is no String"))),
     };
 
-    let base_url = {
-        let url = &req.url;
-        if (&*url.scheme == "http" && url.port == 80) || (&*url.scheme == "https" && url.port == 443) {
-            format!("{}://{}", url.scheme, url.host)
-        } else {
-            format!("{}://{}:{}", url.scheme, url.host, url.port)
-        }
-    };
+    let base_url = base_url(req);
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
 
     let connection = match connection_pool.get() {
@@ -61,7 +55,7 @@ pub fn packagesbyid(req: &mut Request) -> IronResult<Response> {
     };
 
     let packages = match package.versions(&*connection) {
-        Ok(pkgs) => pkgs,
+        Ok(pkgs) => pkgs.into_iter().filter(|pkgver| !pkgver.is_quarantined()).collect::<Vec<_>>(),
         Err(_) => {
             return Ok(Response::with((status::NotFound, "Package not found")));
         }
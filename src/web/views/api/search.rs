@@ -25,19 +25,13 @@ use treexml::{Document, Element};
 use ::web::server::ConnectionPoolKey;
 use ::web::backend::db::{Package, PackageVersion};
 use ::web::backend::xml::ToNugetFeedXml;
+use ::utils::middleware::base_url;
 
 use std::str::FromStr;
 
 pub fn search(req: &mut Request) -> IronResult<Response> {
     let params = req.get_ref::<Params>().unwrap().clone();
-    let base_url = {
-        let url = &req.url;
-        if (&*url.scheme == "http" && url.port == 80) || (&*url.scheme == "https" && url.port == 443) {
-            format!("{}://{}", url.scheme, url.host)
-        } else {
-            format!("{}://{}:{}", url.scheme, url.host, url.port)
-        }
-    };
+    let base_url = base_url(req);
 
     let trimmer: &[_] = &['\\', '"', '\''];
     let ref search_term = match params.find(&["searchTerm"]) {
@@ -100,6 +94,7 @@ pub fn search(req: &mut Request) -> IronResult<Response> {
         });
     }
     let packages: Vec<PackageVersion> = iterators.into_iter().flat_map(|entry| entry)
+    .filter(|pkgver| !pkgver.is_quarantined())
     .filter(|pkgver| {
         if !include_prerelease {
             !pkgver.version().is_prerelease()
@@ -18,13 +18,14 @@ use iron::status;
 use iron::mime::Mime;
 use persistent::Read;
 use chrono::{UTC, TimeZone};
-use semver::Version;
+use ::web::backend::version::NugetVersion;
 use plugin::Pluggable;
 use params::{Params, Value};
 use treexml::{Document, Element};
 use ::web::server::ConnectionPoolKey;
 use ::web::backend::db::{Package, PackageVersion};
 use ::web::backend::xml::ToNugetFeedXml;
+use ::utils::middleware::base_url;
 use std::str::FromStr;
 
 pub fn updates(req: &mut Request) -> IronResult<Response> {
@@ -57,14 +58,7 @@ pub fn updates(req: &mut Request) -> IronResult<Response> {
         _ => return Ok(Response::with((status::BadRequest, "includePrerelease is no boolean"))),
     };
 
-    let base_url = {
-        let url = &req.url;
-        if (&*url.scheme == "http" && url.port == 80) || (&*url.scheme == "https" && url.port == 443) {
-            format!("{}://{}", url.scheme, url.host)
-        } else {
-            format!("{}://{}:{}", url.scheme, url.host, url.port)
-        }
-    };
+    let base_url = base_url(req);
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
 
     let connection = match connection_pool.get() {
@@ -77,8 +71,8 @@ pub fn updates(req: &mut Request) -> IronResult<Response> {
 
     let packages: Vec<PackageVersion> = {
         let mut packages = Vec::new();
-        for (pkg_id, cur_version) in ids {
-            let cur_version = match Version::parse(cur_version) {
+        for &(pkg_id, cur_version) in ids.iter() {
+            let cur_version = match NugetVersion::parse(cur_version) {
                 Ok(ver) => ver,
                 Err(_) => return Ok(Response::with((status::BadRequest, "Version value invalid"))),
             };
@@ -96,7 +90,7 @@ pub fn updates(req: &mut Request) -> IronResult<Response> {
                         Box::new(versions.into_iter().filter(|pkgver| pkgver.version().is_prerelease())) as  Box<Iterator<Item=PackageVersion>>
                     } else {
                         Box::new(versions.into_iter()) as  Box<Iterator<Item=PackageVersion>>
-                    }.filter(move |pkgver| pkgver.version() > cur_version)
+                    }.filter(move |pkgver| pkgver.version() > cur_version && !pkgver.is_quarantined())
                 },
                 Err(err) => {
                     error!("{}", err);
@@ -114,6 +108,33 @@ pub fn updates(req: &mut Request) -> IronResult<Response> {
         packages.into_iter().flat_map(|x| x).collect()
     };
 
+    //deprecated or vulnerable versions, installed ones as well as the offered updates
+    let mut warnings: Vec<String> = Vec::new();
+    for &(pkg_id, cur_version) in ids.iter() {
+        let installed = match NugetVersion::parse(cur_version) {
+            Ok(ver) => PackageVersion::get(&*connection, pkg_id, &ver),
+            Err(_) => continue,
+        };
+        //versions installed from other sources are unknown here
+        let installed = match installed {
+            Ok(installed) => installed,
+            Err(_) => continue,
+        };
+        for pkgver in Some(&installed).into_iter().chain(packages.iter().filter(|pkgver| pkgver.id() == installed.id())) {
+            match pkgver.warnings(&*connection) {
+                Ok(found) => for warning in found {
+                    if !warnings.contains(&warning) {
+                        warnings.push(warning);
+                    }
+                },
+                Err(err) => {
+                    error!("{:?}", err);
+                    return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+                }
+            }
+        }
+    }
+
     let mut feed = Element::new("feed");
     feed.attributes.insert(String::from("xml:base"), format!("{}/api/v2/", &*base_url));
     feed.attributes.insert(String::from("xmlns:d"), String::from("http://schemas.microsoft.com/ado/2007/08/dataservices"));
@@ -163,8 +184,33 @@ pub fn updates(req: &mut Request) -> IronResult<Response> {
        .. Document::default()
     };
 
-    Ok(Response::with((status::Ok, format!("{}", document), {
+    let mut response = Response::with((status::Ok, format!("{}", document), {
         let mime: Mime = "application/atom+xml".parse().unwrap();
         mime
-    })))
+    }));
+    if !warnings.is_empty() {
+        response.headers.set_raw("Warning", warnings.iter().map(|warning| warning_header(warning)).collect());
+    }
+    Ok(response)
+}
+
+/// Miscellaneous persistent warning (RFC 7234), hyper has no typed Warning header
+fn warning_header(text: &str) -> Vec<u8>
+{
+    let text: String = text.chars().map(|c| match c {
+        '"' | '\\' => '\'',
+        c if (c as u32) < 0x20 || (c as u32) > 0x7e => ' ',
+        c => c,
+    }).collect();
+    format!("299 hazel \"{}\"", text).into_bytes()
+}
+
+#[test]
+fn warning_header_text() {
+    assert_eq!(warning_header("Contoso 1.0.0 is deprecated (Legacy)"), b"299 hazel \"Contoso 1.0.0 is deprecated (Legacy)\"".to_vec());
+    //quotes and backslashes would end or escape the quoted-string early
+    assert_eq!(warning_header("use \"Contoso.Core\" \\ 2.0"), b"299 hazel \"use 'Contoso.Core' ' 2.0\"".to_vec());
+    //control characters, line breaks and non-ASCII must not reach the header
+    assert_eq!(warning_header("a\r\nX-Injected: 1\tb\u{7f}"), b"299 hazel \"a  X-Injected: 1 b \"".to_vec());
+    assert_eq!(warning_header("Übersicht ✓"), b"299 hazel \" bersicht  \"".to_vec());
 }
@@ -15,16 +15,63 @@
 
 use iron::{Request, Response, IronResult};
 use iron::status;
-use persistent::Read;
+use iron::headers::Accept;
+use iron::mime::Mime;
+use persistent::{Read, Write};
 use plugin::Pluggable;
 use params::{Params, Value};
+use rustc_serialize::json::{Json, ToJson};
+use treexml::{Document, Element};
 
-use ::web::server::{ConnectionPoolKey, StorageKey};
-use ::web::backend::db::{PackageVersion, User};
-use ::utils::error::BackendError;
+use std::collections::BTreeMap;
+
+use ::web::server::{ConnectionPoolKey, StorageKey, ThrottleKey, MetricsKey};
+use ::utils::throttle::{ThrottleTarget, too_many_requests};
+use ::web::backend::db::{PackageVersion, SymbolFile, User, AuditEvent, AuditAction, WebhookDelivery, WebhookEvent};
+use ::utils::error::{BackendError, SignatureError, ValidationProblem};
+use ::utils::config::live;
 
 header! { (XNugetApiKey, "X-NuGet-ApiKey") => [String] }
 
+/// Lists every problem at once, as json for the web uploader and as xml for nuget clients
+fn validation_failed(req: &Request, problems: &[ValidationProblem]) -> Response
+{
+    let wants_json = req.headers.get::<Accept>()
+        .map(|accept| accept.iter().any(|quality| format!("{}", quality.item).starts_with("application/json")))
+        .unwrap_or(false);
+
+    if wants_json {
+        let errors = problems.iter().map(|problem| {
+            let mut object = BTreeMap::new();
+            object.insert(String::from("location"), problem.location.to_json());
+            object.insert(String::from("message"), problem.message.to_json());
+            Json::Object(object)
+        }).collect::<Vec<_>>();
+        let mut object = BTreeMap::new();
+        object.insert(String::from("errors"), Json::Array(errors));
+        Response::with((status::BadRequest, Json::Object(object).to_string(), {
+            let mime: Mime = "application/json".parse().unwrap();
+            mime
+        }))
+    } else {
+        let mut root = Element::new("errors");
+        for problem in problems {
+            let mut error = Element::new("error");
+            error.attributes.insert(String::from("location"), problem.location.clone());
+            error.text = Some(problem.message.clone());
+            root.children.push(error);
+        }
+        let document = Document {
+            root: Some(root),
+            .. Document::default()
+        };
+        Response::with((status::BadRequest, format!("{}", document), {
+            let mime: Mime = "application/xml".parse().unwrap();
+            mime
+        }))
+    }
+}
+
 pub fn upload(req: &mut Request) -> IronResult<Response> {
     let params = req.get_ref::<Params>().unwrap().clone();
 
@@ -40,13 +87,51 @@ pub fn upload(req: &mut Request) -> IronResult<Response> {
         }
     };
 
+    let address = req.remote_addr.ip();
+    let throttle_mutex = req.extensions.get::<Write<ThrottleKey>>().unwrap().clone();
+    if let Some(retry_after) = throttle_mutex.lock().unwrap().check(&ThrottleTarget::ApiKeyAddress(address)) {
+        return Ok(too_many_requests(retry_after));
+    }
+
     match User::get_by_apikey(&*connection, &apikey) {
         Ok(user) => {
             match params.find(&["package"]) {
                 Some(&Value::File(ref file)) => {
+                    let limit = live().max_upload_filesize_mb as u64 * 1024 * 1024;
+                    if file.path.metadata().map(|metadata| metadata.len() > limit).unwrap_or(false) {
+                        return Ok(Response::with((status::PayloadTooLarge, format!("Packages may not exceed {} MB", limit / 1024 / 1024))));
+                    }
                     match PackageVersion::new(&*connection, &user, storage, file.open().unwrap()) {
-                        Ok(_) => Ok(Response::with(status::Ok)),
+                        Ok(pkgver) => {
+                            req.extensions.get::<Write<MetricsKey>>().unwrap().lock().unwrap().upload(pkgver.byte_size());
+                            AuditEvent::record(&*connection, &user, AuditAction::Upload, address, Some(pkgver.id()), Some(&*format!("{}", pkgver.version())), None, Some(pkgver.to_json()));
+                            //quarantined versions are announced once a rescan releases them
+                            if !pkgver.is_quarantined() {
+                                if let Err(err) = WebhookDelivery::enqueue(&*connection, WebhookEvent::Push, &user, pkgver.id(), Some(&*format!("{}", pkgver.version())), pkgver.to_json()) {
+                                    error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                                }
+                            }
+                            Ok(Response::with(status::Ok))
+                        },
                         Err(BackendError::PermissionDenied) => Ok(Response::with((status::Forbidden, "Only the maintainer or admin is allowed to update a package"))),
+                        Err(err @ BackendError::Infected(_)) => {
+                            warn!(target: "hazel::security", "Rejected upload from {}: {}", address, err);
+                            Ok(Response::with((status::Forbidden, format!("{}", err))))
+                        },
+                        Err(BackendError::ValidationFailed(problems)) => {
+                            info!("Rejected invalid package from {}: {}", address, problems.iter().map(|problem| format!("{}", problem)).collect::<Vec<_>>().join("; "));
+                            Ok(validation_failed(req, &problems))
+                        },
+                        Err(err @ BackendError::QuotaExceeded(_)) => Ok(Response::with((status::PayloadTooLarge, format!("{}", err)))),
+                        Err(BackendError::SignatureError(SignatureError::OpenSSLError(err))) => {
+                            error!("{}", err);
+                            Ok(Response::with((status::InternalServerError, "Unable to verify the package signature, please try again later")))
+                        },
+                        Err(BackendError::SignatureError(err @ SignatureError::Untrusted(_))) |
+                        Err(BackendError::SignatureError(err @ SignatureError::Missing)) => {
+                            info!(target: "hazel::security", "Rejected upload from {}: {}", address, err);
+                            Ok(Response::with((status::Forbidden, format!("{}", err))))
+                        },
                         Err(err) => {
                             error!("{}", err);
                             Ok(Response::with((status::BadRequest, format!("{}", err))))
@@ -57,6 +142,82 @@ pub fn upload(req: &mut Request) -> IronResult<Response> {
             }
         },
         //TODO better match
-        Err(_) => Ok(Response::with((status::InternalServerError, "No User with matching API-Key found"))),
+        Err(_) => {
+            info!(target: "hazel::security", "Invalid API-Key used from {}", address);
+            req.extensions.get::<Write<MetricsKey>>().unwrap().lock().unwrap().failed_login("apikey");
+            match throttle_mutex.lock().unwrap().failure(ThrottleTarget::ApiKeyAddress(address)) {
+                Some(retry_after) => Ok(too_many_requests(retry_after)),
+                None => Ok(Response::with((status::InternalServerError, "No User with matching API-Key found"))),
+            }
+        },
+    }
+}
+
+/// Symbol packages (.snupkg) of versions pushed before, `nuget push` sends them to `api/v2/symbolpackage`
+pub fn symbol_upload(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+
+    let apikey = match req.headers.get::<XNugetApiKey>() {
+        Some(apikey) => apikey.0.clone(),
+        None => return Ok(Response::with((status::Unauthorized, "API-Key missing"))),
+    };
+
+    let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let address = req.remote_addr.ip();
+    let throttle_mutex = req.extensions.get::<Write<ThrottleKey>>().unwrap().clone();
+    if let Some(retry_after) = throttle_mutex.lock().unwrap().check(&ThrottleTarget::ApiKeyAddress(address)) {
+        return Ok(too_many_requests(retry_after));
+    }
+
+    let user = match User::get_by_apikey(&*connection, &apikey) {
+        Ok(user) => user,
+        Err(_) => {
+            info!(target: "hazel::security", "Invalid API-Key used from {}", address);
+            req.extensions.get::<Write<MetricsKey>>().unwrap().lock().unwrap().failed_login("apikey");
+            return match throttle_mutex.lock().unwrap().failure(ThrottleTarget::ApiKeyAddress(address)) {
+                Some(retry_after) => Ok(too_many_requests(retry_after)),
+                None => Ok(Response::with((status::InternalServerError, "No User with matching API-Key found"))),
+            };
+        },
+    };
+
+    let file = match params.find(&["package"]) {
+        Some(&Value::File(ref file)) => file,
+        _ => return Ok(Response::with((status::BadRequest, "package is no File"))),
+    };
+    let limit = live().max_upload_filesize_mb as u64 * 1024 * 1024;
+    if file.path.metadata().map(|metadata| metadata.len() > limit).unwrap_or(false) {
+        return Ok(Response::with((status::PayloadTooLarge, format!("Packages may not exceed {} MB", limit / 1024 / 1024))));
+    }
+
+    match SymbolFile::upload(&*connection, &user, storage, file.open().unwrap()) {
+        Ok((pkgver, symbols)) => {
+            let after = Json::Array(symbols.iter().map(|symbol| {
+                let mut object = BTreeMap::new();
+                object.insert(String::from("path"), symbol.path().to_json());
+                object.insert(String::from("signature"), symbol.signature().to_json());
+                Json::Object(object)
+            }).collect());
+            AuditEvent::record(&*connection, &user, AuditAction::SymbolUpload, address, Some(pkgver.id()), Some(&*format!("{}", pkgver.version())), None, Some(after));
+            Ok(Response::with(status::Ok))
+        },
+        Err(BackendError::PermissionDenied) => Ok(Response::with((status::Forbidden, "Only the maintainer or admin is allowed to push symbols of a package"))),
+        Err(BackendError::ValidationFailed(problems)) => {
+            info!("Rejected invalid symbol package from {}: {}", address, problems.iter().map(|problem| format!("{}", problem)).collect::<Vec<_>>().join("; "));
+            Ok(validation_failed(req, &problems))
+        },
+        Err(err) => {
+            error!("{}", err);
+            Ok(Response::with((status::BadRequest, format!("{}", err))))
+        },
     }
 }
@@ -16,13 +16,15 @@
 use iron::{Request, Response, IronResult};
 use iron::status::{self, Status};
 use persistent::Read;
+use rustc_serialize::json::ToJson;
 
-use ::web::backend::db::User;
+use ::web::backend::db::{User, AuditEvent, AuditAction};
 use ::web::server::ConnectionPoolKey;
 use ::utils::middleware::Authenticated;
 
 pub fn apikey(req: &mut Request) -> IronResult<Response>
 {
+    let address = req.remote_addr.ip();
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
     let connection = match connection_pool.get() {
         Ok(connection) => connection,
@@ -38,16 +40,23 @@ pub fn apikey(req: &mut Request) -> IronResult<Response>
             match User::get(&*connection, username) {
                 Ok(mut user) => {
                     if user.confirmed() {
+                        let before = user.to_json();
                         match req.url.path.iter().last() {
                             Some(x) if x == "reset" => {
                                 match user.generate_apikey(&*connection) {
-                                    Ok(user) => Ok(Response::with((Status::Ok, user.apikey().unwrap()))),
+                                    Ok(user) => {
+                                        AuditEvent::record(&*connection, &user, AuditAction::ApiKeyReset, address, None, None, Some(before), Some(user.to_json()));
+                                        Ok(Response::with((Status::Ok, user.apikey().unwrap())))
+                                    },
                                     _ => Ok(Response::with(Status::InternalServerError)),
                                 }
                             },
                             Some(x) if x == "revoke" => {
                                 match user.revoke_apikey(&*connection) {
-                                    Ok(_) => Ok(Response::with(Status::Ok)),
+                                    Ok(user) => {
+                                        AuditEvent::record(&*connection, &user, AuditAction::ApiKeyRevoke, address, None, None, Some(before), Some(user.to_json()));
+                                        Ok(Response::with(Status::Ok))
+                                    },
                                     _ => Ok(Response::with(Status::InternalServerError)),
                                 }
                             },
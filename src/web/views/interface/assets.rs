@@ -0,0 +1,127 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use iron::{Request, Response, IronResult, Url};
+use iron::status;
+use iron::mime::Mime;
+use iron::headers::{CacheControl, CacheDirective, ETag, EntityTag, IfNoneMatch};
+use iron::modifiers::Redirect;
+use persistent::Read;
+use router::Router;
+use ::web::backend::version::NugetVersion;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use ::web::server::ConnectionPoolKey;
+use ::web::backend::db::{PackageVersion, PackageAsset};
+use ::web::backend::assets::AssetKind;
+use ::utils::error::BackendError;
+use ::utils::CONFIG;
+
+/// Assets only change if the version is uploaded again, the ETag covers that
+const MAX_AGE_SECS: u32 = 24 * 60 * 60;
+
+fn default_icon(req: &Request) -> Response
+{
+    Response::with((status::Found, Redirect({
+        let mut base = req.url.clone();
+        base.path = vec![String::from("img"), String::from("icon.png")];
+        base
+    })))
+}
+
+fn serve(req: &mut Request, kind: AssetKind) -> IronResult<Response> {
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+
+    let ref id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+    let ref version = req.extensions.get::<Router>().unwrap().find("version").unwrap();
+
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let pkgver = match PackageVersion::get(&*connection, id, &match NugetVersion::parse(version) {
+        Ok(ver) => ver,
+        Err(_) => return Ok(Response::with((status::UnprocessableEntity, "Version value invalid"))),
+    }) {
+        Ok(pkgver) => pkgver,
+        //most likely the package was not found (TODO match diesel Error as well)
+        Err(BackendError::DBError(_)) => return Ok(Response::with((status::NotFound, "Package not found"))),
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    if pkgver.is_quarantined() {
+        return Ok(match kind {
+            AssetKind::Icon => default_icon(req),
+            _ => Response::with((status::Forbidden, "This version is quarantined until it passes the malware scan")),
+        });
+    }
+
+    let asset = match PackageAsset::get(&*connection, &pkgver, kind) {
+        Ok(asset) => asset,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (media_type, content) = match asset.as_ref().and_then(|asset| asset.media_type().and_then(|media_type| asset.content().map(|content| (media_type, content)))) {
+        Some(found) => found,
+        None => return Ok(match (kind, asset.is_none(), pkgver.icon_url.as_ref()) {
+            //without fetching the icons are linked like before
+            (AssetKind::Icon, true, Some(url)) if CONFIG.backend.icons.is_none() => match Url::parse(url) {
+                Ok(url) => Response::with((status::Found, Redirect(url))),
+                Err(_) => default_icon(req),
+            },
+            (AssetKind::Icon, _, _) => default_icon(req),
+            _ => Response::with((status::NotFound, format!("The package does not contain a {}", kind.as_str()))),
+        }),
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.input(content);
+    let etag = EntityTag::strong(hasher.result_str());
+
+    let mut response = match req.headers.get::<IfNoneMatch>() {
+        Some(&IfNoneMatch::Items(ref tags)) if tags.iter().any(|tag| tag.weak_eq(&etag)) => Response::with(status::NotModified),
+        _ => Response::with((status::Ok, content.clone(), {
+            let mime: Mime = media_type.parse().unwrap_or("application/octet-stream".parse().unwrap());
+            mime
+        })),
+    };
+    response.headers.set(ETag(etag));
+    response.headers.set(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(MAX_AGE_SECS)]));
+    Ok(response)
+}
+
+pub fn icon(req: &mut Request) -> IronResult<Response> {
+    serve(req, AssetKind::Icon)
+}
+
+pub fn readme(req: &mut Request) -> IronResult<Response> {
+    serve(req, AssetKind::Readme)
+}
+
+pub fn license(req: &mut Request) -> IronResult<Response> {
+    serve(req, AssetKind::License)
+}
@@ -0,0 +1,238 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult, Plugin};
+use iron::status;
+use iron::mime::Mime;
+use params::{Params, Value, Map};
+use persistent::Read;
+use mustache::{Template, compile_path};
+use rustc_serialize::json::ToJson;
+use url::form_urlencoded;
+use diesel::pg::PgConnection;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use ::web::server::ConnectionPoolKey;
+use ::web::backend::db::{User, AuditEvent, AuditAction, AuditFilter};
+use ::utils::middleware::Authenticated;
+use ::utils::CONFIG;
+
+lazy_static! {
+    static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("audit.html")).unwrap();
+}
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(RustcEncodable)]
+struct AuditPage
+{
+    username: String,
+    events: Vec<Event>,
+    actions: Vec<ActionOption>,
+    actor: String,
+    target: String,
+    query: String,
+    previous: Option<PageLink>,
+    next: Option<PageLink>,
+}
+
+#[derive(RustcEncodable)]
+struct PageLink
+{
+    query: String,
+}
+
+#[derive(RustcEncodable)]
+struct Event
+{
+    occurred: String,
+    actor: String,
+    action: String,
+    target: String,
+    source_ip: String,
+    before: String,
+    after: String,
+}
+
+#[derive(RustcEncodable)]
+struct ActionOption
+{
+    name: String,
+    selected: bool,
+}
+
+impl From<AuditEvent> for Event
+{
+    fn from(event: AuditEvent) -> Event
+    {
+        Event {
+            occurred: format!("{}", event.occurred().format("%Y-%m-%d %H:%M:%S")),
+            actor: event.actor().to_owned(),
+            action: event.action().to_owned(),
+            target: match (event.target_id(), event.target_version()) {
+                (Some(id), Some(version)) => format!("{} {}", id, version),
+                (Some(id), None) => id.clone(),
+                _ => String::new(),
+            },
+            source_ip: event.source_ip().cloned().unwrap_or(String::new()),
+            before: event.before_metadata().cloned().unwrap_or(String::new()),
+            after: event.after_metadata().cloned().unwrap_or(String::new()),
+        }
+    }
+}
+
+fn audit_filter(params: &Map) -> AuditFilter
+{
+    let non_empty = |name: &str| match params.find(&[name]) {
+        Some(&Value::String(ref value)) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    };
+
+    AuditFilter {
+        actor: non_empty("actor"),
+        action: non_empty("action").and_then(|action| AuditAction::from_str(&action).ok()),
+        target_id: non_empty("target"),
+    }
+}
+
+fn query_string(filter: &AuditFilter, page: Option<i64>) -> String
+{
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+    if let Some(ref actor) = filter.actor {
+        serializer.append_pair("actor", actor);
+    }
+    if let Some(action) = filter.action {
+        serializer.append_pair("action", action.as_str());
+    }
+    if let Some(ref target) = filter.target_id {
+        serializer.append_pair("target", target);
+    }
+    if let Some(page) = page {
+        serializer.append_pair("page", &format!("{}", page));
+    }
+    serializer.finish()
+}
+
+/// Returns the logged in user, if it is an administrator account
+pub fn admin(req: &Request, connection: &PgConnection) -> Result<User, Response>
+{
+    match req.extensions.get::<Authenticated>().unwrap() {
+        &(true, Some(ref username)) => match User::get(connection, username) {
+            Ok(user) => if user.is_admin() {
+                Ok(user)
+            } else {
+                Err(Response::with((status::Forbidden, "Only administrators may view this page")))
+            },
+            Err(_) => Err(Response::with((status::Unauthorized, "User does not exist anymore"))),
+        },
+        _ => Err(Response::with(status::Unauthorized)),
+    }
+}
+
+pub fn audit(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+    let filter = audit_filter(&params);
+    let page = match params.find(&["page"]) {
+        Some(&Value::String(ref page)) => i64::from_str(page).unwrap_or(1),
+        _ => 1,
+    };
+    let page = if page < 1 { 1 } else { page };
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let user = match admin(req, &*connection) {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    //fetch one more than we display to know if there is a next page
+    let mut events = match AuditEvent::find(&*connection, &filter, (page - 1) * PAGE_SIZE, Some(PAGE_SIZE + 1)) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+    let has_next = events.len() as i64 > PAGE_SIZE;
+    events.truncate(PAGE_SIZE as usize);
+
+    let rendering = AuditPage {
+        username: user.name.clone(),
+        events: events.into_iter().map(Event::from).collect(),
+        actions: AuditAction::all().into_iter().map(|action| ActionOption {
+            name: String::from(action.as_str()),
+            selected: filter.action == Some(action),
+        }).collect(),
+        actor: filter.actor.clone().unwrap_or(String::new()),
+        target: filter.target_id.clone().unwrap_or(String::new()),
+        query: query_string(&filter, None),
+        previous: if page > 1 { Some(PageLink { query: query_string(&filter, Some(page - 1)) }) } else { None },
+        next: if has_next { Some(PageLink { query: query_string(&filter, Some(page + 1)) }) } else { None },
+    };
+
+    let mut buffer = Vec::new();
+    TEMPLATE.render(&mut buffer, &rendering).unwrap();
+    Ok(Response::with((status::Ok, buffer, {
+        let mime: Mime = "text/html".parse().unwrap();
+        mime
+    })))
+}
+
+pub fn audit_export(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+    let filter = audit_filter(&params);
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    if let Err(response) = admin(req, &*connection) {
+        return Ok(response);
+    }
+
+    let events = match AuditEvent::find(&*connection, &filter, 0, None) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    //oldest first, one json object per line
+    let mut body = String::new();
+    for event in events.into_iter().rev() {
+        body.push_str(&event.to_json().to_string());
+        body.push('\n');
+    }
+
+    Ok(Response::with((status::Ok, body, {
+        let mime: Mime = "application/x-ndjson".parse().unwrap();
+        mime
+    })))
+}
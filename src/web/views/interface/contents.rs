@@ -0,0 +1,331 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use iron::{Request, Response, IronResult, Plugin};
+use iron::status;
+use iron::mime::Mime;
+use params::{Params, Value};
+use persistent::Read;
+use router::Router;
+use mustache::{Template, compile_path};
+use diesel::pg::PgConnection;
+use ::web::backend::version::NugetVersion;
+use url::form_urlencoded;
+
+use std::path::PathBuf;
+
+use ::web::server::{ConnectionPoolKey, StorageKey};
+use ::web::backend::db::PackageVersion;
+use ::web::backend::contents::{self, Entry, MAX_INLINE_SIZE};
+use ::utils::error::BackendError;
+use ::utils::middleware::Authenticated;
+use ::utils::CONFIG;
+
+lazy_static! {
+    static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("contents.html")).unwrap();
+}
+
+#[derive(RustcEncodable)]
+struct ContentsPage
+{
+    loggedin: bool,
+    username: Option<String>,
+    package: String,
+    version: String,
+    quarantined: bool,
+    rows: Vec<Row>,
+    file: Option<File>,
+}
+
+/// Line of the file tree, directories are derived from the paths of the files
+#[derive(RustcEncodable)]
+struct Row
+{
+    name: String,
+    directory: bool,
+    indent: usize,
+    query: String,
+    size: u64,
+    compressed_size: u64,
+    compression: String,
+    selected: bool,
+}
+
+#[derive(RustcEncodable)]
+struct File
+{
+    name: String,
+    query: String,
+    size: u64,
+    language: String,
+    content: Option<String>,
+    reason: Option<String>,
+}
+
+fn query(path: &str) -> String
+{
+    form_urlencoded::Serializer::new(String::new()).append_pair("path", path).finish()
+}
+
+fn language(name: &str) -> &'static str
+{
+    let extension = name.rsplit('.').next().unwrap_or("").to_lowercase();
+    match &*extension {
+        "ps1" | "psm1" | "psd1" => "powershell",
+        "nuspec" | "xml" | "config" | "props" | "targets" | "rels" | "psmdcp" | "html" => "xml",
+        "json" => "json",
+        "md" => "markdown",
+        "bat" | "cmd" => "dos",
+        "sh" => "bash",
+        "yml" | "yaml" => "yaml",
+        "ini" => "ini",
+        "cs" => "cs",
+        _ => "nohighlight",
+    }
+}
+
+fn tree(entries: &[Entry], selected: Option<&str>) -> Vec<Row>
+{
+    let mut rows = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+
+    for entry in entries.iter() {
+        let segments: Vec<&str> = entry.name.split('/').collect();
+        let (file_name, directories) = segments.split_last().unwrap();
+
+        let common = open.iter().zip(directories.iter()).take_while(|&(a, b)| a == b).count();
+        open.truncate(common);
+        for directory in directories[common..].iter() {
+            rows.push(Row {
+                name: String::from(*directory),
+                directory: true,
+                indent: open.len() * 20,
+                query: String::new(),
+                size: 0,
+                compressed_size: 0,
+                compression: String::new(),
+                selected: false,
+            });
+            open.push(*directory);
+        }
+
+        rows.push(Row {
+            name: String::from(*file_name),
+            directory: false,
+            indent: open.len() * 20,
+            query: query(&entry.path),
+            size: entry.size,
+            compressed_size: entry.compressed_size,
+            compression: String::from(entry.compression),
+            selected: selected == Some(&*entry.path),
+        });
+    }
+    rows
+}
+
+fn path_param(req: &mut Request) -> Option<String>
+{
+    match req.get_ref::<Params>().unwrap().find(&["path"]) {
+        Some(&Value::String(ref path)) => Some(path.clone()),
+        _ => None,
+    }
+}
+
+fn package_version(req: &Request, connection: &PgConnection) -> Result<PackageVersion, Response>
+{
+    let ref id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+    let ref version = req.extensions.get::<Router>().unwrap().find("version").unwrap();
+
+    match PackageVersion::get(connection, id, &match NugetVersion::parse(version) {
+        Ok(ver) => ver,
+        Err(_) => return Err(Response::with((status::UnprocessableEntity, "Version value invalid"))),
+    }) {
+        Ok(pkgver) => Ok(pkgver),
+        //most likely the package was not found (TODO match diesel Error as well)
+        Err(BackendError::DBError(_)) => Err(Response::with((status::NotFound, "Package not found"))),
+        Err(err) => {
+            error!("{:?}", err);
+            Err(Response::with((status::InternalServerError, "Database Error, please try again later")))
+        }
+    }
+}
+
+/// File tree of the stored package, text files selected by `path` are shown inline
+pub fn contents(req: &mut Request) -> IronResult<Response> {
+    let path = path_param(req);
+
+    let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let pkgver = match package_version(req, &*connection) {
+        Ok(pkgver) => pkgver,
+        Err(response) => return Ok(response),
+    };
+
+    let entries = match pkgver.download(storage).and_then(contents::list) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Filesystem Error, please try again later")));
+        }
+    };
+
+    let file = match path {
+        Some(ref path) => match entries.iter().find(|entry| entry.path == *path) {
+            Some(entry) => Some(File {
+                name: entry.name.clone(),
+                query: query(&entry.path),
+                size: entry.size,
+                language: String::from(language(&entry.name)),
+                content: None,
+                reason: if !entry.is_text() {
+                    Some(String::from("Binary files can only be downloaded."))
+                } else if entry.size > MAX_INLINE_SIZE {
+                    Some(format!("Files larger than {} KiB can only be downloaded.", MAX_INLINE_SIZE / 1024))
+                } else {
+                    None
+                },
+            }),
+            None => return Ok(Response::with((status::NotFound, "File not found in package"))),
+        },
+        None => None,
+    };
+
+    let file = match file {
+        Some(mut file) => {
+            if file.reason.is_none() {
+                match pkgver.download(storage).and_then(|reader| contents::read(reader, path.as_ref().unwrap())) {
+                    Ok(Some((_, content))) => match String::from_utf8(content) {
+                        Ok(content) => file.content = Some(String::from(content.trim_left_matches('\u{feff}'))),
+                        Err(_) => file.reason = Some(String::from("The file is not valid UTF-8 and can only be downloaded.")),
+                    },
+                    Ok(None) => return Ok(Response::with((status::NotFound, "File not found in package"))),
+                    Err(BackendError::EntryTooLarge(_)) => file.reason = Some(String::from("The file is too large to be shown.")),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        return Ok(Response::with((status::InternalServerError, "Filesystem Error, please try again later")));
+                    }
+                }
+            }
+            Some(file)
+        },
+        None => None,
+    };
+
+    let (loggedin, username) = match req.extensions.get::<Authenticated>().unwrap() {
+        &(true, Some(ref username)) => (true, Some(username.clone())),
+        _ => (false, None),
+    };
+
+    let page = ContentsPage {
+        loggedin: loggedin,
+        username: username,
+        package: String::from(pkgver.id()),
+        version: format!("{}", pkgver.version()),
+        quarantined: pkgver.is_quarantined(),
+        rows: tree(&entries, path.as_ref().map(|path| &**path)),
+        file: file,
+    };
+
+    let mut buffer = Vec::new();
+    TEMPLATE.render(&mut buffer, &page).unwrap(); //TODO
+    Ok(Response::with((status::Ok, buffer, {
+        let mime: Mime = "text/html".parse().unwrap();
+        mime
+    })))
+}
+
+/// Attachment header for a file name taken from the package, which must not be trusted.
+/// Quotes, backslashes and control characters are dropped from the plain `filename`,
+/// the exact name is passed as RFC 5987 `filename*`.
+fn content_disposition(name: &str) -> String
+{
+    let fallback: String = name.chars().filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .map(|c| if (c as u32) < 0x80 { c } else { '_' }).collect();
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        match byte {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' =>
+                encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+/// Single file of the stored package as attachment
+pub fn content_download(req: &mut Request) -> IronResult<Response> {
+    let path = match path_param(req) {
+        Some(path) => path,
+        None => return Ok(Response::with((status::BadRequest, "Parameter \"path\" is missing"))),
+    };
+
+    let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let pkgver = match package_version(req, &*connection) {
+        Ok(pkgver) => pkgver,
+        Err(response) => return Ok(response),
+    };
+
+    if pkgver.is_quarantined() {
+        return Ok(Response::with((status::Forbidden, "This version is quarantined until it passes the malware scan")));
+    }
+
+    match pkgver.download(storage).and_then(|reader| contents::read(reader, &path)) {
+        Ok(Some((entry, content))) => {
+            let mut response = Response::with((status::Ok, content, {
+                let mime: Mime = "application/octet-stream".parse().unwrap();
+                mime
+            }));
+            response.headers.set_raw("Content-Disposition", vec![content_disposition(entry.name.rsplit('/').next().unwrap_or("")).into_bytes()]);
+            Ok(response)
+        },
+        Ok(None) => Ok(Response::with((status::NotFound, "File not found in package"))),
+        Err(BackendError::EntryTooLarge(_)) => Ok(Response::with((status::PayloadTooLarge, "The file is too large to be downloaded on its own, download the package instead"))),
+        Err(err) => {
+            error!("{:?}", err);
+            Ok(Response::with((status::InternalServerError, "Filesystem Error, please try again later")))
+        }
+    }
+}
+
+#[test]
+fn download_filenames() {
+    assert_eq!(content_disposition("chocolateyInstall.ps1"),
+               "attachment; filename=\"chocolateyInstall.ps1\"; filename*=UTF-8''chocolateyInstall.ps1");
+    assert_eq!(content_disposition("a\"b\\c.txt"),
+               "attachment; filename=\"abc.txt\"; filename*=UTF-8''a%22b%5Cc.txt");
+    let header = content_disposition("evil\r\nSet-Cookie: session=x.txt");
+    assert!(!header.contains('\r') && !header.contains('\n'));
+    assert_eq!(header, "attachment; filename=\"evilSet-Cookie: session=x.txt\"; filename*=UTF-8''evil%0D%0ASet-Cookie%3A%20session%3Dx.txt");
+    assert_eq!(content_disposition("Ünïcode.txt"),
+               "attachment; filename=\"_n_code.txt\"; filename*=UTF-8''%C3%9Cn%C3%AFcode.txt");
+}
@@ -0,0 +1,280 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult, Plugin};
+use iron::status;
+use iron::modifiers::Redirect;
+use params::{Params, Value};
+use persistent::Read;
+use router::Router;
+use diesel::pg::PgConnection;
+use rustc_serialize::json::{Json, ToJson};
+use url::Url;
+
+use std::str::FromStr;
+
+use ::web::server::ConnectionPoolKey;
+use ::web::backend::db::{User, PackageVersion, Deprecation, DeprecationReason, Advisory, AdvisorySeverity, AuditEvent, AuditAction};
+use ::web::backend::version::{NugetVersion, VersionRange};
+use ::utils::error::BackendError;
+use ::utils::middleware::Authenticated;
+
+/// Returns the logged in user and the version, if the user maintains its package or is an administrator
+fn maintained_version(req: &Request, connection: &PgConnection) -> Result<(User, PackageVersion), Response>
+{
+    let id = match req.extensions.get::<Router>().unwrap().find("id") {
+        Some(id) => String::from(id),
+        None => return Err(Response::with(status::BadRequest)),
+    };
+    let version = match req.extensions.get::<Router>().unwrap().find("version").map(|version| NugetVersion::parse(version)) {
+        Some(Ok(version)) => version,
+        Some(Err(_)) => return Err(Response::with((status::UnprocessableEntity, "Version value invalid"))),
+        None => return Err(Response::with(status::BadRequest)),
+    };
+
+    let user = match req.extensions.get::<Authenticated>().unwrap() {
+        &(true, Some(ref username)) => match User::get(connection, username) {
+            Ok(user) => user,
+            Err(_) => return Err(Response::with((status::Unauthorized, "User does not exist anymore"))),
+        },
+        _ => return Err(Response::with(status::Unauthorized)),
+    };
+
+    let pkgver = match PackageVersion::get(connection, &id, &version) {
+        Ok(pkgver) => pkgver,
+        //most likely the package was not found (TODO match diesel Error as well)
+        Err(BackendError::DBError(_)) => return Err(Response::with((status::NotFound, "Package/Version not found"))),
+        Err(err) => {
+            error!("{:?}", err);
+            return Err(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    match pkgver.package(connection).and_then(|package| package.maintainer(connection)) {
+        Ok(maintainer) => if maintainer == user || user.is_admin() {
+            Ok((user, pkgver))
+        } else {
+            Err(Response::with((status::Forbidden, "You are not the maintainer of the requested package.")))
+        },
+        Err(_) => Err(Response::with((status::InternalServerError, "Database Error, please try again later"))),
+    }
+}
+
+fn back_to_version(req: &Request, pkgver: &PackageVersion) -> Response
+{
+    Response::with((status::SeeOther, Redirect({
+        let mut base = req.url.clone();
+        base.path = vec![String::from("packages"), String::from(pkgver.id()), format!("{}", pkgver.version()), String::from("edit")];
+        base.query = None;
+        base
+    })))
+}
+
+fn failed(err: BackendError) -> Response
+{
+    match err {
+        BackendError::ValidationFailed(problems) => Response::with((status::BadRequest,
+            problems.iter().map(|problem| format!("{}", problem)).collect::<Vec<_>>().join("\n"))),
+        err => {
+            error!("{:?}", err);
+            Response::with((status::InternalServerError, "Database Error, please try again later"))
+        },
+    }
+}
+
+fn deprecation_json(connection: &PgConnection, pkgver: &PackageVersion) -> Result<Json, BackendError>
+{
+    Deprecation::get(connection, pkgver).map(|deprecation| deprecation.map(|deprecation| deprecation.to_json()).unwrap_or(Json::Null))
+}
+
+pub fn deprecate(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+
+    //every reason is a checkbox named after it
+    let reasons = DeprecationReason::all().into_iter().filter(|reason| match params.find(&[reason.as_str()]) {
+        Some(&Value::String(ref value)) => value == "on",
+        _ => false,
+    }).collect::<Vec<_>>();
+    let message = match params.find(&["message"]) {
+        Some(&Value::String(ref message)) if !message.trim().is_empty() => Some(String::from(message.trim())),
+        _ => None,
+    };
+    let alternate_range = match params.find(&["alternate_range"]) {
+        Some(&Value::String(ref range)) => match VersionRange::convert(range) {
+            Ok(range) => range,
+            Err(_) => return Ok(Response::with((status::BadRequest, "The alternate version range is invalid"))),
+        },
+        _ => VersionRange::any(),
+    };
+    let alternate = match params.find(&["alternate_id"]) {
+        Some(&Value::String(ref id)) if !id.trim().is_empty() => Some((String::from(id.trim()), alternate_range)),
+        _ => None,
+    };
+    let all_versions = match params.find(&["all_versions"]) {
+        Some(&Value::String(ref value)) => value == "on",
+        _ => false,
+    };
+
+    let address = req.remote_addr.ip();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (user, pkgver) = match maintained_version(req, &*connection) {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    //the selected version comes first, so invalid input is reported before anything changed
+    let versions = if all_versions {
+        match pkgver.package(&*connection).and_then(|package| package.versions(&*connection)) {
+            Ok(versions) => versions,
+            Err(err) => return Ok(failed(err)),
+        }
+    } else {
+        Vec::new()
+    };
+
+    for version in Some(&pkgver).into_iter().chain(versions.iter().filter(|version| **version != pkgver)) {
+        let before = match deprecation_json(&*connection, version) {
+            Ok(before) => before,
+            Err(err) => return Ok(failed(err)),
+        };
+        match Deprecation::set(&*connection, version, &reasons, message.clone(), alternate.clone()) {
+            Ok(deprecation) => {
+                AuditEvent::record(&*connection, &user, AuditAction::Deprecate, address, Some(version.id()), Some(&*format!("{}", version.version())), Some(before), Some(deprecation.to_json()));
+            },
+            Err(err) => return Ok(failed(err)),
+        }
+    }
+
+    Ok(back_to_version(req, &pkgver))
+}
+
+pub fn undeprecate(req: &mut Request) -> IronResult<Response> {
+    let address = req.remote_addr.ip();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (user, pkgver) = match maintained_version(req, &*connection) {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    let before = match deprecation_json(&*connection, &pkgver) {
+        Ok(before) => before,
+        Err(err) => return Ok(failed(err)),
+    };
+    match Deprecation::remove(&*connection, &pkgver) {
+        Ok(()) => {
+            AuditEvent::record(&*connection, &user, AuditAction::Deprecate, address, Some(pkgver.id()), Some(&*format!("{}", pkgver.version())), Some(before), Some(Json::Null));
+            Ok(back_to_version(req, &pkgver))
+        },
+        Err(err) => Ok(failed(err)),
+    }
+}
+
+pub fn advisory_create(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+
+    let url = match params.find(&["url"]) {
+        Some(&Value::String(ref url)) => match Url::parse(url.trim()) {
+            Ok(ref parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => String::from(url.trim()),
+            _ => return Ok(Response::with((status::BadRequest, "The advisory url has to be an absolute http or https url"))),
+        },
+        _ => return Ok(Response::with((status::BadRequest, "No advisory url given"))),
+    };
+    let severity = match params.find(&["severity"]) {
+        Some(&Value::String(ref severity)) => match AdvisorySeverity::from_str(severity) {
+            Ok(severity) => severity,
+            Err(()) => return Ok(Response::with((status::BadRequest, "Unknown severity"))),
+        },
+        _ => return Ok(Response::with((status::BadRequest, "No severity given"))),
+    };
+
+    let address = req.remote_addr.ip();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (user, pkgver) = match maintained_version(req, &*connection) {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    let before = match Advisory::get(&*connection, &pkgver, &url) {
+        Ok(before) => before.map(|advisory| advisory.to_json()).unwrap_or(Json::Null),
+        Err(err) => return Ok(failed(err)),
+    };
+    match Advisory::new(&*connection, &pkgver, url, severity) {
+        Ok(advisory) => {
+            AuditEvent::record(&*connection, &user, AuditAction::Advisory, address, Some(pkgver.id()), Some(&*format!("{}", pkgver.version())), Some(before), Some(advisory.to_json()));
+            Ok(back_to_version(req, &pkgver))
+        },
+        Err(err) => Ok(failed(err)),
+    }
+}
+
+pub fn advisory_delete(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+
+    let url = match params.find(&["url"]) {
+        Some(&Value::String(ref url)) => url.clone(),
+        _ => return Ok(Response::with((status::BadRequest, "No advisory url given"))),
+    };
+
+    let address = req.remote_addr.ip();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (user, pkgver) = match maintained_version(req, &*connection) {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    match Advisory::get(&*connection, &pkgver, &url) {
+        Ok(Some(advisory)) => match advisory.remove(&*connection) {
+            Ok(()) => {
+                AuditEvent::record(&*connection, &user, AuditAction::Advisory, address, Some(pkgver.id()), Some(&*format!("{}", pkgver.version())), Some(advisory.to_json()), Some(Json::Null));
+                Ok(back_to_version(req, &pkgver))
+            },
+            Err(err) => Ok(failed(err)),
+        },
+        Ok(None) => Ok(Response::with((status::NotFound, "Advisory not found"))),
+        Err(err) => Ok(failed(err)),
+    }
+}
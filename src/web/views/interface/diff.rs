@@ -0,0 +1,205 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use iron::{Request, Response, IronResult};
+use iron::status;
+use iron::mime::Mime;
+use persistent::Read;
+use router::Router;
+use mustache::{Template, compile_path};
+use ::web::backend::version::NugetVersion;
+use lazysort::SortedBy;
+
+use std::path::PathBuf;
+
+use ::web::server::{ConnectionPoolKey, StorageKey};
+use ::web::backend::db::{Package, PackageVersion, PackageDiff};
+use ::utils::error::BackendError;
+use ::utils::middleware::Authenticated;
+use ::utils::CONFIG;
+
+lazy_static! {
+    static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("diff.html")).unwrap();
+}
+
+#[derive(RustcEncodable)]
+struct DiffPage
+{
+    loggedin: bool,
+    username: Option<String>,
+    package: String,
+    base: String,
+    version: String,
+    versions: Vec<VersionOption>,
+    unchanged: bool,
+    metadata: Vec<Change>,
+    dependencies: Vec<Change>,
+    files: Vec<File>,
+    texts: Vec<Text>,
+}
+
+#[derive(RustcEncodable)]
+struct VersionOption
+{
+    version: String,
+    selected: bool,
+}
+
+#[derive(RustcEncodable)]
+struct Change
+{
+    name: String,
+    old: String,
+    new: String,
+}
+
+#[derive(RustcEncodable)]
+struct File
+{
+    path: String,
+    change: String,
+    class: String,
+}
+
+#[derive(RustcEncodable)]
+struct Text
+{
+    name: String,
+    lines: Vec<Line>,
+}
+
+#[derive(RustcEncodable)]
+struct Line
+{
+    text: String,
+    class: String,
+}
+
+impl Text
+{
+    fn new(name: String, diff: &str) -> Text
+    {
+        Text {
+            name: name,
+            lines: diff.lines().map(|line| Line {
+                text: String::from(line),
+                class: String::from(match line.chars().next() {
+                    _ if line.starts_with("---") || line.starts_with("+++") => "diff-header",
+                    Some('@') => "diff-hunk",
+                    Some('+') => "diff-added",
+                    Some('-') => "diff-removed",
+                    _ => "",
+                }),
+            }).collect(),
+        }
+    }
+}
+
+pub fn diff(req: &mut Request) -> IronResult<Response> {
+    let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+
+    let ref id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+    let ref base = req.extensions.get::<Router>().unwrap().find("base").unwrap();
+    let ref version = req.extensions.get::<Router>().unwrap().find("version").unwrap();
+
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let mut compared = Vec::new();
+    for requested in [base, version].iter() {
+        match PackageVersion::get(&*connection, id, &match NugetVersion::parse(requested) {
+            Ok(ver) => ver,
+            Err(_) => return Ok(Response::with((status::UnprocessableEntity, "Version value invalid"))),
+        }) {
+            Ok(pkgver) => compared.push(pkgver),
+            //most likely the package was not found (TODO match diesel Error as well)
+            Err(BackendError::DBError(_)) => return Ok(Response::with((status::NotFound, "Package not found"))),
+            Err(err) => {
+                error!("{:?}", err);
+                return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+            }
+        }
+    }
+
+    let versions = match Package::get(&*connection, id).and_then(|pkg| pkg.versions(&*connection)) {
+        Ok(versions) => versions.into_iter().sorted_by(|a, b| a.version().cmp(&b.version()).reverse())
+            .filter(|pkgver| pkgver.version() != compared[1].version())
+            .map(|pkgver| VersionOption {
+                version: format!("{}", pkgver.version()),
+                selected: pkgver.version() == compared[0].version(),
+            }).collect(),
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let diff = match PackageDiff::new(&*connection, storage, &compared[0], &compared[1]) {
+        Ok(diff) => diff,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Filesystem Error, please try again later")));
+        }
+    };
+
+    let (loggedin, username) = match req.extensions.get::<Authenticated>().unwrap() {
+        &(true, Some(ref username)) => (true, Some(username.clone())),
+        _ => (false, None),
+    };
+
+    let page = DiffPage {
+        loggedin: loggedin,
+        username: username,
+        unchanged: diff.metadata.is_empty() && diff.dependencies.is_empty() && diff.files.is_empty(),
+        metadata: diff.metadata.into_iter().map(|change| Change {
+            name: change.field,
+            old: change.old.unwrap_or(String::new()),
+            new: change.new.unwrap_or(String::new()),
+        }).collect(),
+        dependencies: diff.dependencies.into_iter().map(|change| Change {
+            name: change.id,
+            old: change.old.unwrap_or(String::new()),
+            new: change.new.unwrap_or(String::new()),
+        }).collect(),
+        files: diff.files.into_iter().map(|change| File {
+            path: change.path,
+            change: String::from(change.change),
+            class: String::from(match change.change {
+                "added" => "success",
+                "removed" => "danger",
+                _ => "warning",
+            }),
+        }).collect(),
+        texts: diff.texts.into_iter().map(|text| Text::new(text.name, &text.diff)).collect(),
+        package: diff.id,
+        base: diff.base,
+        version: diff.version,
+        versions: versions,
+    };
+
+    let mut buffer = Vec::new();
+    TEMPLATE.render(&mut buffer, &page).unwrap(); //TODO
+    Ok(Response::with((status::Ok, buffer, {
+        let mime: Mime = "text/html".parse().unwrap();
+        mime
+    })))
+}
@@ -0,0 +1,88 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult};
+use iron::status;
+use iron::mime::Mime;
+use persistent::Read;
+use diesel::connection::SimpleConnection;
+use rustc_serialize::json::{Json, ToJson};
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use ::web::server::{ConnectionPoolKey, StorageKey};
+use ::web::backend::db::pending_migrations;
+use ::utils::CONFIG;
+
+fn json_response(status: status::Status, body: Json) -> Response
+{
+    Response::with((status, body.to_string(), {
+        let mime: Mime = "application/json".parse().unwrap();
+        mime
+    }))
+}
+
+fn check(result: Result<(), String>) -> Json
+{
+    let mut object = BTreeMap::new();
+    match result {
+        Ok(()) => {
+            object.insert(String::from("status"), "ok".to_json());
+        },
+        Err(err) => {
+            object.insert(String::from("status"), "fail".to_json());
+            object.insert(String::from("error"), err.to_json());
+        },
+    };
+    Json::Object(object)
+}
+
+/// Liveness, answers as long as the process serves requests
+pub fn healthz(_: &mut Request) -> IronResult<Response> {
+    let mut object = BTreeMap::new();
+    object.insert(String::from("status"), "ok".to_json());
+    Ok(json_response(status::Ok, Json::Object(object)))
+}
+
+/// Readiness, checks everything needed to serve the feed
+pub fn readyz(req: &mut Request) -> IronResult<Response> {
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
+
+    let (database, migrations) = match connection_pool.get() {
+        Ok(connection) => (
+            connection.batch_execute("SELECT 1").map_err(|err| format!("{}", err)),
+            match pending_migrations(&*connection, &PathBuf::from(&*CONFIG.backend.migrations)) {
+                Ok(ref pending) if pending.is_empty() => Ok(()),
+                Ok(pending) => Err(format!("Pending migrations: {}", pending.join(", "))),
+                Err(err) => Err(format!("{}", err)),
+            }
+        ),
+        Err(err) => (Err(format!("{}", err)), Err(String::from("No database connection"))),
+    };
+    let storage = storage.check_writable().map_err(|err| format!("{}", err));
+
+    let ready = database.is_ok() && migrations.is_ok() && storage.is_ok();
+    let mut checks = BTreeMap::new();
+    checks.insert(String::from("database"), check(database));
+    checks.insert(String::from("migrations"), check(migrations));
+    checks.insert(String::from("storage"), check(storage));
+
+    let mut object = BTreeMap::new();
+    object.insert(String::from("status"), if ready { "ok" } else { "fail" }.to_json());
+    object.insert(String::from("checks"), Json::Object(checks));
+    Ok(json_response(if ready { status::Ok } else { status::ServiceUnavailable }, Json::Object(object)))
+}
@@ -27,6 +27,7 @@ use ::web::server::ConnectionPoolKey;
 use ::web::backend::db::{Package as Pkg, PackageVersion, User};
 use ::utils::middleware::Authenticated;
 use ::utils::CONFIG;
+use ::utils::config::live;
 
 lazy_static! {
     static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("index.html")).unwrap();
@@ -42,6 +43,23 @@ struct Index {
     open_for_registration: bool,
     confirmed: bool,
     api: Option<API>,
+    sso: Option<SSO>,
+}
+
+#[derive(RustcEncodable)]
+struct SSO
+{
+    name: String,
+}
+
+impl SSO
+{
+    fn configured() -> Option<SSO>
+    {
+        CONFIG.auth.oidc.as_ref().map(|config| SSO {
+            name: config.display_name.clone().unwrap_or(String::from("Single Sign-On")),
+        })
+    }
 }
 
 #[derive(RustcEncodable)]
@@ -70,8 +88,8 @@ impl From<PackageVersion> for Package {
     fn from(pkgver: PackageVersion) -> Package {
         Package {
             id: pkgver.id().to_owned(),
-            version: format!("{}", pkgver.version()),
-            icon: pkgver.icon_url.unwrap_or(String::new()),
+            version: String::from(pkgver.original_version()),
+            icon: format!("/packages/{}/{}/icon", pkgver.id(), pkgver.version()),
             title: pkgver.title.unwrap_or(String::new()),
             description: pkgver.summary.unwrap_or(String::new()),
         }
@@ -90,18 +108,19 @@ pub fn index(req: &mut Request) -> IronResult<Response> {
         }
     };
 
+    let live = live();
     let mut index = match req.extensions.get::<Authenticated>().unwrap() {
         &(true, Some(ref username)) => {
             match User::get(&*connection, username) {
-                Ok(user) => Index { repo: Vec::new(), pages: Vec::new(), loggedin: true, username: Some(user.name.clone()), open_for_registration: CONFIG.auth.open_for_registration,
-                    confirmation_required: CONFIG.auth.mail.is_some(), confirmed: user.confirmed(), api: match user.apikey() {
-                        Some(key) => Some(API { key: key, maxfilesize: CONFIG.web.max_upload_filesize_mb }),
+                Ok(user) => Index { repo: Vec::new(), pages: Vec::new(), loggedin: true, username: Some(user.name.clone()), open_for_registration: live.open_for_registration,
+                    confirmation_required: live.mail.is_some(), confirmed: user.confirmed(), api: match user.apikey() {
+                        Some(key) => Some(API { key: key, maxfilesize: live.max_upload_filesize_mb }),
                         None => None,
-                }},
+                }, sso: None },
                 Err(_) => return Ok(Response::with((status::Unauthorized, "User does not exist anymore"))),
             }
         },
-        _ => Index { repo: Vec::new(), pages: Vec::new(), loggedin: false, username: None, api: None, confirmed: false, confirmation_required: CONFIG.auth.mail.is_some(), open_for_registration: CONFIG.auth.open_for_registration },
+        _ => Index { repo: Vec::new(), pages: Vec::new(), loggedin: false, username: None, api: None, confirmed: false, confirmation_required: live.mail.is_some(), open_for_registration: live.open_for_registration, sso: SSO::configured() },
     };
 
     //TODO limit packages when we reach a high count and add helper for quicker updated retrieval
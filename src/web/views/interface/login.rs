@@ -14,21 +14,18 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use iron::{Request, Response, IronResult, Plugin};
-use iron::headers::SetCookie;
-use iron::modifiers::Header;
-use cookie::*;
 use iron::status::{self, Status};
 use persistent::{Read, Write};
-use uuid::Uuid;
 use params::{Params, Value};
-use chrono::*;
 
+use std::cmp;
 use std::str::FromStr;
 
 use ::web::backend::db::User;
-use ::web::server::{ConnectionPoolKey, SessionStoreKey};
+use ::web::server::{ConnectionPoolKey, ThrottleKey, MetricsKey};
 use ::utils::CONFIG;
-use ::utils::middleware::SessionInfo;
+use ::utils::middleware::{create_session, defer_session};
+use ::utils::throttle::{ThrottleTarget, too_many_requests};
 
 pub fn login(req: &mut Request) -> IronResult<Response> {
 
@@ -50,6 +47,16 @@ pub fn login(req: &mut Request) -> IronResult<Response> {
         _ => false
     };
 
+    let address = req.remote_addr.ip();
+    let throttle_mutex = req.extensions.get::<Write<ThrottleKey>>().unwrap().clone();
+    {
+        let throttle = throttle_mutex.lock().unwrap();
+        let locked = cmp::max(throttle.check(&ThrottleTarget::Account(username.clone())), throttle.check(&ThrottleTarget::Address(address)));
+        if let Some(retry_after) = locked {
+            return Ok(too_many_requests(retry_after));
+        }
+    }
+
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
     let connection = match connection_pool.get() {
         Ok(connection) => connection,
@@ -60,42 +67,37 @@ pub fn login(req: &mut Request) -> IronResult<Response> {
     };
 
     match User::login(&*connection, &username, &password) {
-        Ok(success) => {
-            if success {
-                let session_store_mutex = req.extensions.get::<Write<SessionStoreKey>>().unwrap().clone();
-                let mut session_store = session_store_mutex.lock().unwrap();
-                let session_id = Uuid::new_v4().simple().to_string();
-                let session_info = SessionInfo {
-                    expires: match remember {
-                        true  => UTC::now() + Duration::weeks(1),
-                        false => UTC::now() + Duration::hours(1),
-                    },
-                    session_id: session_id.clone(),
-                    remember: remember,
-                };
-                let root_jar = CookieJar::new(&*CONFIG.auth.cookie_key.as_bytes());
-                let jar = root_jar.encrypted();
-                let mut user_cookie = Cookie::new(String::from("hazel_username"), username.clone());
-                let mut session_cookie = Cookie::new(String::from("hazel_sessionid"), session_id);
-
-                session_cookie.max_age = Some((session_info.expires - UTC::now()).num_seconds() as u64);
-                user_cookie.max_age = Some((session_info.expires - UTC::now()).num_seconds() as u64);
-                session_cookie.path = Some(String::from("/"));
-                user_cookie.path = Some(String::from("/"));
-                session_cookie.domain = Some(req.url.host.to_string());
-                user_cookie.domain = Some(req.url.host.to_string());
-
-                jar.add(user_cookie);
-                jar.add(session_cookie);
-
-                session_store.insert(username, session_info);
-                Ok(Response::with((Status::Ok, Header(SetCookie::from_cookie_jar(&root_jar)), "success")))
+        Ok(true) => {
+            let user = match User::get(&*connection, &username) {
+                Ok(user) => user,
+                Err(err) => {
+                    error!("{:?}", err);
+                    return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+                }
+            };
+            //the session is only created after the second factor, the client continues with the returned token
+            if user.totp_enabled() {
+                let token = defer_session(req, username, remember, false);
+                Ok(Response::with((Status::Accepted, format!("totp:{}", token))))
+            } else if CONFIG.auth.require_totp && user.is_plainauth() {
+                let token = defer_session(req, username, remember, true);
+                Ok(Response::with((Status::Accepted, format!("enrol:{}", token))))
             } else {
-                Ok(Response::with(Status::Unauthorized))
+                throttle_mutex.lock().unwrap().success(&ThrottleTarget::Account(username.clone()));
+                let cookies = create_session(req, username, remember);
+                Ok(Response::with((Status::Ok, cookies, "success")))
             }
         },
-        Err(_) => {
-            Ok(Response::with((Status::Unauthorized)))
+        _ => {
+            info!(target: "hazel::security", "Failed login for \"{}\" from {}", username, address);
+            req.extensions.get::<Write<MetricsKey>>().unwrap().lock().unwrap().failed_login("password");
+            let mut throttle = throttle_mutex.lock().unwrap();
+            let account_lock = throttle.failure(ThrottleTarget::Account(username));
+            let address_lock = throttle.failure(ThrottleTarget::Address(address));
+            match cmp::max(account_lock, address_lock) {
+                Some(retry_after) => Ok(too_many_requests(retry_after)),
+                None => Ok(Response::with(Status::Unauthorized)),
+            }
         }
     }
 }
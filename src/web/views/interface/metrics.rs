@@ -0,0 +1,101 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult};
+use iron::status;
+use iron::mime::Mime;
+use iron::headers::{Authorization, Bearer};
+use persistent::{Read, Write};
+use crypto::digest::Digest;
+use crypto::mac::MacResult;
+use crypto::sha2::Sha256;
+
+use std::fmt::Write as FmtWrite;
+
+use ::web::server::{ConnectionPoolKey, MetricsKey};
+use ::web::backend::db::PackageVersion;
+use ::utils::CONFIG;
+use ::utils::metrics::escape_label;
+
+/// Compares the sha256 of both in constant time, so neither the token nor its length leak through timing
+fn token_matches(given: &str, token: &str) -> bool
+{
+    let hash = |value: &str| {
+        let mut hasher = Sha256::new();
+        hasher.input_str(value);
+        let mut result = vec![0; hasher.output_bytes()];
+        hasher.result(&mut result);
+        result
+    };
+    MacResult::new(&hash(given)) == MacResult::new(&hash(token))
+}
+
+pub fn metrics(req: &mut Request) -> IronResult<Response> {
+    if let Some(ref token) = CONFIG.server.metrics_token {
+        match req.headers.get::<Authorization<Bearer>>() {
+            Some(&Authorization(Bearer { token: ref given })) if token_matches(given, token) => {},
+            _ => return Ok(Response::with(status::Unauthorized)),
+        }
+    }
+
+    let mut body = String::new();
+    {
+        let metrics = req.extensions.get::<Write<MetricsKey>>().unwrap().clone();
+        metrics.lock().unwrap().render(&mut body);
+    }
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let state = connection_pool.state();
+    let _ = writeln!(body, "# HELP hazel_db_pool_connections Open database connections");
+    let _ = writeln!(body, "# TYPE hazel_db_pool_connections gauge");
+    let _ = writeln!(body, "hazel_db_pool_connections {}", state.connections);
+    let _ = writeln!(body, "# HELP hazel_db_pool_idle_connections Idle database connections");
+    let _ = writeln!(body, "# TYPE hazel_db_pool_idle_connections gauge");
+    let _ = writeln!(body, "hazel_db_pool_idle_connections {}", state.idle_connections);
+
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let ((versions, storage_bytes), downloads) = match PackageVersion::totals(&*connection)
+            .and_then(|totals| PackageVersion::downloads_per_package(&*connection).map(|downloads| (totals, downloads))) {
+        Ok(result) => result,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let _ = writeln!(body, "# HELP hazel_package_downloads_total Downloads of all versions of a package");
+    let _ = writeln!(body, "# TYPE hazel_package_downloads_total counter");
+    for &(ref id, count) in downloads.iter() {
+        let _ = writeln!(body, "hazel_package_downloads_total{{package=\"{}\"}} {}", escape_label(id), count);
+    }
+    let _ = writeln!(body, "# HELP hazel_package_versions Stored package versions");
+    let _ = writeln!(body, "# TYPE hazel_package_versions gauge");
+    let _ = writeln!(body, "hazel_package_versions {}", versions);
+    let _ = writeln!(body, "# HELP hazel_storage_bytes Size of all stored packages");
+    let _ = writeln!(body, "# TYPE hazel_storage_bytes gauge");
+    let _ = writeln!(body, "hazel_storage_bytes {}", storage_bytes);
+
+    Ok(Response::with((status::Ok, body, {
+        let mime: Mime = "text/plain; version=0.0.4".parse().unwrap();
+        mime
+    })))
+}
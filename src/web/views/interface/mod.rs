@@ -26,3 +26,15 @@ pub mod transfer;
 pub mod mail_confirmation;
 pub mod mail_resend;
 pub mod register;
+pub mod oidc;
+pub mod audit;
+pub mod storage;
+pub mod totp;
+pub mod metrics;
+pub mod health;
+pub mod webhooks;
+pub mod contents;
+pub mod diff;
+pub mod assets;
+pub mod symbols;
+pub mod deprecation;
@@ -0,0 +1,131 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult, Plugin, Url};
+use iron::status;
+use iron::modifiers::Redirect;
+use params::{Params, Value};
+use persistent::{Read, Write};
+use chrono::UTC;
+
+use std::str::FromStr;
+
+use ::web::backend::db::User;
+use ::web::backend::oidc;
+use ::web::server::{ConnectionPoolKey, OIDCStateStoreKey};
+use ::utils::error::{BackendError, LoginError, OIDCError};
+use ::utils::middleware::create_session;
+
+pub fn oidc_login(req: &mut Request) -> IronResult<Response> {
+
+    let params = req.get_ref::<Params>().unwrap().clone();
+
+    let remember = match params.find(&["remember"]) {
+        Some(&Value::String(ref rem)) => bool::from_str(rem).unwrap_or(false),
+        _ => false
+    };
+
+    let (url, state, pending) = match oidc::authorization_request(remember) {
+        Ok(x) => x,
+        Err(err) => {
+            error!("{}", err);
+            return Ok(Response::with((status::BadGateway, "Identity Provider not reachable, please try again later")));
+        }
+    };
+
+    {
+        let state_store_mutex = req.extensions.get::<Write<OIDCStateStoreKey>>().unwrap().clone();
+        let mut state_store = state_store_mutex.lock().unwrap();
+        //garbage collect abandoned attempts
+        let now = UTC::now();
+        let expired: Vec<String> = state_store.iter().filter(|&(_, pending)| pending.expires < now).map(|(state, _)| state.clone()).collect();
+        for state in expired {
+            state_store.remove(&state);
+        }
+        state_store.insert(state, pending);
+    }
+
+    match Url::parse(&url) {
+        Ok(url) => Ok(Response::with((status::TemporaryRedirect, Redirect(url)))),
+        Err(err) => {
+            error!("{}", err);
+            Ok(Response::with((status::BadGateway, "Identity Provider misconfigured")))
+        }
+    }
+}
+
+pub fn oidc_callback(req: &mut Request) -> IronResult<Response> {
+
+    let params = req.get_ref::<Params>().unwrap().clone();
+
+    if let Some(&Value::String(ref error)) = params.find(&["error"]) {
+        info!("OIDC login aborted by identity provider: {}", error);
+        return Ok(Response::with((status::Unauthorized, "Login aborted by Identity Provider")));
+    }
+    let code = match params.find(&["code"]) {
+        Some(&Value::String(ref code)) => code.clone(),
+        _ => return Ok(Response::with(status::BadRequest)),
+    };
+    let state = match params.find(&["state"]) {
+        Some(&Value::String(ref state)) => state.clone(),
+        _ => return Ok(Response::with(status::BadRequest)),
+    };
+
+    let pending = {
+        let state_store_mutex = req.extensions.get::<Write<OIDCStateStoreKey>>().unwrap().clone();
+        let mut state_store = state_store_mutex.lock().unwrap();
+        match state_store.remove(&state) {
+            Some(pending) => if pending.expires >= UTC::now() { pending } else {
+                return Ok(Response::with((status::Unauthorized, format!("{}", OIDCError::UnknownState))));
+            },
+            None => return Ok(Response::with((status::Unauthorized, format!("{}", OIDCError::UnknownState)))),
+        }
+    };
+
+    let identity = match oidc::exchange(&code, &pending) {
+        Ok(identity) => identity,
+        Err(err) => {
+            warn!("OIDC login failed: {}", err);
+            return Ok(Response::with(status::Unauthorized));
+        }
+    };
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    match User::oidc_login(&*connection, &identity) {
+        Ok(_) => {
+            let cookies = create_session(req, identity.username.clone(), pending.remember);
+            Ok(Response::with((status::TemporaryRedirect, cookies, Redirect({
+                let mut base = req.url.clone();
+                base.path = vec![String::from("index")];
+                base.query = None;
+                base
+            }))))
+        },
+        Err(BackendError::LoginError(LoginError::OIDCError(OIDCError::GroupNotAllowed))) => Ok(Response::with((status::Forbidden, format!("{}", OIDCError::GroupNotAllowed)))),
+        Err(BackendError::InvalidProviderForOP) => Ok(Response::with((status::Conflict, "A local account with this username already exists"))),
+        Err(err) => {
+            error!("{:?}", err);
+            Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
+        }
+    }
+}
@@ -29,7 +29,8 @@ use std::path::PathBuf;
 use ::web::server::ConnectionPoolKey;
 use ::utils::CONFIG;
 use ::utils::middleware::Authenticated;
-use ::web::backend::db::{User, Package, PackageVersion};
+use ::web::backend::db::{User, Package, PackageVersion, InstallScript, PackageAsset, Deprecation, DeprecationReason, Advisory, AdvisorySeverity};
+use ::web::backend::assets::{AssetKind, render_markdown};
 
 lazy_static! {
     static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("package.html")).unwrap();
@@ -40,6 +41,7 @@ struct Version
 {
     active: bool,
     version: String,
+    original_version: String,
     creation_date: String,
     title: Option<String>,
     summary: Option<String>,
@@ -51,6 +53,25 @@ struct Version
     hash_algorithm: Option<String>,
     size: i64,
     icon_url: Option<String>,
+    signed: bool,
+    signature_type: Option<String>,
+    signer: Option<String>,
+    signer_fingerprint: Option<String>,
+    scan_status: Option<String>,
+    scan_result: Option<String>,
+    quarantined: bool,
+    has_scripts: bool,
+    scripts: Vec<Script>,
+    readme: Option<String>,
+    has_license: bool,
+    deprecated: bool,
+    deprecation_reasons: Option<String>,
+    deprecation_message: Option<String>,
+    alternate_id: Option<String>,
+    alternate_range: Option<String>,
+    reasons: Vec<ReasonOption>,
+    has_advisories: bool,
+    advisories: Vec<AdvisoryEntry>,
 }
 
 impl From<PackageVersion> for Version {
@@ -58,6 +79,7 @@ impl From<PackageVersion> for Version {
         Version {
             active: false,
             version: format!("{}", pkgver.version()),
+            original_version: String::from(pkgver.original_version()),
             creation_date: format!("{}", pkgver.creation_date()),
             title: pkgver.title.clone(),
             summary: pkgver.summary.clone().map(|x| String::from(x.trim())),
@@ -68,11 +90,92 @@ impl From<PackageVersion> for Version {
             hash: pkgver.hash().cloned(),
             hash_algorithm: pkgver.hash_algorithm().cloned(),
             size: pkgver.byte_size(),
-            icon_url: pkgver.icon_url.clone(),
+            icon_url: Some(format!("/packages/{}/{}/icon", pkgver.id(), pkgver.version())),
+            signed: pkgver.is_signed(),
+            signature_type: pkgver.signature_type().cloned(),
+            signer: pkgver.signer().cloned(),
+            signer_fingerprint: pkgver.signer_fingerprint().cloned(),
+            scan_status: pkgver.scan_status().cloned(),
+            scan_result: pkgver.scan_result().cloned(),
+            quarantined: pkgver.is_quarantined(),
+            has_scripts: false,
+            scripts: Vec::new(),
+            readme: None,
+            has_license: false,
+            deprecated: false,
+            deprecation_reasons: None,
+            deprecation_message: None,
+            alternate_id: None,
+            alternate_range: None,
+            reasons: DeprecationReason::all().into_iter().map(|reason| ReasonOption { name: String::from(reason.as_str()), checked: false }).collect(),
+            has_advisories: false,
+            advisories: Vec::new(),
+        }
+    }
+}
+
+#[derive(RustcEncodable, Debug)]
+struct Finding
+{
+    line: usize,
+    message: String,
+    excerpt: String,
+}
+
+#[derive(RustcEncodable, Debug)]
+struct Script
+{
+    name: String,
+    content: String,
+    flagged: bool,
+    findings: Vec<Finding>,
+}
+
+impl From<InstallScript> for Script {
+    fn from(script: InstallScript) -> Script {
+        let findings: Vec<Finding> = script.findings().into_iter().map(|finding| Finding {
+            line: finding.line,
+            message: String::from(finding.message),
+            excerpt: finding.excerpt,
+        }).collect();
+        Script {
+            name: String::from(script.name()),
+            content: String::from(script.content()),
+            flagged: !findings.is_empty(),
+            findings: findings,
+        }
+    }
+}
+
+#[derive(RustcEncodable, Debug)]
+struct ReasonOption
+{
+    name: String,
+    checked: bool,
+}
+
+#[derive(RustcEncodable, Debug)]
+struct AdvisoryEntry
+{
+    url: String,
+    severity: String,
+}
+
+impl<'a> From<&'a Advisory> for AdvisoryEntry {
+    fn from(advisory: &'a Advisory) -> AdvisoryEntry {
+        AdvisoryEntry {
+            url: String::from(advisory.url()),
+            severity: String::from(advisory.severity().as_str()),
         }
     }
 }
 
+#[derive(RustcEncodable)]
+struct SeverityOption
+{
+    name: String,
+}
+
 #[derive(RustcEncodable)]
 struct API
 {
@@ -84,12 +187,21 @@ struct PackagePage
 {
     package: Package,
     versions: Vec<Version>,
+    previous: Option<String>,
     only_version: bool,
     loggedin: bool,
     username: Option<String>,
     is_maintainer: bool,
     api: Option<API>,
     edit: bool,
+    sso: Option<SSO>,
+    severities: Vec<SeverityOption>,
+}
+
+#[derive(RustcEncodable)]
+struct SSO
+{
+    name: String,
 }
 
 pub fn package_newestver(req: &mut Request) -> IronResult<Response> {
@@ -163,12 +275,54 @@ pub fn package(req: &mut Request) -> IronResult<Response> {
         }
     };
 
+    let (deprecations, advisories) = match (Deprecation::for_package(&*connection, pkg.id()), Advisory::for_package(&*connection, pkg.id())) {
+        (Ok(deprecations), Ok(advisories)) => (deprecations, advisories),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
     let versions: Vec<Version> = match pkg.versions(&*connection) {
         Ok(versions) => versions.into_iter().sorted_by(|a, b| a.version().cmp(&b.version()).reverse()).map(|x| {
+            //install scripts, readme and license are only shown for the selected version
+            let (scripts, readme, license): (Vec<Script>, Option<PackageAsset>, Option<PackageAsset>) = if format!("{}", x.version()) == *version {
+                let scripts = match InstallScript::for_version(&*connection, &x) {
+                    Ok(scripts) => scripts.into_iter().map(Script::from).collect(),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        Vec::new()
+                    },
+                };
+                let asset = |kind| PackageAsset::get(&*connection, &x, kind).unwrap_or_else(|err| {
+                    error!("{:?}", err);
+                    None
+                });
+                (scripts, asset(AssetKind::Readme), asset(AssetKind::License))
+            } else {
+                (Vec::new(), None, None)
+            };
             let mut ver = Version::from(x);
             if ver.version == *version {
                 ver.active = true;
             }
+            ver.has_scripts = !scripts.is_empty();
+            ver.scripts = scripts;
+            ver.readme = readme.as_ref().and_then(|readme| readme.content()).map(|content| render_markdown(&String::from_utf8_lossy(content)));
+            ver.has_license = license.is_some();
+            if let Some(deprecation) = deprecations.iter().find(|deprecation| deprecation.version() == ver.version) {
+                let reasons = deprecation.reasons();
+                ver.deprecated = true;
+                ver.deprecation_reasons = Some(reasons.iter().map(|reason| reason.as_str()).collect::<Vec<_>>().join(", "));
+                ver.deprecation_message = deprecation.message().cloned();
+                ver.alternate_id = deprecation.alternate_id().cloned();
+                ver.alternate_range = deprecation.alternate_range().and_then(|range| range.to_nuget());
+                for option in ver.reasons.iter_mut() {
+                    option.checked = reasons.iter().any(|reason| reason.as_str() == option.name);
+                }
+            }
+            ver.advisories = advisories.iter().filter(|advisory| advisory.version() == ver.version).map(AdvisoryEntry::from).collect();
+            ver.has_advisories = !ver.advisories.is_empty();
             ver
         }).collect(),
         Err(_) => return Ok(Response::with((status::InternalServerError, "Database Error, please try again later"))),
@@ -176,6 +330,8 @@ pub fn package(req: &mut Request) -> IronResult<Response> {
     debug!("{:?}", versions);
 
     let only_version = versions.len() == 1;
+    //versions are sorted newest first, the one below the active one is its predecessor
+    let previous = versions.iter().position(|ver| ver.active).and_then(|index| versions.get(index + 1)).map(|ver| ver.version.clone());
 
     let page = match req.extensions.get::<Authenticated>().unwrap() {
         &(true, Some(ref username)) => {
@@ -188,6 +344,7 @@ pub fn package(req: &mut Request) -> IronResult<Response> {
                     PackagePage {
                         package: pkg,
                         versions: versions,
+                        previous: previous,
                         only_version: only_version,
                         loggedin: true,
                         username: Some(username.clone()),
@@ -197,6 +354,8 @@ pub fn package(req: &mut Request) -> IronResult<Response> {
                             Some(key) => Some(API { key: key }),
                             None => None,
                         },
+                        sso: None,
+                        severities: AdvisorySeverity::all().iter().map(|severity| SeverityOption { name: String::from(severity.as_str()) }).collect(),
                     }
                 },
                 Err(_) => return Ok(Response::with((status::Unauthorized, "User does not exist anymore"))),
@@ -205,12 +364,17 @@ pub fn package(req: &mut Request) -> IronResult<Response> {
         _ => PackagePage {
             package: pkg,
             versions: versions,
+            previous: previous,
             only_version: only_version,
             loggedin: false,
             username: None,
             is_maintainer: false,
             edit: false,
             api: None,
+            sso: CONFIG.auth.oidc.as_ref().map(|config| SSO {
+                name: config.display_name.clone().unwrap_or(String::from("Single Sign-On")),
+            }),
+            severities: Vec::new(),
         },
     };
 
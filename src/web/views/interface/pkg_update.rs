@@ -18,8 +18,9 @@ use iron::status::{self, Status};
 use router::Router;
 use params::{Params, Value};
 use persistent::Read;
+use rustc_serialize::json::ToJson;
 
-use ::web::backend::db::{User, Package};
+use ::web::backend::db::{User, Package, AuditEvent, AuditAction, WebhookDelivery, WebhookEvent};
 use ::web::server::{ConnectionPoolKey, StorageKey};
 use ::utils::middleware::Authenticated;
 
@@ -65,6 +66,7 @@ pub fn pkg_update(req: &mut Request) -> IronResult<Response> {
         _ => None,
     };
 
+    let address = req.remote_addr.ip();
     let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
     let connection = match connection_pool.get() {
@@ -97,6 +99,7 @@ pub fn pkg_update(req: &mut Request) -> IronResult<Response> {
 
                         //not really the best rust syntax
                         //TODO style
+                        let before = pkg.to_json();
                         let mut flag = false;
                         if project_url.is_some() { pkg.project_url = project_url; flag = true; }
                         if license_url.is_some() { pkg.license_url = license_url; flag = true; }
@@ -108,7 +111,13 @@ pub fn pkg_update(req: &mut Request) -> IronResult<Response> {
                         if report_abuse_url.is_some() { pkg.report_abuse_url = report_abuse_url; flag = true; }
 
                         if flag { match pkg.update(&*connection, storage) {
-                            Ok(_) => Ok(Response::with(status::Ok)),
+                            Ok(pkg) => {
+                                AuditEvent::record(&*connection, &user, AuditAction::PackageUpdate, address, Some(pkg.id()), None, Some(before), Some(pkg.to_json()));
+                                if let Err(err) = WebhookDelivery::enqueue(&*connection, WebhookEvent::MetadataEdit, &user, pkg.id(), None, pkg.to_json()) {
+                                    error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                                }
+                                Ok(Response::with(status::Ok))
+                            },
                             //TODO match critical storage error (deletion)
                             Err(_) => Ok(Response::with((status::InternalServerError, "Database Error, please try again later"))),
                         } } else {
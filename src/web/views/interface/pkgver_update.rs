@@ -16,11 +16,12 @@
 use iron::{Request, Response, IronResult, Plugin};
 use iron::status::{self, Status};
 use router::Router;
-use semver::Version;
+use ::web::backend::version::NugetVersion;
 use params::{Params, Value};
 use persistent::Read;
+use rustc_serialize::json::ToJson;
 
-use ::web::backend::db::{User, PackageVersion};
+use ::web::backend::db::{User, PackageVersion, AuditEvent, AuditAction, WebhookDelivery, WebhookEvent};
 use ::web::server::{ConnectionPoolKey, StorageKey};
 use ::utils::error::BackendError;
 use ::utils::middleware::Authenticated;
@@ -51,6 +52,7 @@ pub fn pkgver_update(req: &mut Request) -> IronResult<Response> {
         _ => None,
     };
 
+    let address = req.remote_addr.ip();
     let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
     let connection = match connection_pool.get() {
@@ -61,7 +63,7 @@ pub fn pkgver_update(req: &mut Request) -> IronResult<Response> {
         }
     };
 
-    let mut pkgver = match PackageVersion::get(&*connection, &*id, &match Version::parse(&*version) {
+    let mut pkgver = match PackageVersion::get(&*connection, &*id, &match NugetVersion::parse(&*version) {
         Ok(ver) => ver,
         Err(_) => return Ok(Response::with((status::UnprocessableEntity, "Version value invalid"))),
     }) {
@@ -96,13 +98,20 @@ pub fn pkgver_update(req: &mut Request) -> IronResult<Response> {
 
                         //not really the best rust syntax
                         //TODO style
+                        let before = pkgver.to_json();
                         let mut flag = false;
                         if summary.is_some() { pkgver.summary = summary; flag = true; }
                         if description.is_some() { pkgver.description = description; flag = true; };
                         if release_notes.is_some() { pkgver.release_notes = release_notes; flag = true; };
 
                         if flag { match pkgver.update(&*connection, storage) {
-                            Ok(_) => Ok(Response::with(status::Ok)),
+                            Ok(pkgver) => {
+                                AuditEvent::record(&*connection, &user, AuditAction::VersionUpdate, address, Some(pkgver.id()), Some(&*version), Some(before), Some(pkgver.to_json()));
+                                if let Err(err) = WebhookDelivery::enqueue(&*connection, WebhookEvent::MetadataEdit, &user, pkgver.id(), Some(&*version), pkgver.to_json()) {
+                                    error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                                }
+                                Ok(Response::with(status::Ok))
+                            },
                             //TODO match critical storage error (deletion)
                             Err(_) => Ok(Response::with((status::InternalServerError, "Database Error, please try again later"))),
                         } } else {
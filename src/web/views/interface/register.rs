@@ -17,15 +17,16 @@ use iron::{Request, Response, IronResult, Plugin};
 use iron::status::{self, Status};
 use params::{Params, Value};
 use persistent::Read;
+use rustc_serialize::json::ToJson;
 
-use ::web::backend::db::User;
+use ::web::backend::db::{User, AuditEvent, AuditAction};
 use ::web::server::ConnectionPoolKey;
 use ::utils::error::BackendError;
-use ::utils::CONFIG;
+use ::utils::config::live;
 
 pub fn register(req: &mut Request) -> IronResult<Response> {
 
-    if !CONFIG.auth.open_for_registration {
+    if !live().open_for_registration {
         return Ok(Response::with(Status::BadRequest));
     }
 
@@ -48,6 +49,7 @@ pub fn register(req: &mut Request) -> IronResult<Response> {
         _ => return Ok(Response::with(Status::BadRequest)),
     };
 
+    let address = req.remote_addr.ip();
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
     let connection = match connection_pool.get() {
         Ok(connection) => connection,
@@ -58,7 +60,10 @@ pub fn register(req: &mut Request) -> IronResult<Response> {
     };
 
     match User::register(&*connection, username, fullname, mail, password) {
-        Ok(_) => Ok(Response::with(Status::Ok)),
+        Ok(user) => {
+            AuditEvent::record(&*connection, &user, AuditAction::UserRegister, address, None, None, None, Some(user.to_json()));
+            Ok(Response::with(Status::Ok))
+        },
         Err(BackendError::UserAlreadyExists) | Err(BackendError::DBError(_)) => Ok(Response::with((Status::BadRequest, "User already exists"))),
         Err(x) => { error!("{:?}", x); Ok(Response::with((Status::InternalServerError, "Please try again later"))) },
     }
@@ -0,0 +1,151 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult};
+use iron::status;
+use iron::mime::Mime;
+use persistent::Read;
+use mustache::{Template, compile_path};
+use lazysort::SortedBy;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use ::web::server::ConnectionPoolKey;
+use ::web::backend::db::{Package, PackageVersion};
+use ::utils::CONFIG;
+use ::utils::config::live;
+use super::audit::admin;
+
+lazy_static! {
+    static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("storage.html")).unwrap();
+}
+
+pub fn megabytes(bytes: i64) -> String
+{
+    format!("{:.1}", bytes as f64 / 1048576.0)
+}
+
+#[derive(RustcEncodable)]
+pub struct Quota
+{
+    limit: u64,
+    percent: u64,
+    exceeded: bool,
+}
+
+impl Quota
+{
+    pub fn new(used: i64, limit: Option<u64>) -> Option<Quota>
+    {
+        limit.map(|limit| {
+            let percent = if limit == 0 { 100 } else { (used as f64 / (limit as f64 * 1048576.0) * 100.0) as u64 };
+            Quota {
+                limit: limit,
+                percent: percent,
+                exceeded: percent >= 100,
+            }
+        })
+    }
+}
+
+#[derive(RustcEncodable)]
+struct StoragePage
+{
+    username: String,
+    total: String,
+    users: Vec<Usage>,
+    packages: Vec<Usage>,
+}
+
+#[derive(RustcEncodable)]
+struct Usage
+{
+    name: String,
+    maintainer: String,
+    versions: usize,
+    used: String,
+    quota: Option<Quota>,
+}
+
+pub fn storage(req: &mut Request) -> IronResult<Response> {
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let user = match admin(req, &*connection) {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let (packages, versions) = match (Package::all(&*connection), PackageVersion::all(&*connection)) {
+        (Ok(packages), Ok(versions)) => (packages, versions),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    //(versions, bytes) per package
+    let mut per_package = BTreeMap::new();
+    for version in versions.iter() {
+        let entry = per_package.entry(String::from(version.id())).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += version.byte_size();
+    }
+
+    let live = live();
+    let mut per_user = BTreeMap::new();
+    let mut package_usage = Vec::new();
+    for package in packages.iter() {
+        let (count, bytes) = per_package.get(package.id()).cloned().unwrap_or((0, 0));
+        let entry = per_user.entry(String::from(package.maintainer_id())).or_insert((0, 0));
+        entry.0 += count;
+        entry.1 += bytes;
+        package_usage.push((bytes, Usage {
+            name: String::from(package.id()),
+            maintainer: String::from(package.maintainer_id()),
+            versions: count,
+            used: megabytes(bytes),
+            quota: Quota::new(bytes, live.max_package_storage_mb),
+        }));
+    }
+    let user_usage = per_user.into_iter().map(|(name, (count, bytes))| (bytes, Usage {
+        name: name.clone(),
+        maintainer: name,
+        versions: count,
+        used: megabytes(bytes),
+        quota: Quota::new(bytes, live.max_user_storage_mb),
+    })).collect::<Vec<_>>();
+
+    let rendering = StoragePage {
+        username: user.name.clone(),
+        total: megabytes(versions.iter().fold(0, |sum, version| sum + version.byte_size())),
+        users: user_usage.into_iter().sorted_by(|a, b| b.0.cmp(&a.0)).map(|(_, usage)| usage).collect(),
+        packages: package_usage.into_iter().sorted_by(|a, b| b.0.cmp(&a.0)).map(|(_, usage)| usage).collect(),
+    };
+
+    let mut buffer = Vec::new();
+    TEMPLATE.render(&mut buffer, &rendering).unwrap();
+    Ok(Response::with((status::Ok, buffer, {
+        let mime: Mime = "text/html".parse().unwrap();
+        mime
+    })))
+}
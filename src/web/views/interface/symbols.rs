@@ -0,0 +1,85 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult};
+use iron::status;
+use iron::mime::Mime;
+use iron::headers::{CacheControl, CacheDirective};
+use persistent::Read;
+use router::Router;
+
+use ::web::server::{ConnectionPoolKey, StorageKey};
+use ::web::backend::db::SymbolFile;
+use ::web::backend::symbols::parse_signature;
+
+/// Symbols are addressed by their debug id, they never change
+const MAX_AGE_SECS: u32 = 30 * 24 * 60 * 60;
+
+/// Symbol server following the simple symbol query protocol, `/symbols/<file>/<signature>/<file>`
+pub fn symbol(req: &mut Request) -> IronResult<Response> {
+    let storage = req.extensions.get::<Read<StorageKey>>().unwrap();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+
+    let ref file = req.extensions.get::<Router>().unwrap().find("file").unwrap();
+    let ref signature = req.extensions.get::<Router>().unwrap().find("signature").unwrap();
+    let ref name = req.extensions.get::<Router>().unwrap().find("name").unwrap();
+
+    let signature = match parse_signature(signature) {
+        Some(signature) if file.to_lowercase() == name.to_lowercase() => signature,
+        _ => return Ok(Response::with((status::NotFound, "Symbol not found"))),
+    };
+
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let symbol = match SymbolFile::find(&*connection, file, &signature) {
+        Ok(Some(symbol)) => symbol,
+        Ok(None) => return Ok(Response::with((status::NotFound, "Symbol not found"))),
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+    let pkgver = match symbol.package_version(&*connection) {
+        //quarantined versions are hidden like in the feed
+        Ok(ref pkgver) if pkgver.is_quarantined() => return Ok(Response::with((status::NotFound, "Symbol not found"))),
+        Ok(pkgver) => pkgver,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    match symbol.read(storage, &pkgver) {
+        Ok(Some(content)) => {
+            let mut response = Response::with((status::Ok, content, {
+                let mime: Mime = "application/octet-stream".parse().unwrap();
+                mime
+            }));
+            response.headers.set(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(MAX_AGE_SECS)]));
+            Ok(response)
+        },
+        Ok(None) => Ok(Response::with((status::NotFound, "Symbol not found"))),
+        Err(err) => {
+            error!("{:?}", err);
+            Ok(Response::with((status::InternalServerError, "Filesystem Error, please try again later")))
+        },
+    }
+}
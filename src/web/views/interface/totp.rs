@@ -0,0 +1,289 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult, Plugin, Set};
+use iron::status;
+use iron::mime::Mime;
+use iron::modifiers::Redirect;
+use params::{Params, Value, Map};
+use persistent::{Read, Write};
+use mustache::{Template, compile_path};
+use rustc_serialize::json::ToJson;
+
+use std::cmp;
+use std::path::PathBuf;
+
+use ::web::server::{ConnectionPoolKey, ThrottleKey, MetricsKey};
+use ::web::backend::db::{User, AuditEvent, AuditAction};
+use ::utils::CONFIG;
+use ::utils::error::{BackendError, LoginError};
+use ::utils::middleware::{Authenticated, pending_second_factor, finish_second_factor};
+use ::utils::throttle::{ThrottleTarget, too_many_requests};
+use ::utils::totp;
+
+lazy_static! {
+    static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("totp.html")).unwrap();
+}
+
+#[derive(RustcEncodable)]
+struct EnrolmentPage
+{
+    username: String,
+    secret: String,
+    uri: String,
+    token: Option<String>,
+}
+
+fn param(params: &Map, name: &str) -> Option<String>
+{
+    match params.find(&[name]) {
+        Some(&Value::String(ref value)) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Username of the session, or of a pending login that has to enrol before getting a session
+fn enrolling_user(req: &Request, token: &Option<String>) -> Option<String>
+{
+    match req.extensions.get::<Authenticated>().unwrap() {
+        &(true, Some(ref username)) => Some(username.clone()),
+        _ => token.as_ref()
+                  .and_then(|token| pending_second_factor(req, token))
+                  .and_then(|pending| if pending.enrolment { Some(pending.username) } else { None }),
+    }
+}
+
+pub fn totp_login(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+    let token = match param(&params, "token") {
+        Some(token) => token,
+        None => return Ok(Response::with(status::BadRequest)),
+    };
+    let code = match param(&params, "code") {
+        Some(code) => code,
+        None => return Ok(Response::with(status::BadRequest)),
+    };
+
+    let pending = match pending_second_factor(req, &token) {
+        Some(ref pending) if !pending.enrolment => pending.clone(),
+        _ => return Ok(Response::with((status::Unauthorized, "Login expired, please sign in again"))),
+    };
+
+    let address = req.remote_addr.ip();
+    let throttle_mutex = req.extensions.get::<Write<ThrottleKey>>().unwrap().clone();
+    {
+        let throttle = throttle_mutex.lock().unwrap();
+        let locked = cmp::max(throttle.check(&ThrottleTarget::Account(pending.username.clone())), throttle.check(&ThrottleTarget::Address(address)));
+        if let Some(retry_after) = locked {
+            return Ok(too_many_requests(retry_after));
+        }
+    }
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let verified = match User::get(&*connection, &pending.username) {
+        Ok(mut user) => user.verify_second_factor(&*connection, &code),
+        Err(err) => Err(err),
+    };
+
+    match verified {
+        Ok(true) => {
+            throttle_mutex.lock().unwrap().success(&ThrottleTarget::Account(pending.username.clone()));
+            match finish_second_factor(req, &token) {
+                Some(cookies) => Ok(Response::with((status::Ok, cookies, "success"))),
+                None => Ok(Response::with((status::Unauthorized, "Login expired, please sign in again"))),
+            }
+        },
+        Ok(false) => {
+            info!(target: "hazel::security", "Invalid second factor for \"{}\" from {}", pending.username, address);
+            req.extensions.get::<Write<MetricsKey>>().unwrap().lock().unwrap().failed_login("totp");
+            let mut throttle = throttle_mutex.lock().unwrap();
+            let account_lock = throttle.failure(ThrottleTarget::Account(pending.username));
+            let address_lock = throttle.failure(ThrottleTarget::Address(address));
+            match cmp::max(account_lock, address_lock) {
+                Some(retry_after) => Ok(too_many_requests(retry_after)),
+                None => Ok(Response::with((status::Unauthorized, "Invalid code"))),
+            }
+        },
+        Err(err) => {
+            error!("{:?}", err);
+            Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
+        }
+    }
+}
+
+pub fn totp_enrol_page(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+    let token = param(&params, "token");
+
+    let username = match enrolling_user(req, &token) {
+        Some(username) => username,
+        None => return Ok(Response::with(status::Unauthorized)),
+    };
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    match User::get(&*connection, &username) {
+        Ok(user) => {
+            if !user.is_plainauth() {
+                return Ok(Response::with((status::Conflict, "Two-factor authentication is handled by your login provider")));
+            }
+            if user.totp_enabled() {
+                return Ok(Response::with((status::TemporaryRedirect, Redirect({
+                    let mut base = req.url.clone();
+                    base.path = vec![String::from("user")];
+                    base.query = None;
+                    base
+                }))));
+            }
+        },
+        Err(_) => return Ok(Response::with((status::Unauthorized, "User does not exist anymore"))),
+    };
+
+    let secret = totp::generate_secret();
+    let rendering = EnrolmentPage {
+        uri: totp::provisioning_uri(&username, &secret),
+        username: username,
+        secret: secret,
+        token: token,
+    };
+
+    let mut buffer = Vec::new();
+    TEMPLATE.render(&mut buffer, &rendering).unwrap();
+    Ok(Response::with((status::Ok, buffer, {
+        let mime: Mime = "text/html".parse().unwrap();
+        mime
+    })))
+}
+
+pub fn totp_enrol(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+    let token = param(&params, "token");
+    let secret = match param(&params, "secret") {
+        Some(secret) => secret,
+        None => return Ok(Response::with(status::BadRequest)),
+    };
+    let code = match param(&params, "code") {
+        Some(code) => code,
+        None => return Ok(Response::with(status::BadRequest)),
+    };
+
+    let username = match enrolling_user(req, &token) {
+        Some(username) => username,
+        None => return Ok(Response::with(status::Unauthorized)),
+    };
+
+    let address = req.remote_addr.ip();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let mut user = match User::get(&*connection, &username) {
+        Ok(user) => user,
+        Err(_) => return Ok(Response::with((status::Unauthorized, "User does not exist anymore"))),
+    };
+    if user.totp_enabled() {
+        return Ok(Response::with((status::Conflict, "Two-factor authentication is already enabled")));
+    }
+    let before = user.to_json();
+
+    match user.enable_totp(&*connection, secret, &code) {
+        Ok(recovery_codes) => {
+            AuditEvent::record(&*connection, &user, AuditAction::TotpEnable, address, None, None, Some(before), Some(user.to_json()));
+            let mut response = Response::with((status::Ok, recovery_codes.join("\n")));
+            //an enforced enrolment completes the login
+            if let Some(ref token) = token {
+                if let Some(cookies) = finish_second_factor(req, token) {
+                    response = response.set(cookies);
+                }
+            }
+            Ok(response)
+        },
+        Err(BackendError::LoginError(LoginError::InvalidSecondFactor)) => Ok(Response::with((status::BadRequest, "Invalid code, please check the time of your device"))),
+        Err(BackendError::InvalidProviderForOP) => Ok(Response::with((status::Conflict, "Two-factor authentication is handled by your login provider"))),
+        Err(err) => {
+            error!("{:?}", err);
+            Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
+        }
+    }
+}
+
+pub fn totp_disable(req: &mut Request) -> IronResult<Response> {
+    if CONFIG.auth.require_totp {
+        return Ok(Response::with((status::Forbidden, "Two-factor authentication is required on this server")));
+    }
+
+    let params = req.get_ref::<Params>().unwrap().clone();
+    let code = match param(&params, "code") {
+        Some(code) => code,
+        None => return Ok(Response::with(status::BadRequest)),
+    };
+
+    let address = req.remote_addr.ip();
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    match req.extensions.get::<Authenticated>().unwrap() {
+        &(true, Some(ref username)) => match User::get(&*connection, username) {
+            Ok(mut user) => {
+                let before = user.to_json();
+                match user.verify_second_factor(&*connection, &code) {
+                    Ok(true) => match user.disable_totp(&*connection) {
+                        Ok(user) => {
+                            AuditEvent::record(&*connection, &user, AuditAction::TotpDisable, address, None, None, Some(before), Some(user.to_json()));
+                            Ok(Response::with(status::Ok))
+                        },
+                        Err(err) => {
+                            error!("{:?}", err);
+                            Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
+                        }
+                    },
+                    Ok(false) => Ok(Response::with((status::BadRequest, "Invalid code"))),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
+                    }
+                }
+            },
+            Err(_) => Ok(Response::with((status::Unauthorized, "User does not exist anymore"))),
+        },
+        _ => Ok(Response::with(status::Unauthorized)),
+    }
+}
@@ -16,18 +16,20 @@
 use iron::{Request, Response, IronResult};
 use iron::status;
 use persistent::Read;
+use rustc_serialize::json::ToJson;
 use plugin::Pluggable;
 use router::Router;
 use ::utils::error::BackendError;
 
 use ::web::server::ConnectionPoolKey;
 use ::utils::middleware::Authenticated;
-use ::web::backend::db::{User, Package};
+use ::web::backend::db::{User, Package, AuditEvent, AuditAction, WebhookDelivery, WebhookEvent};
 
 pub fn transfer(req: &mut Request) -> IronResult<Response> {
     let ref id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
     let ref new_owner = req.extensions.get::<Router>().unwrap().find("new_maintainer").unwrap();
 
+    let address = req.remote_addr.ip();
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
     let connection = match connection_pool.get() {
         Ok(connection) => connection,
@@ -61,8 +63,15 @@ pub fn transfer(req: &mut Request) -> IronResult<Response> {
                         match User::get(&*connection, &String::from(*new_owner)) {
                             Ok(new_maintainer) =>
                                 if new_maintainer.confirmed() {
+                                    let before = pkg.to_json();
                                     match pkg.update_maintainer(&*connection, &new_maintainer) {
-                                        Ok(_) => Ok(Response::with(status::Ok)),
+                                        Ok(pkg) => {
+                                            AuditEvent::record(&*connection, &user, AuditAction::Transfer, address, Some(pkg.id()), None, Some(before), Some(pkg.to_json()));
+                                            if let Err(err) = WebhookDelivery::enqueue(&*connection, WebhookEvent::Transfer, &user, pkg.id(), None, pkg.to_json()) {
+                                                error!(target: "hazel::webhook", "Failed to queue webhooks: {:?}", err);
+                                            }
+                                            Ok(Response::with(status::Ok))
+                                        },
                                         Err(err) => {
                                             error!("{:?}", err);
                                             Ok(Response::with((status::InternalServerError, "Transfer failed")))
@@ -25,6 +25,8 @@ use ::web::server::ConnectionPoolKey;
 use ::utils::CONFIG;
 use ::utils::middleware::Authenticated;
 use ::web::backend::db::User;
+use ::utils::config::live;
+use super::storage::{megabytes, Quota};
 
 lazy_static! {
     static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("user.html")).unwrap();
@@ -37,6 +39,11 @@ struct UserPage
     mail: bool,
     edit: bool,
     plainauth: bool,
+    admin: bool,
+    totp: bool,
+    totp_optional: bool,
+    storage_used: String,
+    storage_quota: Option<Quota>,
 }
 
 pub fn user(req: &mut Request) -> IronResult<Response> {
@@ -61,6 +68,15 @@ pub fn user(req: &mut Request) -> IronResult<Response> {
 
     let plain = user.is_plainauth();
     let mail = user.mail().is_some();
+    let admin = user.is_admin();
+    let totp = user.totp_enabled();
+    let storage_used = match user.storage_usage(&*connection) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
 
     let rendering = UserPage {
         user: user,
@@ -70,6 +86,11 @@ pub fn user(req: &mut Request) -> IronResult<Response> {
             _ => false,
         },
         plainauth: plain,
+        admin: admin,
+        totp: totp,
+        totp_optional: !CONFIG.auth.require_totp,
+        storage_used: megabytes(storage_used),
+        storage_quota: Quota::new(storage_used, live().max_user_storage_mb),
     };
 
     let mut buffer = Vec::new();
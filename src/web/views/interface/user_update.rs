@@ -17,8 +17,9 @@ use iron::{Request, Response, IronResult, Plugin};
 use iron::status::{self, Status};
 use params::{Params, Value};
 use persistent::Read;
+use rustc_serialize::json::ToJson;
 
-use ::web::backend::db::User;
+use ::web::backend::db::{User, AuditEvent, AuditAction};
 use ::web::server::ConnectionPoolKey;
 use ::utils::middleware::Authenticated;
 
@@ -39,6 +40,7 @@ pub fn update(req: &mut Request) -> IronResult<Response> {
         _ => None
     };
 
+    let address = req.remote_addr.ip();
     let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
     let connection = match connection_pool.get() {
         Ok(connection) => connection,
@@ -53,6 +55,7 @@ pub fn update(req: &mut Request) -> IronResult<Response> {
         &(true, Some(ref username)) =>
             match User::get(&*connection, username) {
                 Ok(mut user) => {
+                    let before = user.to_json();
                     user.name = fullname;
                     if password.is_some() {
                         if user.update_pass(&*connection, password.unwrap()).is_err() {
@@ -67,7 +70,10 @@ pub fn update(req: &mut Request) -> IronResult<Response> {
                         }
                     }
                     match user.update(&*connection) {
-                        Ok(_) => Ok(Response::with(Status::Ok)),
+                        Ok(user) => {
+                            AuditEvent::record(&*connection, &user, AuditAction::UserUpdate, address, None, None, Some(before), Some(user.to_json()));
+                            Ok(Response::with(Status::Ok))
+                        },
                         _ => Ok(Response::with(Status::InternalServerError)),
                     }
                 },
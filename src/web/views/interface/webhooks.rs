@@ -0,0 +1,273 @@
+// Copyright (C) 2016  Max Planck Institute for Human Development
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use iron::{Request, Response, IronResult, Plugin};
+use iron::status;
+use iron::mime::Mime;
+use iron::modifiers::Redirect;
+use params::{Params, Value};
+use persistent::Read;
+use router::Router;
+use mustache::{Template, compile_path};
+use diesel::pg::PgConnection;
+use url::Url;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use ::web::server::ConnectionPoolKey;
+use ::web::backend::db::{User, Package, Webhook, WebhookDelivery, WebhookEvent};
+use ::web::backend::webhook;
+use ::utils::error::BackendError;
+use ::utils::middleware::Authenticated;
+use ::utils::CONFIG;
+
+lazy_static! {
+    static ref TEMPLATE: Template = compile_path(PathBuf::from(CONFIG.web.resources.clone()).join("webhooks.html")).unwrap();
+}
+
+const DELIVERY_LOG_SIZE: i64 = 50;
+
+#[derive(RustcEncodable)]
+struct WebhooksPage
+{
+    username: String,
+    package: String,
+    webhooks: Vec<Hook>,
+    events: Vec<EventOption>,
+    deliveries: Vec<Delivery>,
+}
+
+#[derive(RustcEncodable)]
+struct Hook
+{
+    id: i64,
+    url: String,
+    events: String,
+    signed: bool,
+}
+
+#[derive(RustcEncodable)]
+struct EventOption
+{
+    name: String,
+}
+
+#[derive(RustcEncodable)]
+struct Delivery
+{
+    created: String,
+    event: String,
+    version: String,
+    url: String,
+    attempts: i32,
+    state: String,
+    delivered: bool,
+    response_status: String,
+    last_error: String,
+}
+
+impl From<WebhookDelivery> for Delivery
+{
+    fn from(delivery: WebhookDelivery) -> Delivery
+    {
+        Delivery {
+            created: format!("{}", delivery.created().format("%Y-%m-%d %H:%M:%S")),
+            event: String::from(delivery.event()),
+            version: delivery.package_version().cloned().unwrap_or(String::new()),
+            url: String::from(delivery.url()),
+            attempts: delivery.attempts(),
+            state: match (delivery.delivered(), delivery.next_attempt()) {
+                (true, _) => String::from("delivered"),
+                (false, Some(next_attempt)) => format!("retrying at {}", next_attempt.format("%Y-%m-%d %H:%M:%S")),
+                (false, None) => String::from("failed"),
+            },
+            delivered: delivery.delivered(),
+            response_status: delivery.response_status().map(|status| format!("{}", status)).unwrap_or(String::new()),
+            last_error: delivery.last_error().cloned().unwrap_or(String::new()),
+        }
+    }
+}
+
+/// Returns the logged in user and the package, if the user maintains it or is an administrator
+fn maintained_package(req: &Request, connection: &PgConnection) -> Result<(User, Package), Response>
+{
+    let id = match req.extensions.get::<Router>().unwrap().find("id") {
+        Some(id) => String::from(id),
+        None => return Err(Response::with(status::BadRequest)),
+    };
+
+    let user = match req.extensions.get::<Authenticated>().unwrap() {
+        &(true, Some(ref username)) => match User::get(connection, username) {
+            Ok(user) => user,
+            Err(_) => return Err(Response::with((status::Unauthorized, "User does not exist anymore"))),
+        },
+        _ => return Err(Response::with(status::Unauthorized)),
+    };
+
+    let package = match Package::get(connection, &id) {
+        Ok(package) => package,
+        //most likely the package was not found (TODO match diesel Error as well)
+        Err(BackendError::DBError(_)) => return Err(Response::with((status::NotFound, "Package not found"))),
+        Err(err) => {
+            error!("{:?}", err);
+            return Err(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    match package.maintainer(connection) {
+        Ok(maintainer) => if maintainer == user || user.is_admin() {
+            Ok((user, package))
+        } else {
+            Err(Response::with((status::Forbidden, "You are not the maintainer of the requested package.")))
+        },
+        Err(_) => Err(Response::with((status::InternalServerError, "Database Error, please try again later"))),
+    }
+}
+
+fn back_to_webhooks(req: &Request, package: &Package) -> Response
+{
+    Response::with((status::SeeOther, Redirect({
+        let mut base = req.url.clone();
+        base.path = vec![String::from("packages"), String::from(package.id()), String::from("webhooks")];
+        base.query = None;
+        base
+    })))
+}
+
+pub fn webhooks(req: &mut Request) -> IronResult<Response> {
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (user, package) = match maintained_package(req, &*connection) {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    //deliveries of global webhooks may reveal urls of other services, so only admins see them
+    let (webhooks, deliveries) = match (Webhook::for_package(&*connection, package.id()),
+                                        WebhookDelivery::for_package(&*connection, package.id(), user.is_admin(), DELIVERY_LOG_SIZE)) {
+        (Ok(webhooks), Ok(deliveries)) => (webhooks, deliveries),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let rendering = WebhooksPage {
+        username: user.name.clone(),
+        package: String::from(package.id()),
+        webhooks: webhooks.into_iter().map(|webhook| Hook {
+            id: webhook.id(),
+            url: String::from(webhook.url()),
+            events: webhook.events().iter().map(|event| event.as_str()).collect::<Vec<_>>().join(", "),
+            signed: webhook.has_secret(),
+        }).collect(),
+        events: WebhookEvent::all().iter().map(|event| EventOption { name: String::from(event.as_str()) }).collect(),
+        deliveries: deliveries.into_iter().map(Delivery::from).collect(),
+    };
+
+    let mut buffer = Vec::new();
+    TEMPLATE.render(&mut buffer, &rendering).unwrap();
+    Ok(Response::with((status::Ok, buffer, {
+        let mime: Mime = "text/html".parse().unwrap();
+        mime
+    })))
+}
+
+pub fn webhook_create(req: &mut Request) -> IronResult<Response> {
+    let params = req.get_ref::<Params>().unwrap().clone();
+
+    let url = match params.find(&["url"]) {
+        Some(&Value::String(ref url)) => match Url::parse(url.trim()) {
+            Ok(ref parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => String::from(url.trim()),
+            _ => return Ok(Response::with((status::BadRequest, "The webhook url has to be an absolute http or https url"))),
+        },
+        _ => return Ok(Response::with((status::BadRequest, "No webhook url given"))),
+    };
+    if let Err(err) = webhook::check_target(&url, &CONFIG.backend.webhook_allowed_hosts) {
+        return Ok(Response::with((status::BadRequest, format!("The webhook url is not allowed: {}", err))));
+    }
+    let secret = match params.find(&["secret"]) {
+        Some(&Value::String(ref secret)) if !secret.is_empty() => Some(secret.clone()),
+        _ => None,
+    };
+    //every event is a checkbox named after it, none checked subscribes to all of them
+    let events = WebhookEvent::all().into_iter().filter(|event| match params.find(&[event.as_str()]) {
+        Some(&Value::String(ref value)) => value == "on",
+        _ => false,
+    }).collect::<Vec<_>>();
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (user, package) = match maintained_package(req, &*connection) {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    match Webhook::new(&*connection, &package, &user, url, secret, &events) {
+        Ok(_) => Ok(back_to_webhooks(req, &package)),
+        Err(err) => {
+            error!("{:?}", err);
+            Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
+        }
+    }
+}
+
+pub fn webhook_delete(req: &mut Request) -> IronResult<Response> {
+    let webhook_id = match req.extensions.get::<Router>().unwrap().find("webhook").map(|id| i64::from_str(id)) {
+        Some(Ok(id)) => id,
+        _ => return Ok(Response::with(status::BadRequest)),
+    };
+
+    let connection_pool = req.extensions.get::<Read<ConnectionPoolKey>>().unwrap();
+    let connection = match connection_pool.get() {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            return Ok(Response::with((status::InternalServerError, "Database Error, please try again later")));
+        }
+    };
+
+    let (_, package) = match maintained_package(req, &*connection) {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    match Webhook::get(&*connection, webhook_id) {
+        Ok(ref webhook) if webhook.package_id() != package.id() => Ok(Response::with((status::NotFound, "Webhook not found"))),
+        Ok(webhook) => match webhook.delete(&*connection) {
+            Ok(()) => Ok(back_to_webhooks(req, &package)),
+            Err(err) => {
+                error!("{:?}", err);
+                Ok(Response::with((status::InternalServerError, "Database Error, please try again later")))
+            }
+        },
+        Err(_) => Ok(Response::with((status::NotFound, "Webhook not found"))),
+    }
+}